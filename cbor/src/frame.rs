@@ -1,8 +1,10 @@
-use bytes::{BufMut, BytesMut};
+use std::io;
+
+use bytes::{Buf, BufMut, BytesMut};
 use sud_core::Event;
-use tokio_util::codec::Encoder;
+use tokio_util::codec::{Decoder, Encoder};
 
-use crate::CborEncoder;
+use crate::{parse::Reader, CborEncoder};
 
 impl<'a> Encoder<Event<'a>> for CborEncoder {
     type Error = std::io::Error;
@@ -11,3 +13,190 @@ impl<'a> Encoder<Event<'a>> for CborEncoder {
         self.write(item, dst.writer())
     }
 }
+
+/// Decodes a stream of CBOR items into events.
+///
+/// Each event is returned as soon as its bytes have arrived; an item that is
+/// cut short stays in the buffer until the rest of it is read. The number of
+/// items left in each definite length container carries over between calls, so
+/// their `SeqEnd` and `MapEnd` are emitted without waiting for more input.
+/// Any number of top level items can follow each other in the stream.
+///
+/// Malformed items are returned as [`io::ErrorKind::InvalidData`] wrapping a
+/// [`ParseError`](crate::ParseError).
+#[derive(Default)]
+pub struct CborDecoder {
+    reader: Reader,
+}
+
+impl CborDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn decode_event(
+        &mut self,
+        src: &mut BytesMut,
+        eof: bool,
+    ) -> io::Result<Option<Event<'static>>> {
+        self.reader.reset();
+
+        let mut pos = 0;
+        let res = if eof && src.is_empty() && self.reader.at_boundary() {
+            Ok(None)
+        } else {
+            self.reader
+                .next(src, &mut pos, eof)
+                .map(|event| event.map(Event::into_static))
+        };
+
+        src.advance(pos);
+        self.reader.advance(pos);
+        res.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+impl Decoder for CborDecoder {
+    type Item = Event<'static>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_event(src, false)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_event(src, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use futures_util::StreamExt;
+    use sud_core::{Atom, Event};
+    use tokio_util::codec::{Decoder, FramedRead};
+
+    use super::CborDecoder;
+    use crate::{CborParser, ErrorKind, ParseError};
+
+    /// `{"a": [1, 2^32, -1000, 1.5], "b": h'00 * 40', "c": [_ (_ "ab" "c")], "d": []}`
+    const DOC: &str = concat!(
+        "a4",
+        "6161",
+        "84011b00000001000000003903e7f93e00",
+        "6162",
+        "5828",
+        "00000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        "6163",
+        "9f7f6261626163ffff",
+        "6164",
+        "80",
+    );
+
+    /// Feeds the input in chunks of `size` bytes, collecting every event.
+    fn decode_chunked(input: &[u8], size: usize) -> Result<Vec<Event<'static>>, std::io::Error> {
+        let mut decoder = CborDecoder::new();
+        let mut buf = BytesMut::new();
+        let mut events = Vec::new();
+        for chunk in input.chunks(size) {
+            buf.extend_from_slice(chunk);
+            while let Some(event) = decoder.decode(&mut buf)? {
+                events.push(event);
+            }
+        }
+        while let Some(event) = decoder.decode_eof(&mut buf)? {
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    #[test]
+    fn chunked() {
+        let input = hex::decode(DOC).unwrap();
+        let expected: Vec<_> = CborParser::new(&input)
+            .map(|event| event.unwrap().into_static())
+            .collect();
+
+        for size in 1..=input.len() {
+            assert_eq!(
+                decode_chunked(&input, size).unwrap(),
+                expected,
+                "chunk size {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn eager() {
+        let mut decoder = CborDecoder::new();
+        // [1, 2^32, h'0102...
+        let mut buf = BytesMut::from(&hex::decode("83011b0000").unwrap()[..]);
+
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap(),
+            Some(Event::SeqStart(Some(3)))
+        );
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap(),
+            Some(Event::Atom(Atom::U64(1)))
+        );
+        // the header is kept in the buffer until its argument is complete
+        assert_eq!(decoder.decode(&mut buf).unwrap(), None);
+        assert_eq!(&buf[..], [0x1b, 0, 0]);
+
+        buf.extend_from_slice(&hex::decode("000100000000430102").unwrap());
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap(),
+            Some(Event::Atom(Atom::U64(1 << 32)))
+        );
+        assert_eq!(decoder.decode(&mut buf).unwrap(), None);
+        assert_eq!(&buf[..], [0x43, 1, 2]);
+
+        buf.extend_from_slice(&[3]);
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap(),
+            Some(Event::from(&[1, 2, 3][..]))
+        );
+        // the array is complete, so it ends without any more input
+        assert!(buf.is_empty());
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(Event::SeqEnd));
+        assert_eq!(decoder.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn errors_have_stream_positions() {
+        let err = decode_chunked(&hex::decode("8201a2016161ff").unwrap(), 2).unwrap_err();
+        let err = err.get_ref().unwrap().downcast_ref::<ParseError>().unwrap();
+        assert_eq!((*err.kind(), err.offset()), (ErrorKind::UnexpectedBreak, 6));
+
+        let err = decode_chunked(&hex::decode("8201").unwrap(), 1).unwrap_err();
+        let err = err.get_ref().unwrap().downcast_ref::<ParseError>().unwrap();
+        assert_eq!((*err.kind(), err.offset()), (ErrorKind::UnexpectedEof, 2));
+    }
+
+    #[tokio::test]
+    async fn framed_read() {
+        let input = hex::decode("a1616101818102f56178").unwrap();
+        let events: Vec<_> = FramedRead::new(&input[..], CborDecoder::new())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        assert_eq!(
+            events,
+            [
+                Event::MapStart(Some(1)),
+                Event::from("a"),
+                Event::Atom(Atom::U64(1)),
+                Event::MapEnd,
+                Event::SeqStart(Some(1)),
+                Event::SeqStart(Some(1)),
+                Event::Atom(Atom::U64(2)),
+                Event::SeqEnd,
+                Event::SeqEnd,
+                Event::Atom(Atom::Bool(true)),
+                Event::from("x"),
+            ]
+        );
+    }
+}
@@ -1,17 +1,66 @@
-use std::io::{self, Write};
+use std::{
+    fmt,
+    io::{self, Write},
+};
 
 use sud_core::{Atom, Event};
 
+pub use frame::CborDecoder;
+pub use parse::{CborParser, ErrorKind, ParseError};
+
 mod frame;
+mod number;
+mod parse;
 
 pub struct CborSerializer<W> {
     enc: CborEncoder,
     out: W,
 }
 
-#[derive(Default)]
-struct CborEncoder {
+/// Writes events as CBOR, to any [`Write`] or through a
+/// [`FramedWrite`](tokio_util::codec::FramedWrite).
+///
+/// Use [`CborEncoder::builder`] to configure the output.
+#[derive(Clone, Debug, Default)]
+pub struct CborEncoder {
     stack: Vec<State>,
+    options: Options,
+}
+
+/// Configures a [`CborEncoder`].
+#[derive(Clone, Debug, Default)]
+pub struct CborEncoderBuilder {
+    options: Options,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Options {
+    shortest_floats: bool,
+}
+
+/// An event that a [`CborEncoder`] can't write.
+///
+/// It is returned as an [`io::ErrorKind::InvalidData`] error wrapping this
+/// value, and nothing is written for the offending event.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum EncodeError {
+    /// An `Atom::Number` that is not in JSON's number syntax.
+    InvalidNumber,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EncodeError::InvalidNumber => "number is not valid JSON",
+        })
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+fn invalid_data(err: EncodeError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -22,8 +71,13 @@ struct State {
 
 impl<W> CborSerializer<W> {
     pub fn new(output: W) -> Self {
+        Self::with_encoder(output, CborEncoder::new())
+    }
+
+    /// Writes to `output` using a configured encoder.
+    pub fn with_encoder(output: W, encoder: CborEncoder) -> Self {
         Self {
-            enc: CborEncoder::default(),
+            enc: encoder,
             out: output,
         }
     }
@@ -35,13 +89,35 @@ impl<W: Write> CborSerializer<W> {
     }
 }
 
+impl CborEncoderBuilder {
+    /// Writes each float as a half or single precision float when that loses
+    /// nothing, instead of always using double precision.
+    pub fn shortest_floats(mut self, shortest: bool) -> Self {
+        self.options.shortest_floats = shortest;
+        self
+    }
+
+    pub fn build(self) -> CborEncoder {
+        CborEncoder {
+            stack: Vec::new(),
+            options: self.options,
+        }
+    }
+}
+
 impl CborEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn builder() -> CborEncoderBuilder {
+        CborEncoderBuilder::default()
+    }
+
     pub fn write<W: Write>(&mut self, event: Event<'_>, mut dst: W) -> io::Result<()> {
         match event {
             Event::Atom(atom) => {
-                if let Some(State { actual_len, .. }) = self.stack.last_mut() {
-                    *actual_len += 1;
-                }
+                self.start_item();
 
                 match atom {
                     Atom::U64(i) => write_num(0x00, &mut dst, i),
@@ -56,23 +132,22 @@ impl CborEncoder {
                     Atom::Char(c) => {
                         write_encoded_bytes(0x60, &mut dst, c.encode_utf8(&mut [0; 4]).as_bytes())
                     }
-                    Atom::Str(s) => write_encoded_bytes(0x60, &mut dst, s.as_bytes()),
+                    Atom::Str(s) | Atom::Hinted(s, _) => {
+                        write_encoded_bytes(0x60, &mut dst, s.as_bytes())
+                    }
                     Atom::Bool(false) => dst.write_all(&[0xf4]),
                     Atom::Bool(true) => dst.write_all(&[0xf5]),
                     Atom::Null => dst.write_all(&[0xf6]),
-                    Atom::F64(i) => {
-                        let mut buf = [0; 9];
-                        buf[0] = 0xfb;
-                        buf[1..9].copy_from_slice(&i.to_be_bytes());
-                        dst.write_all(&buf)
-                    }
-                    _ => Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        "unsupported atom in JSON",
-                    )),
+                    Atom::F64(i) => write_float(&mut dst, i, self.options.shortest_floats),
+                    Atom::Number(text) => match number::Decimal::parse(&text) {
+                        Some(decimal) => decimal.write(&mut dst),
+                        None => Err(invalid_data(EncodeError::InvalidNumber)),
+                    },
+                    _ => Err(io::Error::other("unsupported atom in JSON")),
                 }
             }
             Event::SeqStart(Some(len)) => {
+                self.start_item();
                 self.stack.push(State {
                     expected_len: Some(len),
                     actual_len: 0,
@@ -80,6 +155,7 @@ impl CborEncoder {
                 write_length(0x80, &mut dst, len)
             }
             Event::SeqStart(None) => {
+                self.start_item();
                 self.stack.push(State {
                     expected_len: None,
                     actual_len: 0,
@@ -99,6 +175,7 @@ impl CborEncoder {
                 }
             }
             Event::MapStart(Some(len)) => {
+                self.start_item();
                 self.stack.push(State {
                     expected_len: Some(len * 2),
                     actual_len: 0,
@@ -106,6 +183,7 @@ impl CborEncoder {
                 write_length(0xa0, &mut dst, len)
             }
             Event::MapStart(None) => {
+                self.start_item();
                 self.stack.push(State {
                     expected_len: None,
                     actual_len: 0,
@@ -126,6 +204,13 @@ impl CborEncoder {
             }
         }
     }
+
+    /// Counts a new item, atom or container, towards the enclosing container's length.
+    fn start_item(&mut self) {
+        if let Some(State { actual_len, .. }) = self.stack.last_mut() {
+            *actual_len += 1;
+        }
+    }
 }
 
 fn write_length<W>(base: u8, writer: &mut W, value: usize) -> io::Result<()>
@@ -174,13 +259,66 @@ where
     writer.write_all(&buf[..len])
 }
 
+fn write_float<W>(writer: &mut W, f: f64, shortest: bool) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+{
+    if shortest {
+        if let Some(half) = f64_to_f16(f) {
+            let [a, b] = half.to_be_bytes();
+            return writer.write_all(&[0xf9, a, b]);
+        }
+        let single = f as f32;
+        if single as f64 == f {
+            let [a, b, c, d] = single.to_be_bytes();
+            return writer.write_all(&[0xfa, a, b, c, d]);
+        }
+    }
+
+    let mut buf = [0; 9];
+    buf[0] = 0xfb;
+    buf[1..9].copy_from_slice(&f.to_be_bytes());
+    writer.write_all(&buf)
+}
+
+/// The half precision bits for `f`, if it can be represented exactly.
+fn f64_to_f16(f: f64) -> Option<u16> {
+    let bits = f.to_bits();
+    let sign = ((bits >> 48) & 0x8000) as u16;
+    if f.is_nan() {
+        return Some(0x7e00);
+    }
+    if f.is_infinite() {
+        return Some(sign | 0x7c00);
+    }
+    if f == 0.0 {
+        return Some(sign);
+    }
+
+    let exp = ((bits >> 52) & 0x7ff) as i32 - 1023;
+    let mant = bits & ((1 << 52) - 1);
+    match exp {
+        // normal, the mantissa has to fit in 10 bits
+        -14..=15 if mant.trailing_zeros() >= 42 => {
+            Some(sign | ((exp + 15) as u16) << 10 | (mant >> 42) as u16)
+        }
+        // subnormal, a multiple of 2^-24
+        -24..=-15 => {
+            let shift = (28 - exp) as u32;
+            let full = (1 << 52) | mant;
+            (full.trailing_zeros() >= shift).then(|| sign | (full >> shift) as u16)
+        }
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
 
-    use sud_core::Serializer;
+    use sud_core::{Atom, Event, Serializer};
 
-    use crate::CborSerializer;
+    use crate::{CborEncoder, CborParser, CborSerializer};
 
     #[test]
     fn str_map() {
@@ -197,4 +335,48 @@ mod tests {
         );
         assert_eq!(serializer.enc.stack, &[]);
     }
+
+    #[test]
+    fn nested() {
+        let data = [vec![1, 2], vec![], vec![3]];
+
+        let mut serializer = CborSerializer::new(Vec::new());
+
+        data.try_for_each_event(&mut data.get_state(), |event| serializer.write(event))
+            .unwrap();
+
+        assert_eq!(serializer.out, hex::decode("83820102808103").unwrap());
+        assert_eq!(serializer.enc.stack, &[]);
+    }
+
+    #[test]
+    fn shortest_floats() {
+        let encoder = CborEncoder::builder().shortest_floats(true).build();
+        let mut serializer = CborSerializer::with_encoder(Vec::new(), encoder);
+
+        let floats = [
+            (0.0, "f90000"),
+            (-0.0, "f98000"),
+            (1.5, "f93e00"),
+            (65504.0, "f97bff"),
+            (5.960464477539063e-8, "f90001"),
+            (6.103515625e-5, "f90400"),
+            (f64::NEG_INFINITY, "f9fc00"),
+            (100000.0, "fa47c35000"),
+            (f32::MAX as f64, "fa7f7fffff"),
+            (1.1, "fb3ff199999999999a"),
+        ];
+        for (f, expected) in floats {
+            serializer.out.clear();
+            serializer.write(Event::Atom(Atom::F64(f))).unwrap();
+            assert_eq!(hex::encode(&serializer.out), expected, "{f}");
+
+            let decoded: Vec<_> = CborParser::new(&serializer.out).collect();
+            assert_eq!(decoded, [Ok(Event::Atom(Atom::F64(f)))]);
+        }
+
+        serializer.out.clear();
+        serializer.write(Event::Atom(Atom::F64(f64::NAN))).unwrap();
+        assert_eq!(hex::encode(&serializer.out), "f97e00");
+    }
 }
@@ -0,0 +1,267 @@
+//! `Atom::Number` as bignums (tags 2 and 3) and decimal fractions (tag 4).
+
+use std::io::{self, Write};
+
+use crate::{write_encoded_bytes, write_num};
+
+/// A decimal number as an integer `mantissa * 10^exponent`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Decimal {
+    pub(crate) negative: bool,
+    /// The ascii digits of the mantissa, without leading zeros, so zero is empty.
+    pub(crate) digits: Vec<u8>,
+    pub(crate) exponent: i64,
+}
+
+impl Decimal {
+    /// Reads a number in JSON's syntax, so not `01`, `1.` or `+1`.
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        let digits_ok = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text),
+        };
+        let (mantissa, exp) = match text.split_once(['e', 'E']) {
+            Some((m, e)) => {
+                if !digits_ok(e.strip_prefix(['+', '-']).unwrap_or(e)) {
+                    return None;
+                }
+                (m, e.strip_prefix('+').unwrap_or(e).parse::<i64>().ok()?)
+            }
+            None => (text, 0),
+        };
+        let (int, frac) = match mantissa.split_once('.') {
+            Some((int, frac)) if digits_ok(frac) => (int, frac),
+            Some(_) => return None,
+            None => (mantissa, ""),
+        };
+        if !digits_ok(int) || (int.len() > 1 && int.starts_with('0')) {
+            return None;
+        }
+
+        // trailing zeros are kept, so `1.50` stays `150 * 10^-2`
+        let digits: Vec<u8> = int.bytes().chain(frac.bytes()).collect();
+        let leading = digits.iter().take_while(|&&d| d == b'0').count();
+        Some(Decimal {
+            negative,
+            digits: digits[leading..].to_vec(),
+            exponent: exp.checked_sub(frac.len() as i64)?,
+        })
+    }
+
+    /// Writes the number in JSON's syntax, with a decimal point where that is
+    /// shorter than an exponent.
+    pub(crate) fn to_text(&self) -> String {
+        let digits = match &*self.digits {
+            [] => "0",
+            digits => std::str::from_utf8(digits).expect("digits are ascii"),
+        };
+        let sign = if self.negative { "-" } else { "" };
+        let len = digits.len() as i64;
+        match self.exponent {
+            0 => format!("{sign}{digits}"),
+            e if e < 0 && -e < len => {
+                let (int, frac) = digits.split_at((len + e) as usize);
+                format!("{sign}{int}.{frac}")
+            }
+            e if e < 0 && -e <= len + 6 => {
+                let zeros = "0".repeat((-e - len) as usize);
+                format!("{sign}0.{zeros}{digits}")
+            }
+            e => format!("{sign}{digits}e{e}"),
+        }
+    }
+
+    pub(crate) fn write<W: Write>(&self, dst: &mut W) -> io::Result<()> {
+        if self.exponent != 0 {
+            // tag 4, then a two item array
+            dst.write_all(&[0xc4, 0x82])?;
+            match u64::try_from(self.exponent) {
+                Ok(e) => write_num(0x00, dst, e)?,
+                Err(_) => write_num(0x20, dst, !self.exponent as u64)?,
+            }
+        }
+
+        // negative integers are stored as `-1 - n`
+        let mut magnitude = from_decimal(&self.digits);
+        let negative = self.negative && !magnitude.is_empty();
+        if negative {
+            decrement(&mut magnitude);
+            if magnitude[0] == 0 {
+                magnitude.remove(0);
+            }
+        }
+        match to_u64(&magnitude) {
+            Some(n) => write_num(if negative { 0x20 } else { 0x00 }, dst, n),
+            None => {
+                dst.write_all(&[if negative { 0xc3 } else { 0xc2 }])?;
+                write_encoded_bytes(0x40, dst, &magnitude)
+            }
+        }
+    }
+}
+
+/// Reads big endian bytes as an integer, if it fits.
+pub(crate) fn to_u64(bytes: &[u8]) -> Option<u64> {
+    let leading = bytes.iter().take_while(|&&b| b == 0).count();
+    let bytes = &bytes[leading..];
+    (bytes.len() <= 8).then(|| bytes.iter().fold(0, |n, &b| (n << 8) | b as u64))
+}
+
+/// The ascii digits of the magnitude of a bignum's value, which for a negative
+/// bignum is one more than its bytes.
+pub(crate) fn bignum_digits(bytes: &[u8], negative: bool) -> Vec<u8> {
+    let mut magnitude = bytes.to_vec();
+    if negative {
+        increment(&mut magnitude);
+    }
+    to_decimal(&magnitude)
+}
+
+/// Converts the ascii digits of an integer to its minimal big endian bytes.
+fn from_decimal(digits: &[u8]) -> Vec<u8> {
+    // little endian limbs, so that carries push onto the end
+    let mut limbs: Vec<u32> = Vec::new();
+    for chunk in digits.chunks(9) {
+        let mut carry = chunk.iter().fold(0, |n, &d| n * 10 + (d - b'0') as u64);
+        let scale = 10u64.pow(chunk.len() as u32);
+        for limb in &mut limbs {
+            let n = *limb as u64 * scale + carry;
+            *limb = n as u32;
+            carry = n >> 32;
+        }
+        if carry > 0 {
+            limbs.push(carry as u32);
+        }
+    }
+    let bytes: Vec<u8> = limbs.iter().rev().flat_map(|l| l.to_be_bytes()).collect();
+    let leading = bytes.iter().take_while(|&&b| b == 0).count();
+    bytes[leading..].to_vec()
+}
+
+/// Converts big endian bytes to the ascii digits of the same integer.
+fn to_decimal(bytes: &[u8]) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    let mut chunks = Vec::new();
+    while bytes.iter().any(|&b| b != 0) {
+        // long division by 10^9, a byte at a time
+        let mut rem = 0u64;
+        for b in &mut bytes {
+            let n = (rem << 8) | *b as u64;
+            *b = (n / 1_000_000_000) as u8;
+            rem = n % 1_000_000_000;
+        }
+        chunks.push(rem);
+    }
+
+    let mut digits = match chunks.pop() {
+        Some(first) => first.to_string().into_bytes(),
+        None => return b"0".to_vec(),
+    };
+    for chunk in chunks.iter().rev() {
+        digits.extend_from_slice(format!("{chunk:09}").as_bytes());
+    }
+    digits
+}
+
+fn increment(bytes: &mut Vec<u8>) {
+    for b in bytes.iter_mut().rev() {
+        let (n, carry) = b.overflowing_add(1);
+        *b = n;
+        if !carry {
+            return;
+        }
+    }
+    bytes.insert(0, 1);
+}
+
+/// Subtracts one from a non-zero integer.
+fn decrement(bytes: &mut [u8]) {
+    for b in bytes.iter_mut().rev() {
+        let (n, borrow) = b.overflowing_sub(1);
+        *b = n;
+        if !borrow {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use sud_core::{Atom, Event};
+
+    use crate::{parse::ErrorKind, CborParser, CborSerializer, EncodeError};
+
+    fn encode(text: &str) -> String {
+        let mut serializer = CborSerializer::new(Vec::new());
+        serializer
+            .write(Event::Atom(Atom::Number(Cow::Borrowed(text))))
+            .unwrap();
+        hex::encode(serializer.out)
+    }
+
+    fn decode(input: &str) -> Atom<'static> {
+        let input = hex::decode(input).unwrap();
+        let events: Vec<_> = CborParser::new(&input).collect::<Result<_, _>>().unwrap();
+        match &*events {
+            [Event::Atom(atom)] => atom.to_static(),
+            events => panic!("expected a single atom, got {events:?}"),
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        let number = |s: &str| Atom::Number(Cow::Owned(s.to_owned()));
+        // examples from RFC 8949
+        let cases = [
+            ("18446744073709551616", "c249010000000000000000"),
+            ("-18446744073709551617", "c349010000000000000000"),
+            ("273.15", "c48221196ab3"),
+            ("1.50", "c482211896"),
+            ("-0.000001", "c4822520"),
+            ("15e399", "c48219018f0f"),
+        ];
+        for (text, hex) in cases {
+            assert_eq!(encode(text), hex, "{text}");
+            assert_eq!(decode(hex), number(text), "{hex}");
+        }
+
+        // numbers that fit are encoded as plain integers
+        assert_eq!(encode("1e2"), "c4820201");
+        assert_eq!(encode("-5"), "24");
+        assert_eq!(decode("c2420100"), Atom::U64(256));
+        assert_eq!(decode("c3487fffffffffffffff"), Atom::I64(i64::MIN));
+        assert_eq!(
+            decode("c3488000000000000000"),
+            number("-9223372036854775809")
+        );
+        assert_eq!(decode("c48200c2420100"), Atom::U64(256));
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        for input in ["c201", "c48101", "c48201f5", "c482f501"] {
+            let input = hex::decode(input).unwrap();
+            let err = CborParser::new(&input)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap_err();
+            assert_eq!(*err.kind(), ErrorKind::InvalidNumber, "{input:x?}");
+        }
+
+        for text in [
+            "", "-", "01", "-01", "1.", ".5", "+1", "1e", "1e+-1", "1.2.3", "0x10", "NaN",
+        ] {
+            let mut serializer = CborSerializer::new(Vec::new());
+            let err = serializer
+                .write(Event::Atom(Atom::Number(Cow::Borrowed(text))))
+                .unwrap_err();
+            assert_eq!(
+                err.get_ref().unwrap().downcast_ref(),
+                Some(&EncodeError::InvalidNumber),
+                "{text:?}"
+            );
+        }
+    }
+}
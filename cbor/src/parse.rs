@@ -0,0 +1,686 @@
+use std::{borrow::Cow, fmt};
+
+use sud_core::{Atom, Event};
+
+use crate::number::{self, Decimal};
+
+/// A pull based CBOR parser over an in-memory item.
+///
+/// Byte and text strings are borrowed straight from the input, unless they
+/// were sent as indefinite length chunks. Definite length containers still end
+/// with a `SeqEnd` or `MapEnd`, once all of their items have been read.
+pub struct CborParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    reader: Reader,
+    failed: bool,
+}
+
+impl<'a> CborParser<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        CborParser {
+            input,
+            pos: 0,
+            reader: Reader::default(),
+            failed: false,
+        }
+    }
+}
+
+impl<'a> Iterator for CborParser<'a> {
+    type Item = Result<Event<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let res = match self.reader.next(self.input, &mut self.pos, true) {
+            Ok(Some(event)) => return Some(Ok(event)),
+            Ok(None) if self.pos == self.input.len() => return None,
+            Ok(None) => Err(self.reader.error(ErrorKind::TrailingBytes, self.pos)),
+            Err(err) => Err(err),
+        };
+        self.failed = true;
+        Some(res)
+    }
+}
+
+/// A CBOR item that is truncated or not well-formed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    kind: ErrorKind,
+    offset: usize,
+}
+
+impl ParseError {
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The byte offset into the input of the offending item.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    UnexpectedEof,
+    /// The reserved additional information values 28 to 30, or 31 where an
+    /// indefinite length is not allowed.
+    InvalidAdditionalInfo,
+    /// A break that does not close an indefinite length container.
+    UnexpectedBreak,
+    /// A chunk of an indefinite length string that is not a definite length
+    /// string of the same type.
+    InvalidChunk,
+    /// A negative integer below `i64::MIN`.
+    IntegerOverflow,
+    /// A length that does not fit in memory.
+    LengthOverflow,
+    InvalidUtf8,
+    /// A simple value other than `false`, `true`, `null` or `undefined`.
+    UnsupportedSimple(u8),
+    TrailingBytes,
+    /// A bignum that is not a byte string, or a decimal fraction that is not
+    /// an array of an integer exponent and an integer or bignum mantissa.
+    InvalidNumber,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::UnexpectedEof => f.write_str("unexpected end of input"),
+            ErrorKind::InvalidAdditionalInfo => f.write_str("invalid additional information"),
+            ErrorKind::UnexpectedBreak => f.write_str("unexpected break"),
+            ErrorKind::InvalidChunk => f.write_str("invalid indefinite length string chunk"),
+            ErrorKind::IntegerOverflow => f.write_str("integer out of range"),
+            ErrorKind::LengthOverflow => f.write_str("length out of range"),
+            ErrorKind::InvalidUtf8 => f.write_str("invalid utf-8"),
+            ErrorKind::UnsupportedSimple(v) => write!(f, "unsupported simple value {v}"),
+            ErrorKind::TrailingBytes => f.write_str("trailing bytes"),
+            ErrorKind::InvalidNumber => f.write_str("invalid bignum or decimal fraction"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    map: bool,
+    /// Items left in a definite length container. Maps count keys and values.
+    remaining: Option<u64>,
+    /// Items read so far.
+    seen: u64,
+}
+
+/// One data item, read without touching the reader's state.
+enum Item<'a> {
+    Atom(Atom<'a>),
+    Seq(Option<u64>),
+    Map(Option<u64>),
+    Break,
+}
+
+/// The nesting of a CBOR parser, kept separate from the input so that it can
+/// be resumed when more input arrives.
+#[derive(Debug, Default)]
+pub(crate) struct Reader {
+    stack: Vec<Frame>,
+    /// The top level item is complete.
+    done: bool,
+    /// The absolute offset of `input[0]`.
+    base: usize,
+}
+
+/// Whether an item ran off the end of the input, or is really invalid.
+enum Incomplete {
+    NeedMore,
+    Error(ErrorKind, usize),
+}
+
+impl Reader {
+    /// Reads the next event from `input[*pos..]`.
+    ///
+    /// Returns `None` once the top level item is complete. If `eof` is false,
+    /// `None` is also returned when the input ends mid-item, in which case
+    /// `pos` is left at the start of that item.
+    pub(crate) fn next<'a>(
+        &mut self,
+        input: &'a [u8],
+        pos: &mut usize,
+        eof: bool,
+    ) -> Result<Option<Event<'a>>, ParseError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        // definite length containers end without any input
+        if let Some(&Frame {
+            map,
+            remaining: Some(0),
+            ..
+        }) = self.stack.last()
+        {
+            self.stack.pop();
+            self.done = self.stack.is_empty();
+            return Ok(Some(if map { Event::MapEnd } else { Event::SeqEnd }));
+        }
+
+        let start = *pos;
+        let item = match read_item(input, pos) {
+            Ok(item) => item,
+            Err(Incomplete::NeedMore) if !eof => {
+                *pos = start;
+                return Ok(None);
+            }
+            Err(Incomplete::NeedMore) => {
+                return Err(self.error(ErrorKind::UnexpectedEof, input.len()))
+            }
+            Err(Incomplete::Error(kind, at)) => return Err(self.error(kind, at)),
+        };
+
+        let event = match item {
+            Item::Break => match self.stack.last() {
+                Some(&Frame {
+                    map,
+                    remaining: None,
+                    seen,
+                }) if !map || seen % 2 == 0 => {
+                    self.stack.pop();
+                    self.done = self.stack.is_empty();
+                    if map {
+                        Event::MapEnd
+                    } else {
+                        Event::SeqEnd
+                    }
+                }
+                _ => return Err(self.error(ErrorKind::UnexpectedBreak, start)),
+            },
+            Item::Atom(atom) => {
+                self.start_item();
+                self.done = self.stack.is_empty();
+                Event::Atom(atom)
+            }
+            Item::Seq(len) => {
+                let len = self.container_len(len, 1, start)?;
+                self.start_item();
+                self.stack.push(Frame {
+                    map: false,
+                    remaining: len.map(|(_, items)| items),
+                    seen: 0,
+                });
+                Event::SeqStart(len.map(|(len, _)| len))
+            }
+            Item::Map(len) => {
+                let len = self.container_len(len, 2, start)?;
+                self.start_item();
+                self.stack.push(Frame {
+                    map: true,
+                    remaining: len.map(|(_, items)| items),
+                    seen: 0,
+                });
+                Event::MapStart(len.map(|(len, _)| len))
+            }
+        };
+        Ok(Some(event))
+    }
+
+    /// Starts on the next top level item once the previous one is complete.
+    pub(crate) fn reset(&mut self) {
+        self.done = false;
+    }
+
+    /// Whether the reader is between top level items.
+    pub(crate) fn at_boundary(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Records that `n` bytes were removed from the front of the input.
+    pub(crate) fn advance(&mut self, n: usize) {
+        self.base += n;
+    }
+
+    pub(crate) fn error(&self, kind: ErrorKind, pos: usize) -> ParseError {
+        ParseError {
+            kind,
+            offset: self.base + pos,
+        }
+    }
+
+    /// Counts a new item towards the enclosing container.
+    fn start_item(&mut self) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.seen += 1;
+            if let Some(remaining) = &mut frame.remaining {
+                *remaining -= 1;
+            }
+        }
+    }
+
+    /// The length of the container event, and how many items it holds.
+    fn container_len(
+        &self,
+        len: Option<u64>,
+        per_entry: u64,
+        start: usize,
+    ) -> Result<Option<(usize, u64)>, ParseError> {
+        let Some(len) = len else {
+            return Ok(None);
+        };
+        match (usize::try_from(len), len.checked_mul(per_entry)) {
+            (Ok(entries), Some(items)) => Ok(Some((entries, items))),
+            _ => Err(self.error(ErrorKind::LengthOverflow, start)),
+        }
+    }
+}
+
+/// Reads the initial byte and argument of an item, skipping over any tags
+/// other than the numbers in [`read_number`].
+///
+/// Returns the major type, additional information and argument. The argument is
+/// `None` for an indefinite length, and the tag for a number tag.
+fn read_head(input: &[u8], pos: &mut usize) -> Result<(u8, u8, Option<u64>), Incomplete> {
+    loop {
+        let start = *pos;
+        let &initial = input.get(start).ok_or(Incomplete::NeedMore)?;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+
+        let size = match info {
+            0..=23 => 0,
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            31 => 0,
+            _ => return Err(Incomplete::Error(ErrorKind::InvalidAdditionalInfo, start)),
+        };
+        let bytes = input
+            .get(start + 1..start + 1 + size)
+            .ok_or(Incomplete::NeedMore)?;
+        *pos = start + 1 + size;
+
+        let arg = match info {
+            0..=23 => Some(info as u64),
+            31 => None,
+            _ => Some(bytes.iter().fold(0, |n, &b| (n << 8) | b as u64)),
+        };
+
+        match (major, arg) {
+            (6, Some(2..=4)) => return Ok((major, info, arg)),
+            // tags are not part of the event model, so we only keep the content
+            (6, Some(_)) => continue,
+            (0 | 1 | 6, None) => {
+                return Err(Incomplete::Error(ErrorKind::InvalidAdditionalInfo, start))
+            }
+            _ => return Ok((major, info, arg)),
+        }
+    }
+}
+
+fn read_item<'a>(input: &'a [u8], pos: &mut usize) -> Result<Item<'a>, Incomplete> {
+    let start = *pos;
+    let (major, info, arg) = read_head(input, pos)?;
+    let item = match (major, arg) {
+        (0, Some(n)) => Item::Atom(Atom::U64(n)),
+        (1, Some(n)) => match i64::try_from(n) {
+            Ok(n) => Item::Atom(Atom::I64(-1 - n)),
+            Err(_) => return Err(Incomplete::Error(ErrorKind::IntegerOverflow, start)),
+        },
+        (2, _) => Item::Atom(Atom::Bytes(read_bytes(input, pos, 2, arg)?)),
+        (3, _) => {
+            let bytes = read_bytes(input, pos, 3, arg)?;
+            let s = match bytes {
+                Cow::Borrowed(b) => std::str::from_utf8(b).map(Cow::Borrowed).ok(),
+                Cow::Owned(b) => String::from_utf8(b).map(Cow::Owned).ok(),
+            };
+            match s {
+                Some(s) => Item::Atom(Atom::Str(s)),
+                None => return Err(Incomplete::Error(ErrorKind::InvalidUtf8, start)),
+            }
+        }
+        (4, len) => Item::Seq(len),
+        (6, Some(tag)) => Item::Atom(read_number(input, pos, tag, start)?),
+        (5, len) => Item::Map(len),
+        (7, None) => Item::Break,
+        (7, Some(v)) => Item::Atom(match info {
+            25 => Atom::F64(f16_to_f64(v as u16)),
+            26 => Atom::F64(f32::from_bits(v as u32) as f64),
+            27 => Atom::F64(f64::from_bits(v)),
+            // a one byte simple value must not be one of the short ones
+            24 if v < 32 => return Err(Incomplete::Error(ErrorKind::InvalidAdditionalInfo, start)),
+            _ => match v {
+                20 => Atom::Bool(false),
+                21 => Atom::Bool(true),
+                22 | 23 => Atom::Null,
+                v => {
+                    return Err(Incomplete::Error(
+                        ErrorKind::UnsupportedSimple(v as u8),
+                        start,
+                    ))
+                }
+            },
+        }),
+        _ => unreachable!("major types are 3 bits"),
+    };
+    Ok(item)
+}
+
+/// Reads a bignum (tags 2 and 3) or decimal fraction (tag 4) whose tag has
+/// already been read, as an integer if it fits or else as `Atom::Number`.
+fn read_number<'a>(
+    input: &[u8],
+    pos: &mut usize,
+    tag: u64,
+    start: usize,
+) -> Result<Atom<'a>, Incomplete> {
+    let invalid = || Incomplete::Error(ErrorKind::InvalidNumber, start);
+    let (exponent, (negative, digits)) = if tag == 4 {
+        if read_head(input, pos)? != (4, 2, Some(2)) {
+            return Err(invalid());
+        }
+        let (negative, digits) = read_integer(input, pos, None, start)?;
+        let exponent = std::str::from_utf8(&digits)
+            .expect("digits are ascii")
+            .parse::<i64>()
+            .map_err(|_| invalid())?;
+        (
+            if negative { -exponent } else { exponent },
+            read_integer(input, pos, None, start)?,
+        )
+    } else {
+        (0, read_integer(input, pos, Some(tag), start)?)
+    };
+
+    let decimal = Decimal {
+        negative,
+        digits,
+        exponent,
+    };
+    if exponent == 0 {
+        let text = std::str::from_utf8(&decimal.digits).expect("digits are ascii");
+        match (negative, text.parse::<u64>()) {
+            (false, Ok(n)) => return Ok(Atom::U64(n)),
+            (true, Ok(n)) if n <= 1 << 63 => return Ok(Atom::I64((n as i64).wrapping_neg())),
+            _ => {}
+        }
+    }
+    Ok(Atom::Number(Cow::Owned(decimal.to_text())))
+}
+
+/// Reads an integer or bignum, as its sign and the ascii digits of its
+/// magnitude. `tag` is the bignum tag, if it has already been read.
+fn read_integer(
+    input: &[u8],
+    pos: &mut usize,
+    tag: Option<u64>,
+    start: usize,
+) -> Result<(bool, Vec<u8>), Incomplete> {
+    let invalid = || Incomplete::Error(ErrorKind::InvalidNumber, start);
+    let (major, _, arg) = match tag {
+        Some(tag) => (6, 0, Some(tag)),
+        None => read_head(input, pos)?,
+    };
+    match (major, arg) {
+        (0, Some(n)) => Ok((false, n.to_string().into_bytes())),
+        (1, Some(n)) => Ok((true, (n as u128 + 1).to_string().into_bytes())),
+        (6, Some(tag @ (2 | 3))) => match read_head(input, pos)? {
+            (2, _, len) => {
+                let bytes = read_bytes(input, pos, 2, len)?;
+                Ok((tag == 3, number::bignum_digits(&bytes, tag == 3)))
+            }
+            _ => Err(invalid()),
+        },
+        _ => Err(invalid()),
+    }
+}
+
+/// Reads the payload of a byte or text string whose head has already been read.
+fn read_bytes<'a>(
+    input: &'a [u8],
+    pos: &mut usize,
+    major: u8,
+    len: Option<u64>,
+) -> Result<Cow<'a, [u8]>, Incomplete> {
+    let Some(len) = len else {
+        // indefinite length strings are a series of definite length chunks
+        let mut buf = Vec::new();
+        loop {
+            let start = *pos;
+            if input.get(start) == Some(&0xff) {
+                *pos += 1;
+                return Ok(Cow::Owned(buf));
+            }
+            match read_head(input, pos)? {
+                (m, _, Some(len)) if m == major => {
+                    buf.extend_from_slice(read_slice(input, pos, len, start)?);
+                }
+                _ => return Err(Incomplete::Error(ErrorKind::InvalidChunk, start)),
+            }
+        }
+    };
+    read_slice(input, pos, len, *pos).map(Cow::Borrowed)
+}
+
+fn read_slice<'a>(
+    input: &'a [u8],
+    pos: &mut usize,
+    len: u64,
+    start: usize,
+) -> Result<&'a [u8], Incomplete> {
+    let end = usize::try_from(len)
+        .ok()
+        .and_then(|len| pos.checked_add(len))
+        .ok_or(Incomplete::Error(ErrorKind::LengthOverflow, start))?;
+    let bytes = input.get(*pos..end).ok_or(Incomplete::NeedMore)?;
+    *pos = end;
+    Ok(bytes)
+}
+
+fn f16_to_f64(half: u16) -> f64 {
+    let sign = if half & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exp = (half >> 10) & 0x1f;
+    let mant = (half & 0x3ff) as f64;
+    match exp {
+        0 => sign * mant * 2f64.powi(-24),
+        31 if mant == 0.0 => sign * f64::INFINITY,
+        31 => f64::NAN,
+        _ => sign * (1.0 + mant / 1024.0) * 2f64.powi(exp as i32 - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use sud_core::{de::from_events, pointer::Pointer, Atom, Event, Serializer, Value};
+
+    use super::{CborParser, ErrorKind, ParseError};
+    use crate::CborSerializer;
+
+    fn parse(input: &[u8]) -> Result<Vec<Event<'_>>, ParseError> {
+        CborParser::new(input).collect()
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    fn atom(input: &str) -> Atom<'static> {
+        match &*parse(&hex(input)).unwrap() {
+            [Event::Atom(atom)] => atom.to_static(),
+            events => panic!("expected a single atom, got {events:?}"),
+        }
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(atom("00"), Atom::U64(0));
+        assert_eq!(atom("17"), Atom::U64(23));
+        assert_eq!(atom("1818"), Atom::U64(24));
+        assert_eq!(atom("190100"), Atom::U64(256));
+        assert_eq!(atom("1a000f4240"), Atom::U64(1000000));
+        assert_eq!(atom("1bffffffffffffffff"), Atom::U64(u64::MAX));
+        assert_eq!(atom("20"), Atom::I64(-1));
+        assert_eq!(atom("3903e7"), Atom::I64(-1000));
+        assert_eq!(atom("3b7fffffffffffffff"), Atom::I64(i64::MIN));
+    }
+
+    #[test]
+    fn floats_and_simple() {
+        assert_eq!(atom("f90000"), Atom::F64(0.0));
+        assert_eq!(atom("f93c00"), Atom::F64(1.0));
+        assert_eq!(atom("f93e00"), Atom::F64(1.5));
+        assert_eq!(atom("f97bff"), Atom::F64(65504.0));
+        assert_eq!(atom("f90001"), Atom::F64(5.960464477539063e-8));
+        assert_eq!(atom("f9c400"), Atom::F64(-4.0));
+        assert_eq!(atom("f97c00"), Atom::F64(f64::INFINITY));
+        assert!(matches!(atom("f97e00"), Atom::F64(f) if f.is_nan()));
+        assert_eq!(atom("fa47c35000"), Atom::F64(100000.0));
+        assert_eq!(atom("fb3ff199999999999a"), Atom::F64(1.1));
+        assert_eq!(atom("f4"), Atom::Bool(false));
+        assert_eq!(atom("f5"), Atom::Bool(true));
+        assert_eq!(atom("f6"), Atom::Null);
+        assert_eq!(atom("f7"), Atom::Null);
+        // tags are skipped
+        assert_eq!(atom("c11a514b67b0"), Atom::U64(1363896240));
+    }
+
+    #[test]
+    fn strings() {
+        let input = hex("8264494554464401020304");
+        let events = parse(&input).unwrap();
+        assert!(matches!(
+            &events[1],
+            Event::Atom(Atom::Str(Cow::Borrowed("IETF")))
+        ));
+        assert!(matches!(
+            &events[2],
+            Event::Atom(Atom::Bytes(Cow::Borrowed([1, 2, 3, 4])))
+        ));
+
+        assert_eq!(
+            atom("7f657374726561646d696e67ff"),
+            Atom::Str(Cow::Borrowed("streaming"))
+        );
+        assert_eq!(
+            atom("5f42010243030405ff"),
+            Atom::Bytes(Cow::Borrowed(&[1, 2, 3, 4, 5]))
+        );
+    }
+
+    #[test]
+    fn containers() {
+        // [1, [2, 3], {"a": []}]
+        assert_eq!(
+            parse(&hex("8201820203")).unwrap(),
+            [
+                Event::SeqStart(Some(2)),
+                Event::Atom(Atom::U64(1)),
+                Event::SeqStart(Some(2)),
+                Event::Atom(Atom::U64(2)),
+                Event::Atom(Atom::U64(3)),
+                Event::SeqEnd,
+                Event::SeqEnd,
+            ]
+        );
+        assert_eq!(
+            parse(&hex("bf6161806162f5ff")).unwrap(),
+            [
+                Event::MapStart(None),
+                Event::from("a"),
+                Event::SeqStart(Some(0)),
+                Event::SeqEnd,
+                Event::from("b"),
+                Event::Atom(Atom::Bool(true)),
+                Event::MapEnd,
+            ]
+        );
+        assert_eq!(
+            parse(&hex("9f018202039f0405ffff")).unwrap(),
+            [
+                Event::SeqStart(None),
+                Event::Atom(Atom::U64(1)),
+                Event::SeqStart(Some(2)),
+                Event::Atom(Atom::U64(2)),
+                Event::Atom(Atom::U64(3)),
+                Event::SeqEnd,
+                Event::SeqStart(None),
+                Event::Atom(Atom::U64(4)),
+                Event::Atom(Atom::U64(5)),
+                Event::SeqEnd,
+                Event::SeqEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = Value::Map(vec![
+            (
+                Value::Str("a".to_owned()),
+                Value::Seq(vec![Value::I64(-5), Value::F64(0.5), Value::Null]),
+            ),
+            (Value::U64(1), Value::Bytes(vec![0; 40])),
+        ]);
+
+        let mut serializer = CborSerializer::new(Vec::new());
+        value
+            .try_for_each_event(&mut value.get_state(), |event| serializer.write(event))
+            .unwrap();
+
+        let decoded: Value =
+            from_events(CborParser::new(&serializer.out).map(Result::unwrap)).unwrap();
+        assert_eq!(decoded, value);
+    }
+
+    #[track_caller]
+    fn assert_error(input: &str, kind: ErrorKind, offset: usize) {
+        let err = parse(&hex(input)).unwrap_err();
+        assert_eq!((*err.kind(), err.offset()), (kind, offset), "{input}");
+    }
+
+    #[test]
+    fn errors() {
+        assert_error("", ErrorKind::UnexpectedEof, 0);
+        assert_error("19", ErrorKind::UnexpectedEof, 1);
+        assert_error("8201", ErrorKind::UnexpectedEof, 2);
+        assert_error("4401", ErrorKind::UnexpectedEof, 2);
+        assert_error("1c", ErrorKind::InvalidAdditionalInfo, 0);
+        assert_error("1f", ErrorKind::InvalidAdditionalInfo, 0);
+        assert_error("f801", ErrorKind::InvalidAdditionalInfo, 0);
+        assert_error("8101ff", ErrorKind::TrailingBytes, 2);
+        assert_error("ff", ErrorKind::UnexpectedBreak, 0);
+        assert_error("bf01ff", ErrorKind::UnexpectedBreak, 2);
+        assert_error("5f6161ff", ErrorKind::InvalidChunk, 1);
+        assert_error("3b8000000000000000", ErrorKind::IntegerOverflow, 0);
+        assert_error("62ff61", ErrorKind::InvalidUtf8, 0);
+        assert_error("81f0", ErrorKind::UnsupportedSimple(16), 1);
+        assert_error("5b8000000000000000", ErrorKind::UnexpectedEof, 9);
+        assert_error("f820", ErrorKind::UnsupportedSimple(32), 0);
+
+        let err = parse(&hex("820102ff")).unwrap_err();
+        assert_eq!(err.to_string(), "trailing bytes at offset 3");
+    }
+
+    #[test]
+    fn pointer() {
+        // {1: ["a", {"b": h'00'}], 2: <truncated>}
+        let input = hex("a201826161a16162410002");
+        let pointer = Pointer::parse("/1/1/b").unwrap();
+        let events: Result<Vec<_>, _> = pointer.select(CborParser::new(&input)).collect();
+        assert_eq!(
+            events.unwrap(),
+            [Event::Atom(Atom::Bytes(Cow::Borrowed(&[0])))]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+/// How a text format without byte strings, like JSON, writes bytes.
+///
+/// Decoders that are given the same encoding hint that a string may be bytes,
+/// and `Vec<u8>` reads it back with [`decode`](Self::decode).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ByteEncoding {
+    /// An array of numbers, like `[104,105]`.
+    #[default]
+    Array,
+    /// A string of standard base64 with padding, like `"aGk="`.
+    Base64,
+    /// A string of URL-safe base64 without padding, like `"aGk"`.
+    Base64UrlNoPad,
+    /// A string of lowercase hex, like `"6869"`.
+    Hex,
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+impl ByteEncoding {
+    /// Reads back the contents of a string written with this encoding.
+    ///
+    /// Only the exact form of the encoding is accepted, so each byte string
+    /// has one way of being written. [`Array`](Self::Array) is not a string,
+    /// so it reads nothing.
+    pub fn decode(self, s: &str) -> Option<Vec<u8>> {
+        let s = s.as_bytes();
+        match self {
+            ByteEncoding::Array => None,
+            ByteEncoding::Base64 | ByteEncoding::Base64UrlNoPad => {
+                let (alphabet, s) = match self {
+                    ByteEncoding::Base64 => {
+                        if !s.len().is_multiple_of(4) {
+                            return None;
+                        }
+                        let unpadded =
+                            s.len() - s.iter().rev().take(2).take_while(|&&b| b == b'=').count();
+                        (BASE64, &s[..unpadded])
+                    }
+                    _ => (BASE64_URL, s),
+                };
+                if s.len() % 4 == 1 {
+                    return None;
+                }
+
+                let mut out = Vec::with_capacity(s.len() / 4 * 3 + 2);
+                for group in s.chunks(4) {
+                    let mut n = 0;
+                    for (i, c) in group.iter().enumerate() {
+                        let digit = alphabet.iter().position(|a| a == c)? as u32;
+                        n |= digit << (18 - 6 * i);
+                    }
+                    let bytes = n.to_be_bytes();
+                    let len = group.len() - 1;
+                    // the unused low bits of the last digit must be zero
+                    if bytes[1 + len..].iter().any(|&b| b != 0) {
+                        return None;
+                    }
+                    out.extend_from_slice(&bytes[1..1 + len]);
+                }
+                Some(out)
+            }
+            ByteEncoding::Hex => {
+                if !s.len().is_multiple_of(2) {
+                    return None;
+                }
+                let digit = |c: u8| HEX.iter().position(|&h| h == c).map(|d| d as u8);
+                s.chunks(2)
+                    .map(|pair| Some(digit(pair[0])? << 4 | digit(pair[1])?))
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ByteEncoding;
+
+    #[test]
+    fn decode() {
+        let cases = [
+            (ByteEncoding::Base64, "Zm9vYg=="),
+            (ByteEncoding::Base64UrlNoPad, "Zm9vYg"),
+            (ByteEncoding::Hex, "666f6f62"),
+        ];
+        for (encoding, s) in cases {
+            assert_eq!(encoding.decode(s).unwrap(), b"foob", "{encoding:?}");
+            assert_eq!(encoding.decode("").unwrap(), b"", "{encoding:?}");
+        }
+        assert_eq!(ByteEncoding::Base64.decode("+/8=").unwrap(), [0xfb, 0xff]);
+        assert_eq!(
+            ByteEncoding::Base64UrlNoPad.decode("-_8").unwrap(),
+            [0xfb, 0xff]
+        );
+        assert_eq!(ByteEncoding::Array.decode(""), None);
+    }
+
+    #[test]
+    fn decode_is_exact() {
+        for (encoding, s) in [
+            (ByteEncoding::Base64, "Zm9vYg"),
+            (ByteEncoding::Base64, "Zm9vYh=="),
+            (ByteEncoding::Base64, "Zm9v-_=="),
+            (ByteEncoding::Base64UrlNoPad, "Zm9vYg=="),
+            (ByteEncoding::Base64UrlNoPad, "Zm9vY"),
+            (ByteEncoding::Base64UrlNoPad, "+/8"),
+            (ByteEncoding::Hex, "666F"),
+            (ByteEncoding::Hex, "666"),
+            (ByteEncoding::Hex, "Zm9v"),
+        ] {
+            assert_eq!(encoding.decode(s), None, "{encoding:?} {s}");
+        }
+    }
+}
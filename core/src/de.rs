@@ -0,0 +1,259 @@
+//! The consuming half of the event model.
+//!
+//! A [`Deserializer`] builds a value out of a stream of [`Event`]s that are
+//! pushed into it one at a time. Nothing in here drives the stream, so the
+//! same impl works with an `Iterator`, a `Stream` or a tokio codec.
+
+use std::fmt;
+
+use crate::{
+    event::{Atom, Event},
+    ByteEncoding,
+};
+
+pub trait Deserializer<'de>: Sized {
+    /// The partially built value.
+    type State;
+
+    fn get_state() -> Self::State;
+
+    /// Feeds the next event into the builder.
+    ///
+    /// Returns [`Progress::NeedMore`] until the final event of this value has been
+    /// pushed, at which point the built value is returned in [`Progress::Done`].
+    /// The state should not be used again after it has returned `Done` or an error.
+    fn push_event(state: &mut Self::State, event: Event<'de>) -> Result<Progress<Self>, Error>;
+
+    /// Hidden internal trait method to allow specializations of bytes.
+    ///
+    /// This is the inverse of `Serializer::__private_slice_as_bytes`, and allows
+    /// `Vec<u8>` to be built from a single `Atom::Bytes` rather than a `Seq`.
+    #[doc(hidden)]
+    #[inline]
+    fn __private_vec_from_bytes(_bytes: &[u8]) -> Option<Vec<Self>> {
+        None
+    }
+
+    /// Hidden internal trait method to allow bytes written as strings.
+    ///
+    /// Formats such as JSON have no byte strings, so bytes may come back as
+    /// an `Atom::Hinted` string in the decoder's configured encoding. This
+    /// allows `Vec<u8>` to be built from it.
+    #[doc(hidden)]
+    #[inline]
+    fn __private_vec_from_str(_s: &str, _encoding: ByteEncoding) -> Option<Vec<Self>> {
+        None
+    }
+
+    /// Hidden internal trait method to allow map keys written as strings.
+    ///
+    /// Formats such as JSON only have string keys, so an integer key comes back
+    /// as `Atom::Str`. Maps try this before pushing a string key as an event.
+    #[doc(hidden)]
+    #[inline]
+    fn __private_from_key(_key: &str) -> Option<Self> {
+        None
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Progress<T> {
+    NeedMore,
+    Done(T),
+}
+
+/// Builds a value from a complete sequence of events.
+///
+/// Errors if the events run out before the value is finished, or if there are
+/// events left over afterwards.
+pub fn from_events<'de, T, I>(events: I) -> Result<T, Error>
+where
+    T: Deserializer<'de>,
+    I: IntoIterator<Item = Event<'de>>,
+{
+    let mut state = T::get_state();
+    let mut events = events.into_iter();
+    loop {
+        let Some(event) = events.next() else {
+            return Err(Error::UnexpectedEof);
+        };
+        if let Progress::Done(value) = T::push_event(&mut state, event)? {
+            return match events.next() {
+                Some(event) => Err(Error::unexpected("end of events", &event)),
+                None => Ok(value),
+            };
+        }
+    }
+}
+
+/// Feeds `event` into a nested builder, starting a new one if none is in progress.
+///
+/// Returns the child's value once it completes, clearing `child`.
+#[inline]
+pub fn push_child<'de, T: Deserializer<'de>>(
+    child: &mut Option<T::State>,
+    event: Event<'de>,
+) -> Result<Option<T>, Error> {
+    let state = child.get_or_insert_with(T::get_state);
+    match T::push_event(state, event) {
+        Ok(Progress::NeedMore) => Ok(None),
+        Ok(Progress::Done(value)) => {
+            *child = None;
+            Ok(Some(value))
+        }
+        Err(e) => {
+            *child = None;
+            Err(e)
+        }
+    }
+}
+
+/// Skips over a single value of any shape, however deeply nested.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Ignore;
+
+impl<'de> Deserializer<'de> for Ignore {
+    /// How many containers deep we currently are.
+    type State = usize;
+
+    fn get_state() -> Self::State {
+        0
+    }
+
+    fn push_event(depth: &mut usize, event: Event<'de>) -> Result<Progress<Self>, Error> {
+        if crate::__private::track_depth(depth, &event)? {
+            Ok(Progress::Done(Ignore))
+        } else {
+            Ok(Progress::NeedMore)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
+pub enum Error {
+    /// The event does not fit the shape of the value being built.
+    Unexpected {
+        expected: &'static str,
+        found: Unexpected,
+    },
+    /// A number was outside of the range of the target type.
+    OutOfRange,
+    /// A sequence had the wrong number of elements for a fixed size type.
+    InvalidLength {
+        expected: usize,
+        found: usize,
+    },
+    /// The events ran out before the value was complete.
+    UnexpectedEof,
+    /// A required field was not present in the map.
+    MissingField(&'static str),
+    /// A field was present in the map more than once.
+    DuplicateField(&'static str),
+    /// A key was present in the map more than once.
+    DuplicateKey,
+    /// A field that the type does not know about, when unknown fields are denied.
+    UnknownField(String),
+    /// A variant name that the enum does not have.
+    UnknownVariant(String),
+    Custom(String),
+}
+
+impl Error {
+    pub fn unexpected(expected: &'static str, found: &Event<'_>) -> Self {
+        Error::Unexpected {
+            expected,
+            found: Unexpected::from(found),
+        }
+    }
+
+    pub fn custom(msg: impl fmt::Display) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unexpected { expected, found } => {
+                write!(f, "invalid type: expected {expected}, found {found}")
+            }
+            Error::OutOfRange => f.write_str("number out of range"),
+            Error::InvalidLength { expected, found } => {
+                write!(f, "invalid length {found}, expected {expected}")
+            }
+            Error::UnexpectedEof => f.write_str("unexpected end of events"),
+            Error::MissingField(field) => write!(f, "missing field `{field}`"),
+            Error::DuplicateField(field) => write!(f, "duplicate field `{field}`"),
+            Error::DuplicateKey => f.write_str("duplicate map key"),
+            Error::UnknownField(field) => write!(f, "unknown field `{field}`"),
+            Error::UnknownVariant(variant) => write!(f, "unknown variant `{variant}`"),
+            Error::Custom(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The kind of event that caused an [`Error::Unexpected`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Unexpected {
+    Null,
+    Bool,
+    Str,
+    Bytes,
+    Char,
+    U64,
+    I64,
+    F64,
+    Number,
+    RawJson,
+    MapStart,
+    MapEnd,
+    SeqStart,
+    SeqEnd,
+}
+
+impl From<&Event<'_>> for Unexpected {
+    fn from(event: &Event<'_>) -> Self {
+        match event {
+            Event::Atom(Atom::Null) => Unexpected::Null,
+            Event::Atom(Atom::Bool(_)) => Unexpected::Bool,
+            Event::Atom(Atom::Str(_) | Atom::Hinted(..)) => Unexpected::Str,
+            Event::Atom(Atom::Bytes(_)) => Unexpected::Bytes,
+            Event::Atom(Atom::Char(_)) => Unexpected::Char,
+            Event::Atom(Atom::U64(_)) => Unexpected::U64,
+            Event::Atom(Atom::I64(_)) => Unexpected::I64,
+            Event::Atom(Atom::F64(_)) => Unexpected::F64,
+            Event::Atom(Atom::Number(_)) => Unexpected::Number,
+            Event::Atom(Atom::RawJson(_)) => Unexpected::RawJson,
+            Event::MapStart(_) => Unexpected::MapStart,
+            Event::MapEnd => Unexpected::MapEnd,
+            Event::SeqStart(_) => Unexpected::SeqStart,
+            Event::SeqEnd => Unexpected::SeqEnd,
+        }
+    }
+}
+
+impl fmt::Display for Unexpected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Unexpected::Null => "null",
+            Unexpected::Bool => "boolean",
+            Unexpected::Str => "string",
+            Unexpected::Bytes => "bytes",
+            Unexpected::Char => "char",
+            Unexpected::U64 => "unsigned integer",
+            Unexpected::I64 => "signed integer",
+            Unexpected::F64 => "float",
+            Unexpected::Number => "number",
+            Unexpected::RawJson => "raw JSON",
+            Unexpected::MapStart => "map",
+            Unexpected::MapEnd => "end of map",
+            Unexpected::SeqStart => "sequence",
+            Unexpected::SeqEnd => "end of sequence",
+        };
+        f.write_str(s)
+    }
+}
@@ -1,5 +1,7 @@
 use std::borrow::Cow;
 
+use crate::ByteEncoding;
+
 #[derive(Debug, PartialEq, Clone)]
 #[non_exhaustive]
 pub enum Atom<'a> {
@@ -11,6 +13,27 @@ pub enum Atom<'a> {
     U64(u64),
     I64(i64),
     F64(f64),
+    /// A number in JSON's decimal syntax, like `12345678901234567890123`, for
+    /// numbers that `U64`, `I64` or `F64` cannot hold exactly.
+    Number(Cow<'a, str>),
+    /// A complete JSON value that is already encoded, to be written as it is.
+    RawJson(Cow<'a, str>),
+    /// A string from a text format that may stand for another atom, as the
+    /// [`Hints`] from the decoder's configuration allow. It is still a string
+    /// to anything that expects one.
+    Hinted(Cow<'a, str>, Hints),
+}
+
+/// What a [`Atom::Hinted`] string may be read as besides a string.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Hints {
+    /// The string may be bytes in this encoding.
+    pub bytes: Option<ByteEncoding>,
+    /// The string may be a float that has no other syntax in the format, as
+    /// `"NaN"`, `"Infinity"` or `"-Infinity"`.
+    pub non_finite: bool,
+    /// The string may be an integer, written in the one form an encoder would.
+    pub integers: bool,
 }
 
 impl<'a> Atom<'a> {
@@ -25,6 +48,9 @@ impl<'a> Atom<'a> {
             Atom::U64(v) => Atom::U64(v),
             Atom::I64(v) => Atom::I64(v),
             Atom::F64(v) => Atom::F64(v),
+            Atom::Number(ref v) => Atom::Number(Cow::Owned(v.to_string())),
+            Atom::RawJson(ref v) => Atom::RawJson(Cow::Owned(v.to_string())),
+            Atom::Hinted(ref v, hints) => Atom::Hinted(Cow::Owned(v.to_string()), hints),
         }
     }
 
@@ -39,6 +65,9 @@ impl<'a> Atom<'a> {
             Atom::U64(v) => Atom::U64(v),
             Atom::I64(v) => Atom::I64(v),
             Atom::F64(v) => Atom::F64(v),
+            Atom::Number(v) => Atom::Number(Cow::Owned(v.into_owned())),
+            Atom::RawJson(v) => Atom::RawJson(Cow::Owned(v.into_owned())),
+            Atom::Hinted(v, hints) => Atom::Hinted(Cow::Owned(v.into_owned()), hints),
         }
     }
 }
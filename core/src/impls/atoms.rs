@@ -1,9 +1,10 @@
 use std::borrow::Cow;
 
 use crate::{
+    de::{Deserializer, Error, Progress},
     event::{Atom, Event},
     try_polyfill::Try,
-    Serializer,
+    ByteEncoding, Serializer,
 };
 
 macro_rules! signed_int {
@@ -52,6 +53,108 @@ macro_rules! unsigned_int {
     };
 }
 
+macro_rules! deserialize_int {
+    ($int:ty) => {
+        impl<'de> Deserializer<'de> for $int {
+            type State = ();
+            fn get_state() {}
+
+            #[inline]
+            fn push_event(_state: &mut (), event: Event<'de>) -> Result<Progress<Self>, Error> {
+                int_from_event(event).map(Progress::Done)
+            }
+
+            #[inline]
+            fn __private_from_key(key: &str) -> Option<Self> {
+                is_quoted_integer(key).then(|| key.parse().ok()).flatten()
+            }
+        }
+    };
+}
+
+macro_rules! float {
+    ($float:ty) => {
+        impl Serializer for $float {
+            type State<'a> = ();
+            fn get_state(&self) {}
+
+            #[inline]
+            fn estimate_size(&self) -> usize {
+                1
+            }
+
+            #[inline]
+            fn try_fold_events<'a, B, R, F>(&'a self, _state: &mut (), init: B, mut f: F) -> R
+            where
+                R: Try<Continue = B>,
+                F: FnMut(B, Event<'a>) -> R,
+            {
+                f(init, Event::Atom(Atom::F64(*self as f64)))
+            }
+        }
+
+        impl<'de> Deserializer<'de> for $float {
+            type State = ();
+            fn get_state() {}
+
+            #[inline]
+            fn push_event(_state: &mut (), event: Event<'de>) -> Result<Progress<Self>, Error> {
+                float_from_event(&event).map(|f| Progress::Done(f as $float))
+            }
+        }
+    };
+}
+
+#[inline]
+fn float_from_event(event: &Event<'_>) -> Result<f64, Error> {
+    match event {
+        Event::Atom(Atom::F64(f)) => Ok(*f),
+        Event::Atom(Atom::U64(i)) => Ok(*i as f64),
+        Event::Atom(Atom::I64(i)) => Ok(*i as f64),
+        Event::Atom(Atom::Number(text)) => {
+            text.parse().map_err(|_| Error::unexpected("float", event))
+        }
+        // formats such as JSON can write the floats they have no syntax for as strings
+        Event::Atom(Atom::Hinted(s, hints)) if hints.non_finite => match &**s {
+            "NaN" => Ok(f64::NAN),
+            "Infinity" => Ok(f64::INFINITY),
+            "-Infinity" => Ok(f64::NEG_INFINITY),
+            _ => Err(Error::unexpected("float", event)),
+        },
+        event => Err(Error::unexpected("float", event)),
+    }
+}
+
+#[inline]
+fn int_from_event<T: TryFrom<u64> + TryFrom<i64>>(event: Event<'_>) -> Result<T, Error> {
+    match event {
+        Event::Atom(Atom::U64(i)) => T::try_from(i).map_err(|_| Error::OutOfRange),
+        Event::Atom(Atom::I64(i)) => T::try_from(i).map_err(|_| Error::OutOfRange),
+        // formats such as JSON can quote integers that a reader would round
+        Event::Atom(Atom::Hinted(s, hints)) if hints.integers && is_quoted_integer(&s) => {
+            if s.starts_with('-') {
+                let i: i64 = s.parse().map_err(|_| Error::OutOfRange)?;
+                T::try_from(i).map_err(|_| Error::OutOfRange)
+            } else {
+                let i: u64 = s.parse().map_err(|_| Error::OutOfRange)?;
+                T::try_from(i).map_err(|_| Error::OutOfRange)
+            }
+        }
+        event => Err(Error::unexpected("integer", &event)),
+    }
+}
+
+/// Whether `s` is an integer written the one way an encoder would, so not
+/// `"01"`, `"-0"` or `"+1"`.
+fn is_quoted_integer(s: &str) -> bool {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    match digits.as_bytes() {
+        [b'0'] => digits.len() == s.len(),
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    }
+}
+
 signed_int!(i8);
 signed_int!(i16);
 signed_int!(i32);
@@ -62,6 +165,19 @@ unsigned_int!(u32);
 unsigned_int!(u64);
 unsigned_int!(usize);
 
+deserialize_int!(i8);
+deserialize_int!(i16);
+deserialize_int!(i32);
+deserialize_int!(i64);
+deserialize_int!(isize);
+deserialize_int!(u16);
+deserialize_int!(u32);
+deserialize_int!(u64);
+deserialize_int!(usize);
+
+float!(f32);
+float!(f64);
+
 impl Serializer for u8 {
     type State<'a> = ();
     fn get_state(&self) {}
@@ -89,6 +205,31 @@ impl Serializer for u8 {
     }
 }
 
+impl<'de> Deserializer<'de> for u8 {
+    type State = ();
+    fn get_state() {}
+
+    #[inline]
+    fn push_event(_state: &mut (), event: Event<'de>) -> Result<Progress<Self>, Error> {
+        int_from_event(event).map(Progress::Done)
+    }
+
+    #[inline]
+    fn __private_vec_from_bytes(bytes: &[u8]) -> Option<Vec<u8>> {
+        Some(bytes.to_vec())
+    }
+
+    #[inline]
+    fn __private_vec_from_str(s: &str, encoding: ByteEncoding) -> Option<Vec<u8>> {
+        encoding.decode(s)
+    }
+
+    #[inline]
+    fn __private_from_key(key: &str) -> Option<Self> {
+        is_quoted_integer(key).then(|| key.parse().ok()).flatten()
+    }
+}
+
 impl Serializer for str {
     type State<'a> = ();
     fn get_state(&self) {}
@@ -126,3 +267,33 @@ impl Serializer for String {
         <str as Serializer>::try_fold_events(self, _state, init, f)
     }
 }
+
+/// Only succeeds if the string was borrowed from the source, eg an in-memory buffer.
+impl<'de> Deserializer<'de> for &'de str {
+    type State = ();
+    fn get_state() {}
+
+    #[inline]
+    fn push_event(_state: &mut (), event: Event<'de>) -> Result<Progress<Self>, Error> {
+        match event {
+            Event::Atom(Atom::Str(Cow::Borrowed(s)) | Atom::Hinted(Cow::Borrowed(s), _)) => {
+                Ok(Progress::Done(s))
+            }
+            event => Err(Error::unexpected("borrowed string", &event)),
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for String {
+    type State = ();
+    fn get_state() {}
+
+    #[inline]
+    fn push_event(_state: &mut (), event: Event<'de>) -> Result<Progress<Self>, Error> {
+        match event {
+            Event::Atom(Atom::Str(s) | Atom::Hinted(s, _)) => Ok(Progress::Done(s.into_owned())),
+            Event::Atom(Atom::Char(c)) => Ok(Progress::Done(c.to_string())),
+            event => Err(Error::unexpected("string", &event)),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
-    event::{Atom, Event},
+    de::{push_child, Deserializer, Error, Progress},
+    event::{Atom, Event, Hints},
     tri,
     try_polyfill::Try,
     Serializer,
@@ -157,13 +158,85 @@ impl<T: Serializer, const N: usize> Serializer for [T; N] {
     }
 }
 
+pub struct ListBuilder<'de, T: Deserializer<'de>> {
+    items: Option<Vec<T>>,
+    elem: Option<T::State>,
+}
+
+impl<'de, T: Deserializer<'de>> Deserializer<'de> for Vec<T> {
+    type State = ListBuilder<'de, T>;
+
+    fn get_state() -> Self::State {
+        ListBuilder {
+            items: None,
+            elem: None,
+        }
+    }
+
+    fn push_event(state: &mut Self::State, event: Event<'de>) -> Result<Progress<Self>, Error> {
+        let Some(items) = &mut state.items else {
+            return match event {
+                Event::SeqStart(len) => {
+                    // don't trust the length hint too much
+                    state.items = Some(Vec::with_capacity(len.unwrap_or(0).min(4096)));
+                    Ok(Progress::NeedMore)
+                }
+                Event::Atom(Atom::Bytes(b)) => match T::__private_vec_from_bytes(&b) {
+                    Some(items) => Ok(Progress::Done(items)),
+                    None => Err(Error::unexpected("sequence", &Event::Atom(Atom::Bytes(b)))),
+                },
+                Event::Atom(Atom::Hinted(s, hints @ Hints { bytes: Some(encoding), .. })) => {
+                    match T::__private_vec_from_str(&s, encoding) {
+                        Some(items) => Ok(Progress::Done(items)),
+                        None => {
+                            let event = Event::Atom(Atom::Hinted(s, hints));
+                            Err(Error::unexpected("sequence", &event))
+                        }
+                    }
+                }
+                event => Err(Error::unexpected("sequence", &event)),
+            };
+        };
+
+        if state.elem.is_none() && event == Event::SeqEnd {
+            return Ok(Progress::Done(std::mem::take(items)));
+        }
+        if let Some(item) = push_child::<T>(&mut state.elem, event)? {
+            items.push(item);
+        }
+        Ok(Progress::NeedMore)
+    }
+}
+
+impl<'de, T: Deserializer<'de>, const N: usize> Deserializer<'de> for [T; N] {
+    type State = ListBuilder<'de, T>;
+
+    fn get_state() -> Self::State {
+        <Vec<T> as Deserializer>::get_state()
+    }
+
+    fn push_event(state: &mut Self::State, event: Event<'de>) -> Result<Progress<Self>, Error> {
+        match <Vec<T> as Deserializer>::push_event(state, event)? {
+            Progress::NeedMore => Ok(Progress::NeedMore),
+            Progress::Done(items) => {
+                let found = items.len();
+                match <[T; N]>::try_from(items) {
+                    Ok(array) => Ok(Progress::Done(array)),
+                    Err(_) => Err(Error::InvalidLength { expected: N, found }),
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::borrow::Cow;
 
     use crate::{
-        event::{Atom, Event},
-        Serializer,
+        de::{from_events, Deserializer, Error, Progress},
+        event::{Atom, Event, Hints},
+        ByteEncoding, Serializer,
     };
 
     #[test]
@@ -233,4 +306,108 @@ mod tests {
 
         assert_eq!(v, [Event::Atom(Atom::Bytes(Cow::Borrowed(b"abcdef"))),]);
     }
+
+    #[test]
+    fn deserialize() {
+        let events = [
+            Event::SeqStart(Some(2)),
+            Event::SeqStart(None),
+            Event::Atom(Atom::U64(1)),
+            Event::SeqEnd,
+            Event::SeqStart(Some(0)),
+            Event::SeqEnd,
+            Event::SeqEnd,
+        ];
+
+        let v: Vec<Vec<u32>> = from_events(events).unwrap();
+        assert_eq!(v, [vec![1], vec![]]);
+    }
+
+    #[test]
+    fn deserialize_push() {
+        let mut state = <Vec<String> as Deserializer>::get_state();
+
+        let events = [
+            Event::SeqStart(Some(2)),
+            Event::Atom(Atom::Str("abc".into())),
+            Event::Atom(Atom::Str("def".into())),
+        ];
+        for event in events {
+            assert_eq!(
+                Vec::<String>::push_event(&mut state, event).unwrap(),
+                Progress::NeedMore
+            );
+        }
+
+        assert_eq!(
+            Vec::<String>::push_event(&mut state, Event::SeqEnd).unwrap(),
+            Progress::Done(vec!["abc".to_owned(), "def".to_owned()])
+        );
+    }
+
+    #[test]
+    fn deserialize_bytes() {
+        let v: Vec<u8> = from_events([Event::Atom(Atom::Bytes(Cow::Borrowed(b"abc")))]).unwrap();
+        assert_eq!(v, b"abc");
+
+        let err = from_events::<Vec<u16>, _>([Event::Atom(Atom::Bytes(Cow::Borrowed(b"abc")))]);
+        assert!(matches!(err, Err(Error::Unexpected { .. })));
+
+        // text formats write bytes as strings in the decoder's configured encoding
+        let hinted = |s, encoding| {
+            let hints = Hints {
+                bytes: Some(encoding),
+                ..Hints::default()
+            };
+            Event::Atom(Atom::Hinted(Cow::Borrowed(s), hints))
+        };
+        for (s, encoding) in [
+            ("+/8=", ByteEncoding::Base64),
+            ("-_8", ByteEncoding::Base64UrlNoPad),
+            ("fbff", ByteEncoding::Hex),
+        ] {
+            let v: Vec<u8> = from_events([hinted(s, encoding)]).unwrap();
+            assert_eq!(v, [0xfb, 0xff], "{s}");
+        }
+
+        for (s, encoding) in [
+            ("-_8", ByteEncoding::Base64),
+            ("+/8=", ByteEncoding::Base64UrlNoPad),
+            ("+/8", ByteEncoding::Base64),
+            ("FBFF", ByteEncoding::Hex),
+        ] {
+            let err = from_events::<Vec<u8>, _>([hinted(s, encoding)]);
+            assert!(matches!(err, Err(Error::Unexpected { .. })), "{s}");
+        }
+        // without a hint a string is never bytes
+        let err = from_events::<Vec<u8>, _>([Event::Atom(Atom::Str(Cow::Borrowed("Zm9v")))]);
+        assert!(matches!(err, Err(Error::Unexpected { .. })));
+        let err = from_events::<Vec<String>, _>([hinted("Zm9v", ByteEncoding::Base64)]);
+        assert!(matches!(err, Err(Error::Unexpected { .. })));
+    }
+
+    #[test]
+    fn deserialize_array() {
+        let a: [&str; 2] = from_events([
+            Event::SeqStart(Some(2)),
+            Event::from("abc"),
+            Event::from("def"),
+            Event::SeqEnd,
+        ])
+        .unwrap();
+        assert_eq!(a, ["abc", "def"]);
+
+        let err = from_events::<[u8; 3], _>([
+            Event::SeqStart(Some(1)),
+            Event::Atom(Atom::U64(1)),
+            Event::SeqEnd,
+        ]);
+        assert_eq!(
+            err,
+            Err(Error::InvalidLength {
+                expected: 3,
+                found: 1
+            })
+        );
+    }
 }
@@ -1,9 +1,15 @@
 use std::{
     collections::{btree_map, hash_map, BTreeMap, HashMap},
-    hash::BuildHasher,
+    hash::{BuildHasher, Hash},
 };
 
-use crate::{event::Event, tri, try_polyfill::Try, Serializer};
+use crate::{
+    de::{push_child, Deserializer, Error, Progress},
+    event::{Atom, Event},
+    tri,
+    try_polyfill::Try,
+    MapSerializer, Serializer,
+};
 
 pub enum MapState<'a, K: Serializer, V: Serializer, I: Iterator<Item = (&'a K, &'a V)>> {
     Start,
@@ -72,6 +78,8 @@ impl<K: Serializer, V: Serializer, S: BuildHasher> Serializer for HashMap<K, V,
     }
 }
 
+impl<K: Serializer, V: Serializer, S: BuildHasher> MapSerializer for HashMap<K, V, S> {}
+
 impl<K: Serializer, V: Serializer> Serializer for BTreeMap<K, V> {
     type State<'a>  = MapState<'a, K, V, btree_map::Iter<'a, K, V>> where Self: 'a;
     fn get_state(&self) -> Self::State<'_> {
@@ -128,11 +136,137 @@ impl<K: Serializer, V: Serializer> Serializer for BTreeMap<K, V> {
     }
 }
 
+impl<K: Serializer, V: Serializer> MapSerializer for BTreeMap<K, V> {}
+
+/// A map that can tell whether a key was already in it.
+pub trait InsertNew<K, V> {
+    /// Inserts the entry, or returns `false` if the key is already present.
+    fn insert_new(&mut self, key: K, value: V) -> bool;
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> InsertNew<K, V> for HashMap<K, V, S> {
+    fn insert_new(&mut self, key: K, value: V) -> bool {
+        match self.entry(key) {
+            hash_map::Entry::Occupied(_) => false,
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+                true
+            }
+        }
+    }
+}
+
+impl<K: Ord, V> InsertNew<K, V> for BTreeMap<K, V> {
+    fn insert_new(&mut self, key: K, value: V) -> bool {
+        match self.entry(key) {
+            btree_map::Entry::Occupied(_) => false,
+            btree_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+                true
+            }
+        }
+    }
+}
+
+pub struct MapBuilder<'de, K: Deserializer<'de>, V: Deserializer<'de>, M> {
+    map: Option<M>,
+    key: Option<K::State>,
+    pending: Option<K>,
+    value: Option<V::State>,
+}
+
+impl<'de, K: Deserializer<'de>, V: Deserializer<'de>, M> MapBuilder<'de, K, V, M>
+where
+    M: Default + InsertNew<K, V>,
+{
+    fn new() -> Self {
+        MapBuilder {
+            map: None,
+            key: None,
+            pending: None,
+            value: None,
+        }
+    }
+
+    fn push_event(&mut self, event: Event<'de>) -> Result<Progress<M>, Error> {
+        let Some(map) = &mut self.map else {
+            return match event {
+                Event::MapStart(_) => {
+                    self.map = Some(M::default());
+                    Ok(Progress::NeedMore)
+                }
+                event => Err(Error::unexpected("map", &event)),
+            };
+        };
+
+        match self.pending.take() {
+            None => {
+                if self.key.is_none() {
+                    match &event {
+                        Event::MapEnd => return Ok(Progress::Done(std::mem::take(map))),
+                        Event::Atom(Atom::Str(key)) => {
+                            if let Some(key) = K::__private_from_key(key) {
+                                self.pending = Some(key);
+                                return Ok(Progress::NeedMore);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                self.pending = push_child::<K>(&mut self.key, event)?;
+            }
+            Some(key) => match push_child::<V>(&mut self.value, event)? {
+                Some(value) => {
+                    if !map.insert_new(key, value) {
+                        return Err(Error::DuplicateKey);
+                    }
+                }
+                None => self.pending = Some(key),
+            },
+        }
+        Ok(Progress::NeedMore)
+    }
+}
+
+impl<'de, K, V, S> Deserializer<'de> for HashMap<K, V, S>
+where
+    K: Deserializer<'de> + Eq + Hash,
+    V: Deserializer<'de>,
+    S: BuildHasher + Default,
+{
+    type State = MapBuilder<'de, K, V, Self>;
+
+    fn get_state() -> Self::State {
+        MapBuilder::new()
+    }
+
+    fn push_event(state: &mut Self::State, event: Event<'de>) -> Result<Progress<Self>, Error> {
+        state.push_event(event)
+    }
+}
+
+impl<'de, K, V> Deserializer<'de> for BTreeMap<K, V>
+where
+    K: Deserializer<'de> + Ord,
+    V: Deserializer<'de>,
+{
+    type State = MapBuilder<'de, K, V, Self>;
+
+    fn get_state() -> Self::State {
+        MapBuilder::new()
+    }
+
+    fn push_event(state: &mut Self::State, event: Event<'de>) -> Result<Progress<Self>, Error> {
+        state.push_event(event)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
 
     use crate::{
+        de::{from_events, Error},
         event::{Atom, Event},
         Serializer,
     };
@@ -183,4 +317,89 @@ mod tests {
         ];
         assert_eq!(v, exp);
     }
+
+    #[test]
+    fn deserialize() {
+        let events = [
+            Event::MapStart(None),
+            Event::Atom(Atom::Str("abc".into())),
+            Event::SeqStart(Some(1)),
+            Event::Atom(Atom::I64(1)),
+            Event::SeqEnd,
+            Event::Atom(Atom::Str("def".into())),
+            Event::SeqStart(Some(0)),
+            Event::SeqEnd,
+            Event::MapEnd,
+        ];
+
+        let a: HashMap<String, Vec<i8>> = from_events(events.clone()).unwrap();
+        assert_eq!(
+            a,
+            HashMap::from([("abc".to_owned(), vec![1]), ("def".to_owned(), vec![])])
+        );
+
+        let b: BTreeMap<&str, Vec<i8>> = from_events(events).unwrap();
+        assert_eq!(b, BTreeMap::from([("abc", vec![1]), ("def", vec![])]));
+    }
+
+    #[test]
+    fn deserialize_errors() {
+        let err = from_events::<BTreeMap<String, u8>, _>([
+            Event::MapStart(None),
+            Event::Atom(Atom::Str("abc".into())),
+            Event::Atom(Atom::U64(256)),
+            Event::MapEnd,
+        ]);
+        assert_eq!(err, Err(Error::OutOfRange));
+
+        let err = from_events::<BTreeMap<String, u8>, _>([
+            Event::MapStart(None),
+            Event::Atom(Atom::Str("abc".into())),
+        ]);
+        assert_eq!(err, Err(Error::UnexpectedEof));
+    }
+
+    #[test]
+    fn string_keys() {
+        // formats with only string keys write integer keys as strings
+        let events = [
+            Event::MapStart(None),
+            Event::Atom(Atom::Str("-1".into())),
+            Event::Atom(Atom::U64(1)),
+            Event::Atom(Atom::I64(2)),
+            Event::Atom(Atom::U64(2)),
+            Event::MapEnd,
+        ];
+        let a: BTreeMap<i32, u8> = from_events(events.clone()).unwrap();
+        assert_eq!(a, BTreeMap::from([(-1, 1), (2, 2)]));
+
+        let err = from_events::<HashMap<u8, u8>, _>(events);
+        assert!(matches!(err, Err(Error::Unexpected { .. })));
+        // only the one way of writing each integer is read as a key
+        for key in ["01", "+1"] {
+            let err = from_events::<BTreeMap<u32, u32>, _>([
+                Event::MapStart(None),
+                Event::Atom(Atom::Str(key.into())),
+                Event::Atom(Atom::U64(1)),
+                Event::MapEnd,
+            ]);
+            assert!(matches!(err, Err(Error::Unexpected { .. })), "{key}");
+        }
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let events = [
+            Event::MapStart(None),
+            Event::Atom(Atom::Str("1".into())),
+            Event::Atom(Atom::U64(1)),
+            Event::Atom(Atom::U64(1)),
+            Event::Atom(Atom::U64(2)),
+            Event::MapEnd,
+        ];
+        let err = from_events::<BTreeMap<u32, u32>, _>(events.clone());
+        assert_eq!(err, Err(Error::DuplicateKey));
+        let err = from_events::<HashMap<u32, u32>, _>(events);
+        assert_eq!(err, Err(Error::DuplicateKey));
+    }
 }
@@ -1,4 +1,4 @@
-use crate::{event::Event, try_polyfill::Try, Serializer};
+use crate::{event::Event, try_polyfill::Try, MapSerializer, Serializer};
 
 mod atoms;
 mod list;
@@ -19,3 +19,5 @@ impl<T: Serializer + ?Sized> Serializer for &T {
         T::get_state(self)
     }
 }
+
+impl<T: MapSerializer + ?Sized> MapSerializer for &T {}
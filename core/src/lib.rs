@@ -2,11 +2,24 @@
 
 use std::{borrow::Cow, convert::Infallible};
 
-pub use event::{Atom, Event};
+pub use bytes::ByteEncoding;
+pub use de::{Deserializer, Progress};
+pub use event::{Atom, Event, Hints};
+pub use value::{Value, ValueRef};
+#[cfg(feature = "derive")]
+pub use sud_derive::{Deserializer, Serializer};
 use try_polyfill::Try;
 
+mod bytes;
+pub mod de;
 mod event;
 mod impls;
+pub mod path;
+pub mod pointer;
+pub mod value;
+#[doc(hidden)]
+#[path = "private.rs"]
+pub mod __private;
 
 pub trait Serializer {
     type State<'a>
@@ -79,6 +92,14 @@ pub trait Serializer {
     }
 }
 
+/// A [`Serializer`] whose events are always a single map, or `null` for a
+/// unit value.
+///
+/// The entries of such a value can be spliced into another map. That is what
+/// `#[sud(flatten)]` fields and the newtype variants of internally tagged
+/// enums do, so their types must implement this.
+pub trait MapSerializer: Serializer {}
+
 pub mod try_polyfill {
     use std::{convert::Infallible, ops::ControlFlow, task::Poll};
 
@@ -0,0 +1,1190 @@
+//! Querying event streams with JSONPath ([RFC 9535]).
+//!
+//! A subset of the RFC is supported: name, wildcard, index and slice
+//! selectors, child and descendant segments, and filters that test or compare
+//! a single member of the candidate, like `[?@.level == 'error']`. Queries
+//! that would select a value twice or out of document order are not, see
+//! [`Matcher`].
+//!
+//! ```
+//! use sud_core::{path::Path, Event};
+//!
+//! let events = [
+//!     Event::MapStart(None),
+//!     Event::from("events"),
+//!     Event::SeqStart(None),
+//!     Event::MapStart(None),
+//!     Event::from("id"),
+//!     Event::from(1u64),
+//!     Event::MapEnd,
+//!     Event::MapStart(None),
+//!     Event::from("id"),
+//!     Event::from(2u64),
+//!     Event::MapEnd,
+//!     Event::SeqEnd,
+//!     Event::MapEnd,
+//! ];
+//! let path: Path = "$.events[*].id".parse().unwrap();
+//! let matches: Vec<_> = path
+//!     .query(events.into_iter().map(Ok::<_, ()>))
+//!     .collect::<Result<_, _>>()
+//!     .unwrap();
+//! assert_eq!(matches, [[Event::from(1u64)], [Event::from(2u64)]]);
+//! ```
+//!
+//! [RFC 9535]: https://www.rfc-editor.org/rfc/rfc9535
+
+use std::{borrow::Cow, cmp::Ordering, collections::VecDeque, fmt, str::FromStr};
+
+use crate::{Atom, Event};
+
+/// A parsed JSONPath query, like `$.events[*].user.id` or `$..error`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Segment {
+    /// Whether the selectors apply to every descendant, rather than only to
+    /// the children.
+    descendant: bool,
+    selectors: Vec<Selector>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice {
+        start: Option<i64>,
+        end: Option<i64>,
+        step: i64,
+    },
+    Filter(Filter),
+}
+
+impl Selector {
+    /// Whether the selector needs the length of a sequence to pick from it.
+    fn needs_len(&self) -> bool {
+        match *self {
+            Selector::Index(i) => i < 0,
+            Selector::Slice { start, end, .. } => {
+                start.is_some_and(|i| i < 0) || end.is_some_and(|i| i < 0)
+            }
+            _ => false,
+        }
+    }
+
+    fn selects_key(&self, key: Option<&str>) -> bool {
+        match self {
+            Selector::Name(name) => key == Some(name),
+            Selector::Wildcard => true,
+            _ => false,
+        }
+    }
+
+    fn selects_index(&self, index: usize, len: Option<usize>) -> bool {
+        let index = index as i64;
+        let len = len.map(|len| len as i64);
+        let normalize = |i: i64, len: i64| if i >= 0 { i } else { len + i };
+        match *self {
+            Selector::Wildcard => true,
+            Selector::Index(i) if i >= 0 => index == i,
+            Selector::Index(i) => len.is_some_and(|len| index == len + i),
+            Selector::Slice { step: 0, .. } => false,
+            // steps are never negative, see `Parser::selector`
+            Selector::Slice { start, end, step } => match len {
+                Some(len) => {
+                    let lower = normalize(start.unwrap_or(0), len).clamp(0, len);
+                    let upper = normalize(end.unwrap_or(len), len).clamp(0, len);
+                    lower <= index && index < upper && (index - lower) % step == 0
+                }
+                // only reached when the bounds are not relative to the end
+                None => {
+                    let lower = start.unwrap_or(0);
+                    lower <= index
+                        && end.is_none_or(|end| index < end)
+                        && (index - lower) % step == 0
+                }
+            },
+            _ => false,
+        }
+    }
+}
+
+/// A filter selector, testing either the candidate itself or one of its
+/// members.
+#[derive(Debug, PartialEq, Clone)]
+struct Filter {
+    /// The member to test, or `None` for the candidate itself.
+    member: Option<String>,
+    /// Without a comparison, the filter tests that the member exists.
+    comparison: Option<(Op, Literal)>,
+}
+
+impl Filter {
+    /// Tests the first event of the value being filtered on, if there is one.
+    fn test(&self, value: Option<&Event<'_>>) -> bool {
+        let Some((op, literal)) = &self.comparison else {
+            return value.is_some();
+        };
+        let ordering = match value {
+            Some(Event::Atom(atom)) => literal.compare(atom),
+            // containers never equal a literal, and are not ordered
+            _ => None,
+        };
+        match op {
+            Op::Eq => ordering == Some(Ordering::Equal),
+            Op::Ne => ordering != Some(Ordering::Equal),
+            Op::Lt => ordering == Some(Ordering::Less),
+            Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Op::Gt => ordering == Some(Ordering::Greater),
+            Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Literal {
+    Null,
+    Bool(bool),
+    Str(String),
+    Num(f64),
+}
+
+impl Literal {
+    /// How `atom` compares to the literal. Only numbers and strings are
+    /// ordered, other values are only ever equal or not.
+    fn compare(&self, atom: &Atom<'_>) -> Option<Ordering> {
+        match (atom, self) {
+            (Atom::Null, Literal::Null) => Some(Ordering::Equal),
+            (Atom::Bool(a), Literal::Bool(b)) => (a == b).then_some(Ordering::Equal),
+            (Atom::Str(a) | Atom::Hinted(a, _), Literal::Str(b)) => Some((**a).cmp(&**b)),
+            (Atom::U64(a), Literal::Num(b)) => (*a as f64).partial_cmp(b),
+            (Atom::I64(a), Literal::Num(b)) => (*a as f64).partial_cmp(b),
+            (Atom::F64(a), Literal::Num(b)) => a.partial_cmp(b),
+            (Atom::Number(a), Literal::Num(b)) => a.parse::<f64>().ok()?.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+impl Path {
+    pub fn parse(path: &str) -> Result<Self, Error> {
+        Parser { path, pos: 0 }.path()
+    }
+
+    /// Finds the values that the query matches in `events`.
+    ///
+    /// See [`Matches`].
+    pub fn query<'a, I>(self, events: I) -> Matches<'a, I> {
+        Matches {
+            events,
+            matcher: Matcher::new(self),
+        }
+    }
+}
+
+impl FromStr for Path {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Path::parse(s)
+    }
+}
+
+struct Parser<'s> {
+    path: &'s str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.path.as_bytes().get(self.pos).copied()
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        let found = self.path[self.pos..].starts_with(s);
+        if found {
+            self.pos += s.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), Error> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(Error::InvalidSyntax(self.pos))
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn path(mut self) -> Result<Path, Error> {
+        self.expect("$")?;
+        let mut segments: Vec<Segment> = Vec::new();
+        loop {
+            self.whitespace();
+            if self.pos == self.path.len() {
+                return Ok(Path { segments });
+            }
+
+            // a second descendant segment can reach the same value through
+            // two ancestors, which RFC 9535 would return twice
+            if self.path[self.pos..].starts_with("..") && segments.iter().any(|s| s.descendant) {
+                return Err(Error::Unsupported(self.pos));
+            }
+            let descendant = self.eat("..");
+            let selectors = if self.eat("[") {
+                self.selectors()?
+            } else if descendant || self.eat(".") {
+                if self.eat("*") {
+                    vec![Selector::Wildcard]
+                } else {
+                    vec![Selector::Name(self.name()?)]
+                }
+            } else {
+                return Err(Error::InvalidSyntax(self.pos));
+            };
+            segments.push(Segment {
+                descendant,
+                selectors,
+            });
+        }
+    }
+
+    /// Reads a bracketed list of selectors, after the `[`.
+    fn selectors(&mut self) -> Result<Vec<Selector>, Error> {
+        let mut selectors = Vec::new();
+        loop {
+            self.whitespace();
+            let start = self.pos;
+            selectors.push(self.selector()?);
+            if !in_document_order(&selectors) {
+                return Err(Error::Unsupported(start));
+            }
+            self.whitespace();
+            if self.eat("]") {
+                return Ok(selectors);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector, Error> {
+        match self.peek() {
+            Some(b'\'' | b'"') => Ok(Selector::Name(self.string()?)),
+            Some(b'*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some(b'?') => {
+                self.pos += 1;
+                Ok(Selector::Filter(self.filter()?))
+            }
+            _ => {
+                let start = self.int()?;
+                self.whitespace();
+                if !self.eat(":") {
+                    return start
+                        .map(Selector::Index)
+                        .ok_or(Error::InvalidSyntax(self.pos));
+                }
+                self.whitespace();
+                let end = self.int()?;
+                self.whitespace();
+                let step = if self.eat(":") {
+                    self.whitespace();
+                    let pos = self.pos;
+                    let step = self.int()?;
+                    // RFC 9535 returns these in reverse, which would mean
+                    // holding back the whole sequence
+                    if step.is_some_and(|step| step < 0) {
+                        return Err(Error::Unsupported(pos));
+                    }
+                    step
+                } else {
+                    None
+                };
+                Ok(Selector::Slice {
+                    start,
+                    end,
+                    step: step.unwrap_or(1),
+                })
+            }
+        }
+    }
+
+    /// Reads an integer, if there is one.
+    fn int(&mut self) -> Result<Option<i64>, Error> {
+        let start = self.pos;
+        self.eat("-");
+        let digits = self.path[self.pos..]
+            .bytes()
+            .take_while(u8::is_ascii_digit)
+            .count();
+        self.pos += digits;
+        let text = &self.path[start..self.pos];
+        match text {
+            "" => Ok(None),
+            // no leading zeros, and no negative zero
+            "-" | "-0" => Err(Error::InvalidSyntax(start)),
+            _ if digits > 1 && text.trim_start_matches('-').starts_with('0') => {
+                Err(Error::InvalidSyntax(start))
+            }
+            _ => match text.parse::<i64>() {
+                Ok(i) if i.unsigned_abs() < 1 << 53 => Ok(Some(i)),
+                _ => Err(Error::InvalidSyntax(start)),
+            },
+        }
+    }
+
+    /// Reads a member name in shorthand, like `.name`.
+    fn name(&mut self) -> Result<String, Error> {
+        let rest = &self.path[self.pos..];
+        let len = rest
+            .char_indices()
+            .find(|&(i, c)| {
+                let first = c.is_ascii_alphabetic() || c == '_' || !c.is_ascii();
+                !(first || (i > 0 && c.is_ascii_digit()))
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        if len == 0 {
+            return Err(Error::InvalidSyntax(self.pos));
+        }
+        self.pos += len;
+        Ok(rest[..len].to_owned())
+    }
+
+    /// Reads a quoted string, with either kind of quotes.
+    fn string(&mut self) -> Result<String, Error> {
+        let quote = self.path[self.pos..].chars().next();
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let c = self.path[self.pos..].chars().next();
+            let Some(c) = c.filter(|&c| c >= ' ') else {
+                return Err(Error::InvalidSyntax(self.pos));
+            };
+            self.pos += c.len_utf8();
+            if Some(c) == quote {
+                return Ok(s);
+            }
+            if c != '\\' {
+                s.push(c);
+                continue;
+            }
+
+            let escape = self.peek().ok_or(Error::InvalidSyntax(self.pos))?;
+            self.pos += 1;
+            s.push(match escape {
+                b'b' => '\x08',
+                b'f' => '\x0c',
+                b'n' => '\n',
+                b'r' => '\r',
+                b't' => '\t',
+                b'/' => '/',
+                b'\\' => '\\',
+                b'u' => self.unicode()?,
+                b if Some(b as char) == quote => b as char,
+                _ => return Err(Error::InvalidSyntax(self.pos - 1)),
+            });
+        }
+    }
+
+    /// Reads the hex digits of a `\u` escape, and the low surrogate that
+    /// follows a high one.
+    fn unicode(&mut self) -> Result<char, Error> {
+        let start = self.pos - 2;
+        let high = self.hex(start)?;
+        let n = match high {
+            0xd800..=0xdbff => {
+                if !self.eat("\\u") {
+                    return Err(Error::InvalidSyntax(start));
+                }
+                let low = self.hex(start)?;
+                if !(0xdc00..=0xdfff).contains(&low) {
+                    return Err(Error::InvalidSyntax(start));
+                }
+                0x10000 + ((high as u32 - 0xd800) << 10) + (low as u32 - 0xdc00)
+            }
+            n => n as u32,
+        };
+        char::from_u32(n).ok_or(Error::InvalidSyntax(start))
+    }
+
+    fn hex(&mut self, start: usize) -> Result<u16, Error> {
+        let digits = self.path.get(self.pos..self.pos + 4);
+        let n = digits.and_then(|digits| u16::from_str_radix(digits, 16).ok());
+        self.pos += 4;
+        n.ok_or(Error::InvalidSyntax(start))
+    }
+
+    /// Reads a filter, after the `?`.
+    fn filter(&mut self) -> Result<Filter, Error> {
+        self.whitespace();
+        if !self.eat("@") {
+            return Err(Error::Unsupported(self.pos));
+        }
+        let member = if self.eat(".") {
+            Some(self.name()?)
+        } else if self.eat("[") {
+            self.whitespace();
+            if !matches!(self.peek(), Some(b'\'' | b'"')) {
+                return Err(Error::Unsupported(self.pos));
+            }
+            let name = self.string()?;
+            self.whitespace();
+            self.expect("]")?;
+            Some(name)
+        } else {
+            None
+        };
+        if matches!(self.peek(), Some(b'.' | b'[')) {
+            return Err(Error::Unsupported(self.pos));
+        }
+
+        self.whitespace();
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        let Some(&(_, op)) = ops.iter().find(|(s, _)| self.eat(s)) else {
+            return match self.peek() {
+                Some(b',' | b']') => Ok(Filter {
+                    member,
+                    comparison: None,
+                }),
+                _ => Err(Error::Unsupported(self.pos)),
+            };
+        };
+        self.whitespace();
+        let literal = self.literal()?;
+        Ok(Filter {
+            member,
+            comparison: Some((op, literal)),
+        })
+    }
+
+    fn literal(&mut self) -> Result<Literal, Error> {
+        if matches!(self.peek(), Some(b'\'' | b'"')) {
+            return Ok(Literal::Str(self.string()?));
+        }
+        for (s, literal) in [
+            ("null", Literal::Null),
+            ("true", Literal::Bool(true)),
+            ("false", Literal::Bool(false)),
+        ] {
+            if self.eat(s) {
+                return Ok(literal);
+            }
+        }
+
+        let start = self.pos;
+        let len = self.path[start..]
+            .bytes()
+            .take_while(|b| matches!(b, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'))
+            .count();
+        self.pos += len;
+        match self.path[start..self.pos].parse::<f64>() {
+            Ok(n) if len > 0 && n.is_finite() => Ok(Literal::Num(n)),
+            _ if len == 0 => Err(Error::Unsupported(start)),
+            _ => Err(Error::InvalidSyntax(start)),
+        }
+    }
+}
+
+/// Whether the values picked by a list of selectors come in document order,
+/// each at most once, which is the order RFC 9535 returns them in.
+///
+/// Several selectors are only allowed if each picks a single value: at most one
+/// name, since the order of members depends on the document, and indices from
+/// the start in increasing order.
+fn in_document_order(selectors: &[Selector]) -> bool {
+    if let [_] = selectors {
+        return true;
+    }
+    let mut names = 0;
+    let mut last_index = None;
+    selectors.iter().all(|selector| match *selector {
+        Selector::Name(_) => {
+            names += 1;
+            names == 1
+        }
+        Selector::Index(i) if i >= 0 && last_index.is_none_or(|last| last < i) => {
+            last_index = Some(i);
+            true
+        }
+        _ => false,
+    })
+}
+
+/// Finds the values that a [`Path`] matches, one event at a time.
+///
+/// Each match is returned by [`Matcher::next_match`] as the events of the
+/// whole value, once it is complete. Matches are returned in the order that
+/// they start in, so a match comes before any that are nested inside of it.
+/// Queries that RFC 9535 would answer in another order or with a value
+/// more than once, such as `$[1,0]`, `$[0,0]` or `$[::-1]`, are
+/// [`Error::Unsupported`] when parsed. The exception is a descendant segment
+/// that can select both a value and something nested inside of it, like
+/// `$..*`, where the RFC lists all of a value's children before any of their
+/// own children and the matcher keeps to document order.
+///
+/// Only the path down to the current event is kept, so apart from the
+/// matches themselves, memory use depends on how deeply nested the input is
+/// and not on its size. The exceptions are filters, where a candidate is held
+/// back until the member being tested has gone past, and indices and slices
+/// counting from the end of a sequence without a length, which hold back the
+/// whole sequence.
+///
+/// Each top level value in the stream is queried in turn.
+#[derive(Debug, Clone)]
+pub struct Matcher<'a> {
+    path: Path,
+    /// How many containers deep the stream currently is.
+    depth: usize,
+    /// The containers that the query still has to look inside of.
+    frames: Vec<Frame<'a>>,
+    /// A value that cannot be queried until more of it has been seen.
+    pending: Option<Box<Pending<'a>>>,
+    /// Matches in the order they start, which are open until they end.
+    groups: VecDeque<Group<'a>>,
+}
+
+#[derive(Debug, Clone)]
+struct Frame<'a> {
+    /// The depth of the stream inside of this container.
+    depth: usize,
+    /// The segments that apply to the children of this container.
+    positions: Vec<usize>,
+    kind: FrameKind<'a>,
+}
+
+#[derive(Debug, Clone)]
+enum FrameKind<'a> {
+    Map {
+        /// The key of the next value, once it has been seen.
+        key: Option<Option<Cow<'a, str>>>,
+    },
+    Seq {
+        index: usize,
+        len: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Pending<'a> {
+    buffer: Vec<Event<'a>>,
+    /// The segments that apply to the value, known so far.
+    positions: Vec<usize>,
+    /// How many containers deep into the value the buffer is.
+    depth: usize,
+    kind: PendingKind<'a>,
+}
+
+#[derive(Debug, Clone)]
+enum PendingKind<'a> {
+    /// Waiting for the filters on a value to be decided.
+    Filters {
+        tests: Vec<Test>,
+        key: Option<Option<Cow<'a, str>>>,
+    },
+    /// Waiting for the end of a sequence, to count its length.
+    Len(usize),
+}
+
+/// A filter on a candidate value, and whether it has passed yet.
+#[derive(Debug, Clone)]
+struct Test {
+    segment: usize,
+    selector: usize,
+    passed: Option<bool>,
+}
+
+#[derive(Debug, Clone)]
+struct Group<'a> {
+    events: Vec<Event<'a>>,
+    /// How many containers deep into the match the stream is.
+    depth: usize,
+    done: bool,
+}
+
+impl<'a> Matcher<'a> {
+    pub fn new(path: Path) -> Self {
+        Matcher {
+            path,
+            depth: 0,
+            frames: Vec::new(),
+            pending: None,
+            groups: VecDeque::new(),
+        }
+    }
+
+    /// Takes the next event of the stream.
+    pub fn push(&mut self, event: Event<'a>) {
+        match &mut self.pending {
+            Some(pending) => {
+                if pending.push(&self.path, event) {
+                    let pending = self.pending.take().expect("pending is set");
+                    self.replay(*pending);
+                }
+            }
+            None => self.step(event, None),
+        }
+    }
+
+    /// Returns the events of the next complete match, if there is one.
+    pub fn next_match(&mut self) -> Option<Vec<Event<'a>>> {
+        if self.groups.front()?.done {
+            self.groups.pop_front().map(|group| group.events)
+        } else {
+            None
+        }
+    }
+
+    fn step(&mut self, event: Event<'a>, resolved: Option<(Vec<usize>, Option<usize>)>) {
+        let before = self.depth;
+        match event {
+            Event::MapStart(_) | Event::SeqStart(_) => self.depth += 1,
+            Event::MapEnd | Event::SeqEnd => self.depth = self.depth.saturating_sub(1),
+            Event::Atom(_) => {}
+        }
+
+        let pending = match resolved {
+            Some((positions, len)) => self.visit(&event, positions, len),
+            None => self.structure(&event, before),
+        };
+        if let Some(mut pending) = pending {
+            // the value is seen again once it is resolved
+            self.depth = before;
+            if pending.push(&self.path, event) {
+                self.replay(pending);
+            } else {
+                self.pending = Some(Box::new(pending));
+            }
+            return;
+        }
+
+        for group in self.groups.iter_mut().filter(|group| !group.done) {
+            match event {
+                Event::MapStart(_) | Event::SeqStart(_) => group.depth += 1,
+                Event::MapEnd | Event::SeqEnd => group.depth -= 1,
+                Event::Atom(_) => {}
+            }
+            group.done = group.depth == 0;
+            group.events.push(event.clone());
+        }
+    }
+
+    /// Follows the event through the containers that the query looks inside.
+    fn structure(&mut self, event: &Event<'a>, before: usize) -> Option<Pending<'a>> {
+        let Some(frame) = self.frames.last_mut() else {
+            if before == 0 {
+                return self.visit(event, vec![0], None);
+            }
+            return None;
+        };
+        if before > frame.depth {
+            // inside of a value that the query does not look inside of
+            return None;
+        }
+        if matches!(event, Event::MapEnd | Event::SeqEnd) {
+            self.frames.pop();
+            return None;
+        }
+
+        let (positions, tests) = match &mut frame.kind {
+            FrameKind::Map { key: key @ None } => {
+                *key = Some(key_name(event));
+                return None;
+            }
+            FrameKind::Map { key } => {
+                let key = key.take().flatten();
+                children(&self.path, &frame.positions, |selector| {
+                    selector.selects_key(key.as_deref())
+                })
+            }
+            FrameKind::Seq { index, len } => {
+                let (i, len) = (*index, *len);
+                *index += 1;
+                children(&self.path, &frame.positions, |selector| {
+                    selector.selects_index(i, len)
+                })
+            }
+        };
+        if tests.is_empty() {
+            self.visit(event, positions, None)
+        } else {
+            Some(Pending {
+                buffer: Vec::new(),
+                positions,
+                depth: 0,
+                kind: PendingKind::Filters { tests, key: None },
+            })
+        }
+    }
+
+    /// Starts on a value that the segments at `positions` apply to.
+    fn visit(
+        &mut self,
+        event: &Event<'a>,
+        positions: Vec<usize>,
+        len: Option<usize>,
+    ) -> Option<Pending<'a>> {
+        let n = self.path.segments.len();
+        let matched = positions.last() == Some(&n);
+        let mut children = positions.clone();
+        if matched {
+            children.pop();
+        }
+
+        if !children.is_empty() {
+            let kind = match *event {
+                Event::MapStart(_) => Some(FrameKind::Map { key: None }),
+                Event::SeqStart(size) => {
+                    let len = len.or(size);
+                    let needs_len = children.iter().any(|&q| {
+                        self.path.segments[q]
+                            .selectors
+                            .iter()
+                            .any(Selector::needs_len)
+                    });
+                    if len.is_none() && needs_len {
+                        return Some(Pending {
+                            buffer: Vec::new(),
+                            positions,
+                            depth: 0,
+                            kind: PendingKind::Len(0),
+                        });
+                    }
+                    Some(FrameKind::Seq { index: 0, len })
+                }
+                Event::Atom(_) | Event::MapEnd | Event::SeqEnd => None,
+            };
+            if let Some(kind) = kind {
+                self.frames.push(Frame {
+                    depth: self.depth,
+                    positions: children,
+                    kind,
+                });
+            }
+        }
+
+        if matched {
+            self.groups.push_back(Group {
+                events: Vec::new(),
+                depth: 0,
+                done: false,
+            });
+        }
+        None
+    }
+
+    /// Carries on with a value that has been held back, now that it can be
+    /// queried.
+    fn replay(&mut self, pending: Pending<'a>) {
+        let mut positions = pending.positions;
+        let mut len = None;
+        match pending.kind {
+            PendingKind::Filters { tests, .. } => {
+                for test in tests.iter().filter(|test| test.passed == Some(true)) {
+                    positions.push(test.segment + 1);
+                }
+                positions.sort_unstable();
+                positions.dedup();
+            }
+            PendingKind::Len(n) => len = Some(n),
+        }
+
+        let mut events = pending.buffer.into_iter();
+        if let Some(event) = events.next() {
+            self.step(event, Some((positions, len)));
+        }
+        for event in events {
+            self.push(event);
+        }
+    }
+}
+
+/// The segments that apply to a child, given those that apply to its parent,
+/// and the filters that still have to be tested on the child.
+fn children(
+    path: &Path,
+    parent: &[usize],
+    selects: impl Fn(&Selector) -> bool,
+) -> (Vec<usize>, Vec<Test>) {
+    let mut positions = Vec::new();
+    let mut tests = Vec::new();
+    for &q in parent {
+        let segment = &path.segments[q];
+        if segment.descendant {
+            positions.push(q);
+        }
+        for (i, selector) in segment.selectors.iter().enumerate() {
+            if let Selector::Filter(_) = selector {
+                tests.push(Test {
+                    segment: q,
+                    selector: i,
+                    passed: None,
+                });
+            } else if selects(selector) {
+                positions.push(q + 1);
+            }
+        }
+    }
+    positions.sort_unstable();
+    positions.dedup();
+    // a filter cannot select a child that is already selected
+    tests.retain(|test| !positions.contains(&(test.segment + 1)));
+    (positions, tests)
+}
+
+/// The name that a key event can be selected by.
+fn key_name<'a>(event: &Event<'a>) -> Option<Cow<'a, str>> {
+    match event {
+        Event::Atom(Atom::Str(key)) => Some(key.clone()),
+        Event::Atom(Atom::U64(key)) => Some(Cow::Owned(key.to_string())),
+        Event::Atom(Atom::I64(key)) => Some(Cow::Owned(key.to_string())),
+        _ => None,
+    }
+}
+
+impl<'a> Pending<'a> {
+    /// Holds back the next event of the value, returning whether the value
+    /// can now be queried.
+    fn push(&mut self, path: &Path, event: Event<'a>) -> bool {
+        let before = self.depth;
+        match event {
+            Event::MapStart(_) | Event::SeqStart(_) => self.depth += 1,
+            Event::MapEnd | Event::SeqEnd => self.depth = self.depth.saturating_sub(1),
+            Event::Atom(_) => {}
+        }
+
+        let resolved = match &mut self.kind {
+            PendingKind::Filters { tests, key } => {
+                let filter =
+                    |test: &Test| match &path.segments[test.segment].selectors[test.selector] {
+                        Selector::Filter(filter) => filter,
+                        _ => unreachable!("tests are only made for filters"),
+                    };
+                if before == 0 {
+                    for test in tests.iter_mut() {
+                        if filter(test).member.is_none() {
+                            test.passed = Some(filter(test).test(Some(&event)));
+                        }
+                    }
+                } else if before == 1 && matches!(self.buffer[0], Event::MapStart(_)) {
+                    match key.take() {
+                        None if !matches!(event, Event::MapEnd) => *key = Some(key_name(&event)),
+                        None => {}
+                        Some(key) => {
+                            for test in tests.iter_mut().filter(|test| test.passed.is_none()) {
+                                let member = filter(test).member.as_deref();
+                                if member.is_some() && member == key.as_deref() {
+                                    test.passed = Some(filter(test).test(Some(&event)));
+                                }
+                            }
+                        }
+                    }
+                }
+                if self.depth == 0 {
+                    // the members that have not been seen do not exist
+                    for test in tests.iter_mut().filter(|test| test.passed.is_none()) {
+                        test.passed = Some(filter(test).test(None));
+                    }
+                }
+                tests.iter().all(|test| test.passed.is_some())
+            }
+            PendingKind::Len(n) => {
+                if before == 1 && !matches!(event, Event::MapEnd | Event::SeqEnd) {
+                    *n += 1;
+                }
+                self.depth == 0
+            }
+        };
+        self.buffer.push(event);
+        resolved
+    }
+}
+
+/// An iterator over the values that a [`Path`] matches.
+///
+/// Created by [`Path::query`]. Each item is the events of one matching value;
+/// see [`Matcher`]. Errors from the source are passed on as they are.
+#[derive(Debug, Clone)]
+pub struct Matches<'a, I> {
+    events: I,
+    matcher: Matcher<'a>,
+}
+
+impl<'a, I, E> Iterator for Matches<'a, I>
+where
+    I: Iterator<Item = Result<Event<'a>, E>>,
+{
+    type Item = Result<Vec<Event<'a>>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(events) = self.matcher.next_match() {
+                return Some(Ok(events));
+            }
+            match self.events.next()? {
+                Ok(event) => self.matcher.push(event),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Error {
+    /// The query is not valid JSONPath, at this offset.
+    InvalidSyntax(usize),
+    /// The query uses a part of JSONPath that is not supported, at this
+    /// offset.
+    Unsupported(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidSyntax(offset) => write!(f, "invalid JSONPath at offset {offset}"),
+            Error::Unsupported(offset) => {
+                write!(f, "unsupported JSONPath feature at offset {offset}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Matcher, Path};
+    use crate::{de::from_events, Event, Serializer, Value};
+
+    fn str(s: &str) -> Value {
+        Value::Str(s.to_owned())
+    }
+
+    fn map(entries: &[(&str, Value)]) -> Value {
+        Value::Map(entries.iter().map(|(k, v)| (str(k), v.clone())).collect())
+    }
+
+    fn document() -> Value {
+        let user = |id| map(&[("id", Value::U64(id))]);
+        map(&[
+            (
+                "events",
+                Value::Seq(vec![
+                    map(&[("level", str("info")), ("user", user(1))]),
+                    map(&[
+                        ("user", user(2)),
+                        ("level", str("error")),
+                        ("error", str("boom")),
+                    ]),
+                    map(&[("level", str("error")), ("user", user(3))]),
+                ]),
+            ),
+            ("error", map(&[("error", str("nested"))])),
+        ])
+    }
+
+    fn events(value: &Value, lengths: bool) -> Vec<Event<'_>> {
+        let mut events = Vec::new();
+        value.for_each_event(&mut value.get_state(), |event| {
+            events.push(match event {
+                Event::SeqStart(_) if !lengths => Event::SeqStart(None),
+                Event::MapStart(_) if !lengths => Event::MapStart(None),
+                event => event,
+            })
+        });
+        events
+    }
+
+    fn query(path: &str, value: &Value) -> Vec<Value> {
+        let path = Path::parse(path).unwrap();
+        let matches = |lengths| -> Vec<Value> {
+            let events = events(value, lengths).into_iter().map(Ok::<_, ()>);
+            path.clone()
+                .query(events)
+                .map(|events| from_events(events.unwrap()).unwrap())
+                .collect()
+        };
+        // the same whether or not sequences have their length up front
+        let matches_without = matches(false);
+        assert_eq!(matches(true), matches_without, "{path:?}");
+        matches_without
+    }
+
+    #[test]
+    fn children() {
+        let doc = document();
+        assert_eq!(query("$", &doc), std::slice::from_ref(&doc));
+        assert_eq!(
+            query("$.events[*].user.id", &doc),
+            [Value::U64(1), Value::U64(2), Value::U64(3)]
+        );
+        assert_eq!(query("$['events'][1][\"error\"]", &doc), [str("boom")]);
+        assert_eq!(query("$.missing.id", &doc), []);
+        assert_eq!(query("$.events.level", &doc), []);
+    }
+
+    #[test]
+    fn indices() {
+        let doc = document();
+        let levels = |path| query(path, &doc);
+        let [info, error] = [str("info"), str("error")];
+        assert_eq!(levels("$.events[0].level"), std::slice::from_ref(&info));
+        assert_eq!(levels("$.events[-1].level"), std::slice::from_ref(&error));
+        assert_eq!(levels("$.events[-4].level"), []);
+        assert_eq!(levels("$.events[1:].level"), [error.clone(), error.clone()]);
+        assert_eq!(levels("$.events[:-1].level"), [info.clone(), error.clone()]);
+        assert_eq!(levels("$.events[::2].level"), [info.clone(), error.clone()]);
+        assert_eq!(levels("$.events[0:3:0].level"), []);
+        assert_eq!(levels("$.events[0,2].level"), [info, error]);
+    }
+
+    #[test]
+    fn descendants() {
+        let doc = document();
+        assert_eq!(
+            query("$..error", &doc),
+            [str("boom"), map(&[("error", str("nested"))]), str("nested")]
+        );
+        assert_eq!(
+            query("$..id", &doc),
+            [Value::U64(1), Value::U64(2), Value::U64(3)]
+        );
+        assert_eq!(query("$.events..[1]", &doc).len(), 1);
+        assert_eq!(query("$.error..*", &doc), [str("nested")]);
+    }
+
+    #[test]
+    fn filters() {
+        let doc = document();
+        assert_eq!(
+            query("$.events[?@.level == 'error'].user.id", &doc),
+            [Value::U64(2), Value::U64(3)]
+        );
+        assert_eq!(
+            query("$.events[?@['level'] != \"error\"].user.id", &doc),
+            [Value::U64(1)]
+        );
+        assert_eq!(query("$.events[?@.error].user.id", &doc), [Value::U64(2)]);
+        assert_eq!(query("$.events[?@.user == 1]", &doc), []);
+        assert_eq!(
+            query("$..[?@.id >= 2].id", &doc),
+            [Value::U64(2), Value::U64(3)]
+        );
+
+        let numbers = Value::Seq(vec![
+            Value::U64(1),
+            Value::F64(2.5),
+            Value::I64(-3),
+            str("4"),
+            Value::Null,
+        ]);
+        assert_eq!(query("$[?@ > 1]", &numbers), [Value::F64(2.5)]);
+        assert_eq!(
+            query("$[?@ <= 1.0]", &numbers),
+            [Value::U64(1), Value::I64(-3)]
+        );
+        assert_eq!(query("$[?@ == null]", &numbers), [Value::Null]);
+        assert_eq!(query("$[?@ < '5']", &numbers), [str("4")]);
+    }
+
+    #[test]
+    fn each_value() {
+        let mut matcher = Matcher::new(Path::parse("$.a").unwrap());
+        let events = [
+            Event::MapStart(None),
+            Event::from("a"),
+            Event::from(1u64),
+            Event::MapEnd,
+            Event::from(2u64),
+            Event::MapStart(None),
+            Event::from("a"),
+            Event::SeqStart(None),
+            Event::SeqEnd,
+            Event::MapEnd,
+        ];
+        let mut matches = Vec::new();
+        for event in events {
+            matcher.push(event);
+            matches.extend(std::iter::from_fn(|| matcher.next_match()));
+        }
+        assert_eq!(
+            matches,
+            [
+                vec![Event::from(1u64)],
+                vec![Event::SeqStart(None), Event::SeqEnd]
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = [
+            ("events", Error::InvalidSyntax(0)),
+            ("$.", Error::InvalidSyntax(2)),
+            ("$.[0]", Error::InvalidSyntax(2)),
+            ("$[01]", Error::InvalidSyntax(2)),
+            ("$[-0]", Error::InvalidSyntax(2)),
+            ("$['a]", Error::InvalidSyntax(5)),
+            ("$['\\x']", Error::InvalidSyntax(4)),
+            ("$[0 1]", Error::InvalidSyntax(4)),
+            ("$[?@.a && @.b]", Error::Unsupported(7)),
+            ("$[?length(@) > 1]", Error::Unsupported(3)),
+            ("$[?@.a.b]", Error::Unsupported(6)),
+            // RFC 9535 would return these in another order, or twice
+            ("$[::-1]", Error::Unsupported(4)),
+            ("$[0,0]", Error::Unsupported(4)),
+            ("$[2, 0]", Error::Unsupported(5)),
+            ("$[0,-1]", Error::Unsupported(4)),
+            ("$[0,*]", Error::Unsupported(4)),
+            ("$[0:2,1]", Error::Unsupported(6)),
+            ("$['a','b']", Error::Unsupported(6)),
+            ("$..a..b", Error::Unsupported(4)),
+        ];
+        for (path, err) in cases {
+            assert_eq!(Path::parse(path), Err(err), "{path}");
+        }
+
+        let doc = Value::Map(vec![
+            (str("\u{1f600}"), map(&[("x", Value::U64(1))])),
+            (str("a'b"), map(&[("x", Value::U64(2))])),
+        ]);
+        for (path, x) in [("$['\\ud83d\\ude00'] .x", 1u64), ("$['a\\'b'].x", 2)] {
+            let path = Path::parse(path).unwrap();
+            let matches: Vec<_> = path
+                .query(events(&doc, true).into_iter().map(Ok::<_, ()>))
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(matches, [[Event::from(x)]]);
+        }
+    }
+}
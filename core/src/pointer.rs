@@ -0,0 +1,443 @@
+//! Selecting one value out of an event stream with a JSON Pointer ([RFC 6901]).
+//!
+//! ```
+//! use sud_core::{pointer::Pointer, Event};
+//!
+//! let events = [
+//!     Event::MapStart(None),
+//!     Event::from("items"),
+//!     Event::SeqStart(None),
+//!     Event::from("first"),
+//!     Event::from("second"),
+//!     Event::SeqEnd,
+//!     Event::MapEnd,
+//! ];
+//! let pointer: Pointer = "/items/1".parse().unwrap();
+//! let selected: Vec<_> = pointer
+//!     .select(events.into_iter().map(Ok::<_, ()>))
+//!     .collect::<Result<_, _>>()
+//!     .unwrap();
+//! assert_eq!(selected, [Event::from("second")]);
+//! ```
+//!
+//! [RFC 6901]: https://www.rfc-editor.org/rfc/rfc6901
+
+use std::{fmt, str::FromStr};
+
+use crate::{Atom, Event};
+
+/// A parsed JSON Pointer, like `/items/3/name`.
+///
+/// Each reference token names a map key, or an index into a sequence. The
+/// empty pointer refers to the whole value.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Pointer {
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Token {
+    key: String,
+    /// The token as an array index, if it is written as one.
+    index: Option<u64>,
+}
+
+impl Token {
+    fn new(key: String) -> Self {
+        // no leading zeros, and `-` (past the end) never matches anything
+        let index = match key.as_bytes() {
+            [b'0'] => Some(0),
+            [b'1'..=b'9', rest @ ..] if rest.iter().all(u8::is_ascii_digit) => key.parse().ok(),
+            _ => None,
+        };
+        Token { key, index }
+    }
+
+    fn matches_key(&self, event: &Event<'_>) -> bool {
+        match event {
+            Event::Atom(Atom::Str(key)) => *key == self.key,
+            Event::Atom(Atom::U64(key)) => self.index == Some(*key),
+            Event::Atom(Atom::I64(key)) => self.key == key.to_string(),
+            _ => false,
+        }
+    }
+}
+
+impl Pointer {
+    pub fn parse(pointer: &str) -> Result<Self, Error> {
+        if pointer.is_empty() {
+            return Ok(Pointer::default());
+        }
+        let Some(rest) = pointer.strip_prefix('/') else {
+            return Err(Error::MissingSlash);
+        };
+
+        let mut tokens = Vec::new();
+        let mut offset = 1;
+        for token in rest.split('/') {
+            let mut key = String::with_capacity(token.len());
+            let mut chars = token.char_indices();
+            while let Some((i, c)) = chars.next() {
+                if c != '~' {
+                    key.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some((_, '0')) => key.push('~'),
+                    Some((_, '1')) => key.push('/'),
+                    _ => return Err(Error::InvalidEscape(offset + i)),
+                }
+            }
+            offset += token.len() + 1;
+            tokens.push(Token::new(key));
+        }
+        Ok(Pointer { tokens })
+    }
+
+    /// Selects the value that the pointer refers to out of `events`.
+    ///
+    /// See [`Select`].
+    pub fn select<I>(self, events: I) -> Select<I> {
+        Select {
+            events,
+            selector: Selector::new(self),
+        }
+    }
+}
+
+impl FromStr for Pointer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Pointer::parse(s)
+    }
+}
+
+/// Picks the events of the value that a [`Pointer`] refers to, one event at a
+/// time.
+///
+/// Every other value is skipped over as its events go past, keeping only the
+/// depth, so nothing is built up in memory however large the input is. This
+/// suits sources that push events, like a codec; [`Select`] wraps it up for
+/// an iterator.
+///
+/// Each top level value in the stream is selected from in turn, so a stream
+/// of several values gives the matching value from each of them.
+#[derive(Debug, Clone)]
+pub struct Selector {
+    pointer: Pointer,
+    /// How many containers deep the stream currently is.
+    depth: usize,
+    /// How many tokens of the pointer the current value matches.
+    matched: usize,
+    state: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// Waiting for a top level value.
+    Root,
+    /// Looking for the next token amongst the entries of a map.
+    Map { key: bool, matches: bool },
+    /// Looking for the next token amongst the elements of a sequence.
+    Seq { index: u64 },
+    /// Passing on the selected value.
+    Emit,
+    /// The selected value is over, or does not exist.
+    Done,
+}
+
+impl Selector {
+    pub fn new(pointer: Pointer) -> Self {
+        Selector {
+            pointer,
+            depth: 0,
+            matched: 0,
+            state: State::Root,
+        }
+    }
+
+    /// Whether the current top level value has nothing more to select.
+    ///
+    /// The rest of the value can be skipped without reading it, if the source
+    /// allows that.
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Takes the next event of the stream, returning it back if it is part of
+    /// the selected value.
+    pub fn push<'a>(&mut self, event: Event<'a>) -> Option<Event<'a>> {
+        let before = self.depth;
+        match event {
+            Event::MapStart(_) | Event::SeqStart(_) => self.depth += 1,
+            Event::MapEnd | Event::SeqEnd => self.depth = self.depth.saturating_sub(1),
+            Event::Atom(_) => {}
+        }
+
+        match self.state {
+            State::Done if before == 0 => {
+                self.matched = 0;
+                self.descend(event)
+            }
+            State::Root => self.descend(event),
+            State::Done => None,
+            State::Emit => {
+                if self.depth == self.matched {
+                    self.state = State::Done;
+                }
+                Some(event)
+            }
+            // inside of a value that does not match
+            _ if before > self.matched + 1 => None,
+            State::Map { key: true, .. } => {
+                self.state = match event {
+                    Event::MapEnd => State::Done,
+                    key => State::Map {
+                        key: false,
+                        matches: self.pointer.tokens[self.matched].matches_key(&key),
+                    },
+                };
+                None
+            }
+            State::Map {
+                key: false,
+                matches,
+            } => {
+                self.state = State::Map {
+                    key: true,
+                    matches: false,
+                };
+                if matches {
+                    self.matched += 1;
+                    self.descend(event)
+                } else {
+                    None
+                }
+            }
+            State::Seq { index } => {
+                if matches!(event, Event::SeqEnd) {
+                    self.state = State::Done;
+                    return None;
+                }
+                self.state = State::Seq { index: index + 1 };
+                if self.pointer.tokens[self.matched].index == Some(index) {
+                    self.matched += 1;
+                    self.descend(event)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Starts on a value that matches the first `matched` tokens.
+    fn descend<'a>(&mut self, event: Event<'a>) -> Option<Event<'a>> {
+        if self.matched == self.pointer.tokens.len() {
+            self.state = if self.depth == self.matched {
+                State::Done
+            } else {
+                State::Emit
+            };
+            return Some(event);
+        }
+
+        self.state = match event {
+            Event::MapStart(_) => State::Map {
+                key: true,
+                matches: false,
+            },
+            Event::SeqStart(_) => State::Seq { index: 0 },
+            _ => State::Done,
+        };
+        None
+    }
+}
+
+/// An iterator over the events of the value that a [`Pointer`] refers to.
+///
+/// Created by [`Pointer::select`]. Errors from the source are passed on as
+/// they are. Only the first top level value of the source is selected from,
+/// and the source is not read any further once the selected value is over, so
+/// the rest of a large document is never even parsed.
+#[derive(Debug, Clone)]
+pub struct Select<I> {
+    events: I,
+    selector: Selector,
+}
+
+impl<'a, I, E> Iterator for Select<I>
+where
+    I: Iterator<Item = Result<Event<'a>, E>>,
+{
+    type Item = Result<Event<'a>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.selector.is_done() {
+            match self.events.next()? {
+                Ok(event) => {
+                    if let Some(event) = self.selector.push(event) {
+                        return Some(Ok(event));
+                    }
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Error {
+    /// A non-empty pointer did not start with `/`.
+    MissingSlash,
+    /// A `~` at this offset was not followed by `0` or `1`.
+    InvalidEscape(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingSlash => f.write_str("JSON pointer does not start with `/`"),
+            Error::InvalidEscape(offset) => {
+                write!(f, "invalid escape in JSON pointer at offset {offset}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{Error, Pointer, Selector};
+    use crate::{de::from_events, Atom, Event, Serializer, Value};
+
+    fn str(s: &str) -> Value {
+        Value::Str(s.to_owned())
+    }
+
+    /// The example document from RFC 6901.
+    fn document() -> Value {
+        Value::Map(vec![
+            (str("foo"), Value::Seq(vec![str("bar"), str("baz")])),
+            (str(""), Value::U64(0)),
+            (str("a/b"), Value::U64(1)),
+            (str("c%d"), Value::U64(2)),
+            (str("e^f"), Value::U64(3)),
+            (str("g|h"), Value::U64(4)),
+            (str("i\\j"), Value::U64(5)),
+            (str("k\"l"), Value::U64(6)),
+            (str(" "), Value::U64(7)),
+            (str("m~n"), Value::U64(8)),
+        ])
+    }
+
+    fn select(pointer: &str, value: &Value) -> Option<Value> {
+        let mut events = Vec::new();
+        value.for_each_event(&mut value.get_state(), |event| events.push(event));
+        let pointer = Pointer::parse(pointer).unwrap();
+        let selected: Vec<_> = pointer
+            .select(events.into_iter().map(Ok::<_, ()>))
+            .map(Result::unwrap)
+            .collect();
+        (!selected.is_empty()).then(|| from_events(selected).unwrap())
+    }
+
+    #[test]
+    fn rfc_examples() {
+        let doc = document();
+        assert_eq!(select("", &doc), Some(doc.clone()));
+        assert_eq!(
+            select("/foo", &doc),
+            Some(Value::Seq(vec![str("bar"), str("baz")]))
+        );
+        assert_eq!(select("/foo/0", &doc), Some(str("bar")));
+        let cases = [
+            ("/", 0),
+            ("/a~1b", 1),
+            ("/c%d", 2),
+            ("/e^f", 3),
+            ("/g|h", 4),
+            ("/i\\j", 5),
+            ("/k\"l", 6),
+            ("/ ", 7),
+            ("/m~0n", 8),
+        ];
+        for (pointer, n) in cases {
+            assert_eq!(select(pointer, &doc), Some(Value::U64(n)), "{pointer}");
+        }
+    }
+
+    #[test]
+    fn missing() {
+        let doc = document();
+        for pointer in ["/bar", "/foo/2", "/foo/-", "/foo/01", "/foo/0/x", "/a~1b/0"] {
+            assert_eq!(select(pointer, &doc), None, "{pointer}");
+        }
+
+        // other values are skipped however they are nested, and keys need not
+        // be strings
+        let doc = Value::Map(vec![
+            (
+                Value::Seq(vec![str("x")]),
+                Value::Map(vec![(str("x"), Value::Null)]),
+            ),
+            (str("x"), Value::Seq(vec![Value::Seq(vec![]), Value::Null])),
+            (Value::U64(3), str("three")),
+            (Value::I64(-1), str("minus one")),
+        ]);
+        assert_eq!(select("/x/1", &doc), Some(Value::Null));
+        assert_eq!(select("/3", &doc), Some(str("three")));
+        assert_eq!(select("/-1", &doc), Some(str("minus one")));
+    }
+
+    #[test]
+    fn stops_early() {
+        // anything after the selected value is not read
+        let events = [
+            Ok(Event::SeqStart(None)),
+            Ok(Event::Atom(Atom::Str(Cow::Borrowed("a")))),
+            Ok(Event::Atom(Atom::U64(1))),
+            Err("not read"),
+        ];
+        let pointer = Pointer::parse("/1").unwrap();
+        let selected: Result<Vec<_>, _> = pointer.select(events.into_iter()).collect();
+        assert_eq!(selected, Ok(vec![Event::Atom(Atom::U64(1))]));
+    }
+
+    #[test]
+    fn each_value() {
+        let mut selector = Selector::new(Pointer::parse("/a").unwrap());
+        let events = [
+            Event::MapStart(None),
+            Event::from("a"),
+            Event::from(1u64),
+            Event::MapEnd,
+            Event::from(2u64),
+            Event::MapStart(None),
+            Event::from("a"),
+            Event::SeqStart(None),
+            Event::SeqEnd,
+            Event::MapEnd,
+        ];
+        let selected: Vec<_> = events
+            .into_iter()
+            .filter_map(|e| selector.push(e))
+            .collect();
+        assert_eq!(
+            selected,
+            [Event::from(1u64), Event::SeqStart(None), Event::SeqEnd]
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(Pointer::parse("a"), Err(Error::MissingSlash));
+        assert_eq!(Pointer::parse("/a/b~"), Err(Error::InvalidEscape(4)));
+        assert_eq!(Pointer::parse("/~2"), Err(Error::InvalidEscape(1)));
+    }
+}
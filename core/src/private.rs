@@ -0,0 +1,510 @@
+//! Support code for `sud-derive`. Not public API.
+
+use std::{borrow::Cow, marker::PhantomData};
+
+pub use crate::de::push_child;
+use crate::{
+    de::{Error, Progress, Unexpected},
+    event::{Atom, Event},
+    tri,
+    try_polyfill::Try,
+    MapSerializer, Serializer,
+};
+
+/// Serializes a map-like value with a `tag: variant` entry inserted at the front.
+///
+/// This is how newtype variants of internally tagged enums are represented.
+/// A unit value becomes a map containing only the tag. A value that is not a
+/// map after all, which only a faulty [`MapSerializer`] impl can produce, is
+/// passed on as the entry after the tag, so that the output is an unbalanced
+/// map for a strict encoder to reject.
+pub struct Tagged<'a, T: Serializer + ?Sized + 'a> {
+    state: T::State<'a>,
+    tag: &'static str,
+    variant: &'static str,
+    /// How many of the opening events have been emitted.
+    sent: u8,
+    /// Whether we still owe the `MapEnd` of a map we opened around the value.
+    close: bool,
+}
+
+impl<'a, T: MapSerializer + ?Sized> Tagged<'a, T> {
+    const DONE: u8 = u8::MAX;
+    /// The value is over, and only our `MapEnd` is left.
+    const CLOSING: u8 = u8::MAX - 1;
+
+    pub fn new(value: &'a T, tag: &'static str, variant: &'static str) -> Self {
+        Tagged {
+            state: value.get_state(),
+            tag,
+            variant,
+            sent: 0,
+            close: false,
+        }
+    }
+
+    pub fn try_fold_events<B, R, F>(&mut self, value: &'a T, mut init: B, mut f: F) -> R
+    where
+        R: Try<Continue = B>,
+        F: FnMut(B, Event<'a>) -> R,
+    {
+        let Tagged {
+            state,
+            tag,
+            variant,
+            sent,
+            close,
+        } = self;
+
+        if *sent != Self::CLOSING {
+            init = tri!(value.try_fold_events(state, init, |mut acc, event| {
+                if *sent == Self::DONE {
+                    return f(acc, event);
+                }
+
+                // the first event of the value opens our map. If we get interrupted,
+                // the value will offer this event again and we carry on from `sent`.
+                let (len, unit) = match event {
+                    Event::MapStart(len) => (len.map(|len| len + 1), false),
+                    Event::Atom(Atom::Null) => (Some(1), true),
+                    _ => (None, false),
+                };
+                let count = if unit { 4 } else { 3 };
+                while *sent < count {
+                    let event = match *sent {
+                        0 => Event::MapStart(len),
+                        1 => Event::Atom(Atom::Str(Cow::Borrowed(tag))),
+                        2 => Event::Atom(Atom::Str(Cow::Borrowed(variant))),
+                        _ => Event::MapEnd,
+                    };
+                    acc = tri!(f(acc, event));
+                    *sent += 1;
+                }
+                *sent = Self::DONE;
+                if len.is_none() && !matches!(event, Event::MapStart(_)) {
+                    *close = true;
+                    return f(acc, event);
+                }
+                R::from_continue(acc)
+            }));
+            if !*close {
+                return R::from_continue(init);
+            }
+            *sent = Self::CLOSING;
+        }
+
+        let acc = tri!(f(init, Event::MapEnd));
+        *close = false;
+        *sent = Self::DONE;
+        R::from_continue(acc)
+    }
+}
+
+/// Serializes the entries of a map-like value without the surrounding map.
+///
+/// This is how `#[sud(flatten)]` fields are written into their parent. A unit
+/// value has no entries. A value that is not a map after all, which only a
+/// faulty [`MapSerializer`] impl can produce, is passed on as it is.
+pub struct Flatten<'a, T: Serializer + ?Sized + 'a> {
+    state: T::State<'a>,
+    depth: usize,
+}
+
+impl<'a, T: MapSerializer + ?Sized> Flatten<'a, T> {
+    pub fn new(value: &'a T) -> Self {
+        Flatten {
+            state: value.get_state(),
+            depth: 0,
+        }
+    }
+
+    pub fn try_fold_events<B, R, F>(&mut self, value: &'a T, init: B, mut f: F) -> R
+    where
+        R: Try<Continue = B>,
+        F: FnMut(B, Event<'a>) -> R,
+    {
+        let Flatten { state, depth } = self;
+
+        value.try_fold_events(state, init, |acc, event| match event {
+            Event::MapStart(_) if *depth == 0 => {
+                *depth = 1;
+                R::from_continue(acc)
+            }
+            Event::Atom(Atom::Null) if *depth == 0 => R::from_continue(acc),
+            Event::MapEnd if *depth == 1 => {
+                *depth = 0;
+                R::from_continue(acc)
+            }
+            Event::MapStart(_) | Event::SeqStart(_) => {
+                let acc = tri!(f(acc, event));
+                *depth += 1;
+                R::from_continue(acc)
+            }
+            Event::MapEnd | Event::SeqEnd => {
+                let acc = tri!(f(acc, event));
+                *depth -= 1;
+                R::from_continue(acc)
+            }
+            event => f(acc, event),
+        })
+    }
+}
+
+/// Tracks how deeply nested the events of a single value are.
+///
+/// Returns true once `event` completes the value.
+pub fn track_depth(depth: &mut usize, event: &Event<'_>) -> Result<bool, Error> {
+    match event {
+        Event::MapStart(_) | Event::SeqStart(_) => *depth += 1,
+        Event::MapEnd | Event::SeqEnd => match depth.checked_sub(1) {
+            Some(d) => *depth = d,
+            None => return Err(Error::unexpected("value", event)),
+        },
+        Event::Atom(_) => {}
+    }
+    Ok(*depth == 0)
+}
+
+/// The variants of a derived enum, independent of how they are tagged.
+pub trait Variants<'de>: Sized {
+    /// The builder for the content of any one variant.
+    type Content;
+
+    const VARIANTS: &'static [&'static str];
+
+    fn variant(name: &str) -> Option<usize>;
+
+    /// Builds a unit variant without any content.
+    fn unit(index: usize) -> Option<Self>;
+
+    /// Starts building the content of a variant.
+    ///
+    /// The content of a unit variant is `null`.
+    fn content(index: usize) -> Self::Content;
+
+    fn push_content(
+        content: &mut Self::Content,
+        event: Event<'de>,
+    ) -> Result<Progress<Self>, Error>;
+}
+
+fn variant_index<'de, T: Variants<'de>>(event: Event<'de>) -> Result<usize, Error> {
+    match event {
+        Event::Atom(Atom::Str(name) | Atom::Hinted(name, _)) => {
+            T::variant(&name).ok_or_else(|| Error::UnknownVariant(name.into_owned()))
+        }
+        event => Err(Error::unexpected("variant name", &event)),
+    }
+}
+
+/// `{"Variant": content}`, or `"Variant"` for unit variants.
+pub enum ExternallyTagged<'de, T: Variants<'de>> {
+    Start,
+    Variant,
+    Content(T::Content),
+    End(Option<T>),
+}
+
+impl<'de, T: Variants<'de>> ExternallyTagged<'de, T> {
+    pub fn new() -> Self {
+        ExternallyTagged::Start
+    }
+
+    pub fn push_event(&mut self, event: Event<'de>) -> Result<Progress<T>, Error> {
+        match self {
+            ExternallyTagged::Start => match event {
+                Event::MapStart(_) => *self = ExternallyTagged::Variant,
+                Event::Atom(Atom::Str(_) | Atom::Hinted(..)) => {
+                    let index = variant_index::<T>(event)?;
+                    return T::unit(index).map(Progress::Done).ok_or(Error::Unexpected {
+                        expected: "map",
+                        found: Unexpected::Str,
+                    });
+                }
+                event => return Err(Error::unexpected("enum", &event)),
+            },
+            ExternallyTagged::Variant => {
+                let index = variant_index::<T>(event)?;
+                *self = ExternallyTagged::Content(T::content(index));
+            }
+            ExternallyTagged::Content(content) => {
+                if let Progress::Done(value) = T::push_content(content, event)? {
+                    *self = ExternallyTagged::End(Some(value));
+                }
+            }
+            ExternallyTagged::End(value) => match event {
+                Event::MapEnd => return Ok(Progress::Done(value.take().unwrap())),
+                event => return Err(Error::unexpected("end of map", &event)),
+            },
+        }
+        Ok(Progress::NeedMore)
+    }
+}
+
+impl<'de, T: Variants<'de>> Default for ExternallyTagged<'de, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `{"tag": "Variant", ...content}`
+///
+/// Any entries that come before the tag are buffered until the variant is known.
+pub struct InternallyTagged<'de, T: Variants<'de>> {
+    tag: &'static str,
+    state: Internal<'de, T>,
+}
+
+enum Internal<'de, T: Variants<'de>> {
+    Start,
+    /// Looking for the tag.
+    Entries {
+        buffer: Vec<Event<'de>>,
+        depth: usize,
+        key: bool,
+    },
+    /// Found the tag, the next event is the variant name.
+    Variant(Vec<Event<'de>>),
+    Content(T::Content),
+    /// A unit variant, which ignores the rest of the map.
+    Unit(Option<T>, usize),
+}
+
+impl<'de, T: Variants<'de>> InternallyTagged<'de, T> {
+    pub fn new(tag: &'static str) -> Self {
+        InternallyTagged {
+            tag,
+            state: Internal::Start,
+        }
+    }
+
+    pub fn push_event(&mut self, event: Event<'de>) -> Result<Progress<T>, Error> {
+        match &mut self.state {
+            Internal::Start => match event {
+                Event::MapStart(_) => {
+                    self.state = Internal::Entries {
+                        buffer: Vec::new(),
+                        depth: 0,
+                        key: true,
+                    }
+                }
+                event => return Err(Error::unexpected("map", &event)),
+            },
+            Internal::Entries { buffer, depth, key } => {
+                if *key {
+                    match &event {
+                        Event::Atom(Atom::Str(k)) if k == self.tag => {
+                            self.state = Internal::Variant(std::mem::take(buffer));
+                            return Ok(Progress::NeedMore);
+                        }
+                        Event::MapEnd => return Err(Error::MissingField(self.tag)),
+                        _ => {}
+                    }
+                }
+                if track_depth(depth, &event)? {
+                    *key = !*key;
+                }
+                buffer.push(event);
+            }
+            Internal::Variant(buffer) => {
+                let index = variant_index::<T>(event)?;
+                if let Some(value) = T::unit(index) {
+                    self.state = Internal::Unit(Some(value), 0);
+                    return Ok(Progress::NeedMore);
+                }
+
+                let buffer = std::mem::take(buffer);
+                let mut content = T::content(index);
+                let events = std::iter::once(Event::MapStart(None)).chain(buffer);
+                for event in events {
+                    if let Progress::Done(_) = T::push_content(&mut content, event)? {
+                        return Err(Error::custom("variant content ended early"));
+                    }
+                }
+                self.state = Internal::Content(content);
+            }
+            Internal::Content(content) => return T::push_content(content, event),
+            Internal::Unit(value, depth) => {
+                if *depth == 0 && event == Event::MapEnd {
+                    return Ok(Progress::Done(value.take().unwrap()));
+                }
+                track_depth(depth, &event)?;
+            }
+        }
+        Ok(Progress::NeedMore)
+    }
+}
+
+/// `{"tag": "Variant", "content": content}`
+///
+/// If the content comes before the tag, it is buffered until the variant is known.
+pub struct AdjacentlyTagged<'de, T: Variants<'de>> {
+    tag: &'static str,
+    content: &'static str,
+    started: bool,
+    phase: Adjacent,
+    /// Nesting of the content being buffered.
+    depth: usize,
+    variant: Option<usize>,
+    buffer: Option<Vec<Event<'de>>>,
+    state: Option<T::Content>,
+    value: Option<T>,
+}
+
+enum Adjacent {
+    Key,
+    Tag,
+    Content,
+    Ignore(usize),
+}
+
+impl<'de, T: Variants<'de>> AdjacentlyTagged<'de, T> {
+    pub fn new(tag: &'static str, content: &'static str) -> Self {
+        AdjacentlyTagged {
+            tag,
+            content,
+            started: false,
+            phase: Adjacent::Key,
+            depth: 0,
+            variant: None,
+            buffer: None,
+            state: None,
+            value: None,
+        }
+    }
+
+    pub fn push_event(&mut self, event: Event<'de>) -> Result<Progress<T>, Error> {
+        if !self.started {
+            return match event {
+                Event::MapStart(_) => {
+                    self.started = true;
+                    Ok(Progress::NeedMore)
+                }
+                event => Err(Error::unexpected("map", &event)),
+            };
+        }
+
+        match &mut self.phase {
+            Adjacent::Key => match event {
+                Event::Atom(Atom::Str(key)) if key == self.tag => {
+                    if self.variant.is_some() {
+                        return Err(Error::DuplicateField(self.tag));
+                    }
+                    self.phase = Adjacent::Tag;
+                }
+                Event::Atom(Atom::Str(key)) if key == self.content => {
+                    if self.value.is_some() || self.buffer.is_some() {
+                        return Err(Error::DuplicateField(self.content));
+                    }
+                    match self.variant {
+                        Some(index) => self.state = Some(T::content(index)),
+                        None => self.buffer = Some(Vec::new()),
+                    }
+                    self.phase = Adjacent::Content;
+                }
+                Event::Atom(Atom::Str(_)) => self.phase = Adjacent::Ignore(0),
+                Event::MapEnd => {
+                    let Some(index) = self.variant else {
+                        return Err(Error::MissingField(self.tag));
+                    };
+                    return match self.value.take() {
+                        Some(value) => Ok(Progress::Done(value)),
+                        None => T::unit(index)
+                            .map(Progress::Done)
+                            .ok_or(Error::MissingField(self.content)),
+                    };
+                }
+                event => return Err(Error::unexpected("field name", &event)),
+            },
+            Adjacent::Tag => {
+                let index = variant_index::<T>(event)?;
+                self.variant = Some(index);
+                self.phase = Adjacent::Key;
+
+                // replay the content we saw before the tag
+                if let Some(buffer) = self.buffer.take() {
+                    let mut content = T::content(index);
+                    for event in buffer {
+                        if let Progress::Done(value) = T::push_content(&mut content, event)? {
+                            self.value = Some(value);
+                        }
+                    }
+                    if self.value.is_none() {
+                        return Err(Error::UnexpectedEof);
+                    }
+                }
+            }
+            Adjacent::Content => match (&mut self.state, &mut self.buffer) {
+                (Some(content), _) => {
+                    if let Progress::Done(value) = T::push_content(content, event)? {
+                        self.value = Some(value);
+                        self.state = None;
+                        self.phase = Adjacent::Key;
+                    }
+                }
+                (None, Some(buffer)) => {
+                    if track_depth(&mut self.depth, &event)? {
+                        self.phase = Adjacent::Key;
+                    }
+                    buffer.push(event);
+                }
+                (None, None) => unreachable!(),
+            },
+            Adjacent::Ignore(depth) => {
+                if track_depth(depth, &event)? {
+                    self.phase = Adjacent::Key;
+                }
+            }
+        }
+        Ok(Progress::NeedMore)
+    }
+}
+
+/// Just the content. Each variant is tried in order until one of them accepts it.
+pub struct Untagged<'de, T: Variants<'de>> {
+    buffer: Vec<Event<'de>>,
+    depth: usize,
+    _variants: PhantomData<T>,
+}
+
+impl<'de, T: Variants<'de>> Untagged<'de, T> {
+    pub fn new() -> Self {
+        Untagged {
+            buffer: Vec::new(),
+            depth: 0,
+            _variants: PhantomData,
+        }
+    }
+
+    pub fn push_event(&mut self, event: Event<'de>) -> Result<Progress<T>, Error> {
+        let done = track_depth(&mut self.depth, &event)?;
+        self.buffer.push(event);
+        if !done {
+            return Ok(Progress::NeedMore);
+        }
+
+        let buffer = std::mem::take(&mut self.buffer);
+        for index in 0..T::VARIANTS.len() {
+            let mut content = T::content(index);
+            let mut result = Ok(Progress::NeedMore);
+            for event in buffer.iter().cloned() {
+                result = T::push_content(&mut content, event);
+                if !matches!(result, Ok(Progress::NeedMore)) {
+                    break;
+                }
+            }
+            if let Ok(Progress::Done(value)) = result {
+                return Ok(Progress::Done(value));
+            }
+        }
+        Err(Error::custom(
+            "data did not match any variant of untagged enum",
+        ))
+    }
+}
+
+impl<'de, T: Variants<'de>> Default for Untagged<'de, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,552 @@
+//! A format agnostic tree of data.
+
+use std::{borrow::Cow, slice};
+
+use crate::{
+    de::{Deserializer, Error, Progress},
+    event::{Atom, Event},
+    tri,
+    try_polyfill::Try,
+    Serializer,
+};
+
+/// Any value that can be described by [`Event`]s.
+///
+/// Maps keep their entries in the order they were read, and their keys can be any value.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Str(String),
+    Bytes(Vec<u8>),
+    Char(char),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Number(String),
+    RawJson(String),
+    Seq(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+}
+
+impl From<Atom<'_>> for Value {
+    fn from(atom: Atom<'_>) -> Self {
+        match atom {
+            Atom::Null => Value::Null,
+            Atom::Bool(v) => Value::Bool(v),
+            Atom::Str(v) | Atom::Hinted(v, _) => Value::Str(v.into_owned()),
+            Atom::Bytes(v) => Value::Bytes(v.into_owned()),
+            Atom::Char(v) => Value::Char(v),
+            Atom::U64(v) => Value::U64(v),
+            Atom::I64(v) => Value::I64(v),
+            Atom::F64(v) => Value::F64(v),
+            Atom::Number(v) => Value::Number(v.into_owned()),
+            Atom::RawJson(v) => Value::RawJson(v.into_owned()),
+        }
+    }
+}
+
+/// A borrowed version of [`Value`].
+///
+/// Strings and bytes keep borrowing from the events they were built from, so
+/// building one over an in-memory buffer doesn't need to allocate for them.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum ValueRef<'a> {
+    #[default]
+    Null,
+    Bool(bool),
+    Str(Cow<'a, str>),
+    Bytes(Cow<'a, [u8]>),
+    Char(char),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Number(Cow<'a, str>),
+    RawJson(Cow<'a, str>),
+    Seq(Vec<ValueRef<'a>>),
+    Map(Vec<(ValueRef<'a>, ValueRef<'a>)>),
+}
+
+impl<'a> From<Atom<'a>> for ValueRef<'a> {
+    fn from(atom: Atom<'a>) -> Self {
+        match atom {
+            Atom::Null => ValueRef::Null,
+            Atom::Bool(v) => ValueRef::Bool(v),
+            Atom::Str(v) | Atom::Hinted(v, _) => ValueRef::Str(v),
+            Atom::Bytes(v) => ValueRef::Bytes(v),
+            Atom::Char(v) => ValueRef::Char(v),
+            Atom::U64(v) => ValueRef::U64(v),
+            Atom::I64(v) => ValueRef::I64(v),
+            Atom::F64(v) => ValueRef::F64(v),
+            Atom::Number(v) => ValueRef::Number(v),
+            Atom::RawJson(v) => ValueRef::RawJson(v),
+        }
+    }
+}
+
+impl Value {
+    /// Drops the value without recursing into it.
+    ///
+    /// Dropping a value normally recurses once per level of nesting, which can
+    /// overflow the call stack for very deep values.
+    pub fn drop_iterative(mut self) {
+        drop_tree(&mut self);
+    }
+}
+
+impl ValueRef<'_> {
+    /// Drops the value without recursing into it, like
+    /// [`Value::drop_iterative`].
+    pub fn drop_iterative(mut self) {
+        drop_tree(&mut self);
+    }
+
+    /// Copies any borrowed data to make an owned [`Value`].
+    pub fn to_value(&self) -> Value {
+        let mut builder = <Value as Deserializer>::get_state();
+        let value =
+            self.fold_events(
+                &mut self.get_state(),
+                None,
+                |value, event| match Value::push_event(&mut builder, event) {
+                    Ok(Progress::Done(value)) => Some(value),
+                    Ok(Progress::NeedMore) => value,
+                    Err(_) => unreachable!("a tree always produces balanced events"),
+                },
+            );
+        value.expect("a tree always produces a complete value")
+    }
+}
+
+/// What [`Value`] and [`ValueRef`] have in common, so they can share the
+/// serializer and builder.
+trait Tree<'de>: Sized {
+    fn view(&self) -> View<'_, Self>;
+
+    fn atom(atom: Atom<'de>) -> Self;
+    fn seq(seq: Vec<Self>) -> Self;
+    fn map(map: Vec<(Self, Self)>) -> Self;
+
+    /// Moves the values this one contains onto `into`, leaving it empty.
+    fn take_children(&mut self, into: &mut Vec<Self>);
+}
+
+/// Drops the children of a tree with an explicit stack rather than
+/// recursion, so that deeply nested values can't overflow the call stack.
+fn drop_tree<'de, T: Tree<'de>>(tree: &mut T) {
+    let mut stack = Vec::new();
+    tree.take_children(&mut stack);
+    while let Some(mut child) = stack.pop() {
+        child.take_children(&mut stack);
+    }
+}
+
+enum View<'a, T> {
+    Atom(Atom<'a>),
+    Seq(&'a [T]),
+    Map(&'a [(T, T)]),
+}
+
+macro_rules! tree {
+    ($ty:ident) => {
+        fn view(&self) -> View<'_, Self> {
+            View::Atom(match self {
+                $ty::Null => Atom::Null,
+                $ty::Bool(v) => Atom::Bool(*v),
+                $ty::Str(v) => Atom::Str(Cow::Borrowed(v)),
+                $ty::Bytes(v) => Atom::Bytes(Cow::Borrowed(v)),
+                $ty::Char(v) => Atom::Char(*v),
+                $ty::U64(v) => Atom::U64(*v),
+                $ty::I64(v) => Atom::I64(*v),
+                $ty::F64(v) => Atom::F64(*v),
+                $ty::Number(v) => Atom::Number(Cow::Borrowed(v)),
+                $ty::RawJson(v) => Atom::RawJson(Cow::Borrowed(v)),
+                $ty::Seq(seq) => return View::Seq(seq),
+                $ty::Map(map) => return View::Map(map),
+            })
+        }
+
+        fn atom(atom: Atom<'de>) -> Self {
+            $ty::from(atom)
+        }
+
+        fn seq(seq: Vec<Self>) -> Self {
+            $ty::Seq(seq)
+        }
+
+        fn map(map: Vec<(Self, Self)>) -> Self {
+            $ty::Map(map)
+        }
+
+        fn take_children(&mut self, into: &mut Vec<Self>) {
+            match self {
+                $ty::Seq(seq) => into.append(seq),
+                $ty::Map(map) => {
+                    for (key, value) in map.drain(..) {
+                        into.push(key);
+                        into.push(value);
+                    }
+                }
+                _ => {}
+            }
+        }
+    };
+}
+
+impl<'de> Tree<'de> for Value {
+    tree!(Value);
+}
+
+impl<'de> Tree<'de> for ValueRef<'de> {
+    tree!(ValueRef);
+}
+
+/// Walks the tree with an explicit stack rather than recursion, so that deeply
+/// nested values can't overflow the call stack.
+pub struct ValueState<'a, T> {
+    stack: Vec<Frame<'a, T>>,
+}
+
+enum Frame<'a, T> {
+    /// A value that hasn't emitted anything yet.
+    Value(&'a T),
+    Seq(slice::Iter<'a, T>),
+    Map(slice::Iter<'a, (T, T)>),
+}
+
+fn get_state<'a, T>(value: &'a T) -> ValueState<'a, T> {
+    ValueState {
+        stack: vec![Frame::Value(value)],
+    }
+}
+
+fn estimate_size<'de, T: Tree<'de>>(value: &T) -> usize {
+    match value.view() {
+        View::Atom(_) => 1,
+        View::Seq(seq) => seq.len() + 2,
+        View::Map(map) => map.len() * 2 + 2,
+    }
+}
+
+fn try_fold_events<'a, 'de, T, B, R, F>(state: &mut ValueState<'a, T>, mut init: B, mut f: F) -> R
+where
+    T: Tree<'de>,
+    R: Try<Continue = B>,
+    F: FnMut(B, Event<'a>) -> R,
+{
+    let stack = &mut state.stack;
+    while let Some(frame) = stack.last_mut() {
+        match frame {
+            Frame::Value(value) => {
+                let value: &'a T = value;
+                match value.view() {
+                    View::Atom(atom) => {
+                        init = tri!(f(init, Event::Atom(atom)));
+                        stack.pop();
+                    }
+                    View::Seq(seq) => {
+                        init = tri!(f(init, Event::SeqStart(Some(seq.len()))));
+                        *frame = Frame::Seq(seq.iter());
+                    }
+                    View::Map(map) => {
+                        init = tri!(f(init, Event::MapStart(Some(map.len()))));
+                        *frame = Frame::Map(map.iter());
+                    }
+                }
+            }
+            Frame::Seq(iter) => match iter.next() {
+                Some(value) => stack.push(Frame::Value(value)),
+                None => {
+                    init = tri!(f(init, Event::SeqEnd));
+                    stack.pop();
+                }
+            },
+            Frame::Map(iter) => match iter.next() {
+                Some((key, value)) => {
+                    stack.push(Frame::Value(value));
+                    stack.push(Frame::Value(key));
+                }
+                None => {
+                    init = tri!(f(init, Event::MapEnd));
+                    stack.pop();
+                }
+            },
+        }
+    }
+    R::from_continue(init)
+}
+
+macro_rules! serializer {
+    () => {
+        type State<'a>
+            = ValueState<'a, Self>
+        where
+            Self: 'a;
+
+        fn get_state(&self) -> Self::State<'_> {
+            get_state(self)
+        }
+
+        #[inline]
+        fn estimate_size(&self) -> usize {
+            estimate_size(self)
+        }
+
+        fn try_fold_events<'a, B, R, F>(&'a self, state: &mut Self::State<'a>, init: B, f: F) -> R
+        where
+            R: Try<Continue = B>,
+            F: FnMut(B, Event<'a>) -> R,
+        {
+            try_fold_events(state, init, f)
+        }
+    };
+}
+
+impl Serializer for Value {
+    serializer!();
+}
+
+impl Serializer for ValueRef<'_> {
+    serializer!();
+}
+
+/// Builds the tree with an explicit stack of the containers that are still open.
+pub struct ValueBuilder<T> {
+    stack: Vec<Partial<T>>,
+}
+
+enum Partial<T> {
+    Seq(Vec<T>),
+    Map(Vec<(T, T)>, Option<T>),
+}
+
+fn push_event<'de, T: Tree<'de>>(
+    state: &mut ValueBuilder<T>,
+    event: Event<'de>,
+) -> Result<Progress<T>, Error> {
+    let stack = &mut state.stack;
+    let value = match event {
+        Event::Atom(atom) => T::atom(atom),
+        Event::SeqStart(len) => {
+            stack.push(Partial::Seq(Vec::with_capacity(len.unwrap_or(0).min(4096))));
+            return Ok(Progress::NeedMore);
+        }
+        Event::MapStart(len) => {
+            stack.push(Partial::Map(
+                Vec::with_capacity(len.unwrap_or(0).min(4096)),
+                None,
+            ));
+            return Ok(Progress::NeedMore);
+        }
+        Event::SeqEnd => match stack.pop() {
+            Some(Partial::Seq(seq)) => T::seq(seq),
+            _ => return Err(Error::unexpected("value", &event)),
+        },
+        Event::MapEnd => match stack.pop() {
+            Some(Partial::Map(map, None)) => T::map(map),
+            _ => return Err(Error::unexpected("value", &event)),
+        },
+    };
+
+    match stack.last_mut() {
+        None => return Ok(Progress::Done(value)),
+        Some(Partial::Seq(seq)) => seq.push(value),
+        Some(Partial::Map(_, key @ None)) => *key = Some(value),
+        Some(Partial::Map(map, key @ Some(_))) => map.push((key.take().unwrap(), value)),
+    }
+    Ok(Progress::NeedMore)
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type State = ValueBuilder<Self>;
+
+    fn get_state() -> Self::State {
+        ValueBuilder { stack: Vec::new() }
+    }
+
+    fn push_event(state: &mut Self::State, event: Event<'de>) -> Result<Progress<Self>, Error> {
+        push_event(state, event)
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueRef<'de> {
+    type State = ValueBuilder<Self>;
+
+    fn get_state() -> Self::State {
+        ValueBuilder { stack: Vec::new() }
+    }
+
+    fn push_event(state: &mut Self::State, event: Event<'de>) -> Result<Progress<Self>, Error> {
+        push_event(state, event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{Value, ValueRef};
+    use crate::{
+        de::{from_events, Error, Unexpected},
+        event::{Atom, Event},
+        Serializer,
+    };
+
+    fn events(value: &Value) -> Vec<Event<'_>> {
+        let mut v = Vec::new();
+        value.for_each_event(&mut value.get_state(), |event| v.push(event));
+        v
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = Value::Map(vec![
+            (Value::Str("a".to_owned()), Value::U64(1)),
+            (
+                Value::Seq(vec![Value::Bool(true)]),
+                Value::Seq(vec![
+                    Value::Null,
+                    Value::Bytes(vec![1, 2]),
+                    Value::Char('x'),
+                    Value::I64(-1),
+                    Value::F64(0.5),
+                    Value::Map(vec![]),
+                ]),
+            ),
+        ]);
+
+        let events = events(&value);
+        assert_eq!(
+            events,
+            [
+                Event::MapStart(Some(2)),
+                Event::Atom(Atom::Str(Cow::Borrowed("a"))),
+                Event::Atom(Atom::U64(1)),
+                Event::SeqStart(Some(1)),
+                Event::Atom(Atom::Bool(true)),
+                Event::SeqEnd,
+                Event::SeqStart(Some(6)),
+                Event::Atom(Atom::Null),
+                Event::Atom(Atom::Bytes(Cow::Borrowed(&[1, 2]))),
+                Event::Atom(Atom::Char('x')),
+                Event::Atom(Atom::I64(-1)),
+                Event::Atom(Atom::F64(0.5)),
+                Event::MapStart(Some(0)),
+                Event::MapEnd,
+                Event::SeqEnd,
+                Event::MapEnd,
+            ]
+        );
+        assert_eq!(from_events::<Value, _>(events), Ok(value));
+    }
+
+    #[test]
+    fn resume() {
+        let value = Value::Seq(vec![Value::Map(vec![(Value::U64(1), Value::U64(2))])]);
+        let expected = events(&value);
+
+        // only accept one event per call
+        let mut state = value.get_state();
+        let mut v = Vec::new();
+        loop {
+            let mut n = 0;
+            let res = value.try_for_each_event(&mut state, |event| {
+                if n < 1 {
+                    n += 1;
+                    v.push(event);
+                    Ok(())
+                } else {
+                    Err(())
+                }
+            });
+            if res.is_ok() {
+                break;
+            }
+        }
+
+        assert_eq!(v, expected);
+    }
+
+    #[test]
+    fn deep() {
+        const DEPTH: usize = 100_000;
+
+        let events = || {
+            (0..DEPTH)
+                .map(|_| Event::SeqStart(Some(1)))
+                .chain(std::iter::once(Event::Atom(Atom::Null)))
+                .chain((0..DEPTH).map(|_| Event::SeqEnd))
+        };
+        let value: Value = from_events(events()).unwrap();
+
+        let count = value.fold_events(&mut value.get_state(), 0, |n, _| n + 1);
+        assert_eq!(count, DEPTH * 2 + 1);
+
+        value.drop_iterative();
+        let value: ValueRef = from_events(events()).unwrap();
+        value.drop_iterative();
+    }
+
+    #[test]
+    fn unbalanced() {
+        let err = from_events::<Value, _>([Event::SeqStart(None), Event::MapEnd]);
+        assert_eq!(
+            err,
+            Err(Error::Unexpected {
+                expected: "value",
+                found: Unexpected::MapEnd
+            })
+        );
+
+        let err = from_events::<Value, _>([
+            Event::MapStart(None),
+            Event::Atom(Atom::Null),
+            Event::MapEnd,
+        ]);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn borrowed() {
+        let input = String::from("hello");
+        let bytes = vec![1u8, 2, 3];
+        let events = [
+            Event::MapStart(Some(2)),
+            Event::Atom(Atom::Str(Cow::Borrowed(&input))),
+            Event::Atom(Atom::Bytes(Cow::Borrowed(&bytes))),
+            Event::Atom(Atom::Str(Cow::Owned("owned".to_owned()))),
+            Event::SeqStart(Some(1)),
+            Event::Atom(Atom::Str(Cow::Borrowed(&input[1..]))),
+            Event::SeqEnd,
+            Event::MapEnd,
+        ];
+        let value: ValueRef = from_events(events.clone()).unwrap();
+
+        let ValueRef::Map(map) = &value else {
+            panic!("expected a map")
+        };
+        assert!(
+            matches!(&map[0].0, ValueRef::Str(Cow::Borrowed(s)) if s.as_ptr() == input.as_ptr())
+        );
+        assert!(
+            matches!(&map[0].1, ValueRef::Bytes(Cow::Borrowed(b)) if b.as_ptr() == bytes.as_ptr())
+        );
+        assert!(matches!(&map[1].0, ValueRef::Str(Cow::Owned(_))));
+
+        let mut v = Vec::new();
+        value.for_each_event(&mut value.get_state(), |event| v.push(event));
+        assert_eq!(v, events);
+
+        assert_eq!(
+            value.to_value(),
+            Value::Map(vec![
+                (Value::Str("hello".to_owned()), Value::Bytes(vec![1, 2, 3])),
+                (
+                    Value::Str("owned".to_owned()),
+                    Value::Seq(vec![Value::Str("ello".to_owned())])
+                ),
+            ])
+        );
+    }
+}
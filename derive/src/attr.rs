@@ -0,0 +1,249 @@
+use syn::{ext::IdentExt, meta::ParseNestedMeta, Attribute, ExprPath, LitStr};
+
+use crate::case::RenameRule;
+
+/// How an enum variant is represented in the event stream.
+pub enum Tagging {
+    /// `{"Variant": content}`, or `"Variant"` for unit variants.
+    External,
+    /// `{"tag": "Variant", ...fields}`
+    Internal { tag: String },
+    /// `{"tag": "Variant", "content": content}`
+    Adjacent { tag: String, content: String },
+    /// Just the content, with no indication of which variant it was.
+    Untagged,
+}
+
+/// The `#[sud(...)]` attributes on the type itself.
+pub struct Container {
+    pub tagging: Tagging,
+    pub rename_all: Option<RenameRule>,
+    pub deny_unknown_fields: bool,
+    /// Missing fields are taken from the type's `Default` impl.
+    pub default: bool,
+}
+
+impl Container {
+    pub fn from_ast(input: &syn::DeriveInput) -> syn::Result<Self> {
+        let mut tag = None;
+        let mut content = None;
+        let mut untagged = None;
+        let mut rename_all = None;
+        let mut deny_unknown_fields = false;
+        let mut default = false;
+
+        for attr in sud_attrs(&input.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    tag = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("content") {
+                    content = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("untagged") {
+                    untagged = Some(meta.path.clone());
+                } else if meta.path.is_ident("rename_all") {
+                    rename_all = Some(RenameRule::from_lit(&meta.value()?.parse()?)?);
+                } else if meta.path.is_ident("deny_unknown_fields") {
+                    deny_unknown_fields = true;
+                } else if meta.path.is_ident("default") {
+                    default = true;
+                } else {
+                    return Err(meta.error("unknown sud container attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        let tagging = match (tag, content, untagged) {
+            (None, None, None) => Tagging::External,
+            (Some(tag), None, None) => Tagging::Internal { tag },
+            (Some(tag), Some(content), None) => Tagging::Adjacent { tag, content },
+            (None, None, Some(_)) => Tagging::Untagged,
+            (None, Some(_), _) => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "`content` requires `tag` to also be set",
+                ))
+            }
+            (_, _, Some(untagged)) => {
+                return Err(syn::Error::new_spanned(
+                    untagged,
+                    "`untagged` cannot be combined with `tag` or `content`",
+                ))
+            }
+        };
+
+        let is_enum = matches!(input.data, syn::Data::Enum(_));
+        if !is_enum && !matches!(tagging, Tagging::External) {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "tagging attributes are only supported on enums",
+            ));
+        }
+        if is_enum && default {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`default` is only supported on structs",
+            ));
+        }
+
+        Ok(Container {
+            tagging,
+            rename_all,
+            deny_unknown_fields,
+            default,
+        })
+    }
+}
+
+pub enum Default {
+    /// `#[sud(default)]`
+    Trait,
+    /// `#[sud(default = "path")]`
+    Path(ExprPath),
+}
+
+/// The `#[sud(...)]` attributes on a field.
+pub struct Field {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub default: Option<Default>,
+    pub skip: bool,
+    pub flatten: bool,
+}
+
+impl Field {
+    pub fn from_ast(field: &syn::Field, rename_all: Option<RenameRule>) -> syn::Result<Self> {
+        let ident = field.ident.as_ref().map(|ident| ident.unraw().to_string());
+        let mut name = match (&ident, rename_all) {
+            (Some(ident), Some(rule)) => rule.apply_to_field(ident),
+            (Some(ident), None) => ident.clone(),
+            (None, _) => String::new(),
+        };
+        let mut aliases = Vec::new();
+        let mut default = None;
+        let mut skip = false;
+        let mut flatten = false;
+
+        for attr in sud_attrs(&field.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.path.is_ident("alias") {
+                    aliases.push(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("default") {
+                    default = Some(parse_default(&meta)?);
+                } else if meta.path.is_ident("skip") {
+                    skip = true;
+                } else if meta.path.is_ident("flatten") {
+                    flatten = true;
+                } else {
+                    return Err(meta.error("unknown sud field attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        if flatten && ident.is_none() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "`flatten` is only supported on named fields",
+            ));
+        }
+        if flatten && skip {
+            return Err(syn::Error::new_spanned(
+                field,
+                "`flatten` cannot be combined with `skip`",
+            ));
+        }
+
+        Ok(Field {
+            name,
+            aliases,
+            default,
+            skip,
+            flatten,
+        })
+    }
+}
+
+/// The `#[sud(...)]` attributes on an enum variant.
+pub struct Variant {
+    pub name: String,
+    pub aliases: Vec<String>,
+    /// Applies to the fields of a struct variant.
+    pub rename_all: Option<RenameRule>,
+}
+
+impl Variant {
+    pub fn from_ast(variant: &syn::Variant, rename_all: Option<RenameRule>) -> syn::Result<Self> {
+        let ident = variant.ident.unraw().to_string();
+        let mut name = match rename_all {
+            Some(rule) => rule.apply_to_variant(&ident),
+            None => ident,
+        };
+        let mut aliases = Vec::new();
+        let mut fields_rename_all = None;
+
+        for attr in sud_attrs(&variant.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.path.is_ident("alias") {
+                    aliases.push(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("rename_all") {
+                    fields_rename_all = Some(RenameRule::from_lit(&meta.value()?.parse()?)?);
+                } else {
+                    return Err(meta.error("unknown sud variant attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(Variant {
+            name,
+            aliases,
+            rename_all: fields_rename_all,
+        })
+    }
+}
+
+fn parse_default(meta: &ParseNestedMeta) -> syn::Result<Default> {
+    if meta.input.peek(syn::Token![=]) {
+        let path = meta.value()?.parse::<LitStr>()?;
+        Ok(Default::Path(path.parse()?))
+    } else {
+        Ok(Default::Trait)
+    }
+}
+
+fn sud_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("sud"))
+}
+
+/// A field of a struct or variant, along with its attributes.
+pub struct FieldInfo<'a> {
+    pub member: syn::Member,
+    pub ty: &'a syn::Type,
+    pub attrs: Field,
+}
+
+pub fn parse_fields(
+    fields: &syn::Fields,
+    rename_all: Option<RenameRule>,
+) -> syn::Result<Vec<FieldInfo<'_>>> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let member = match &field.ident {
+                Some(ident) => syn::Member::Named(ident.clone()),
+                None => syn::Member::from(i),
+            };
+            Ok(FieldInfo {
+                member,
+                ty: &field.ty,
+                attrs: Field::from_ast(field, rename_all)?,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,728 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{parse_quote, Data, DeriveInput, Fields, Generics, Lifetime, Type, WherePredicate};
+
+use crate::{
+    attr::{self, parse_fields, Container, FieldInfo, Tagging},
+    ser::newtype,
+};
+
+/// The generics shared by the impls and by every builder of a type.
+struct Ctx<'a> {
+    vis: &'a syn::Visibility,
+    /// The type's generics, plus `'__de` and the `Deserializer` bounds.
+    generics: Generics,
+    /// `Name<...>` as written inside the impls.
+    ty: TokenStream,
+}
+
+impl Ctx<'_> {
+    fn phantom(&self) -> TokenStream {
+        let ty = &self.ty;
+        quote!(::core::marker::PhantomData<fn() -> (&'__de (), #ty)>)
+    }
+}
+
+pub fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let container = Container::from_ast(input)?;
+    let name = &input.ident;
+
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param
+            .bounds
+            .push(parse_quote!(::sud_core::Deserializer<'__de>));
+    }
+    let mut predicates = Vec::new();
+    match &input.data {
+        Data::Struct(data) => bound_fields(&input.generics, &data.fields, &mut predicates),
+        Data::Enum(data) => {
+            for variant in &data.variants {
+                bound_fields(&input.generics, &variant.fields, &mut predicates);
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`Deserializer` cannot be derived for unions",
+            ))
+        }
+    }
+    if container.default {
+        let (_, ty_generics, _) = input.generics.split_for_impl();
+        predicates.push(parse_quote!(#name #ty_generics: ::core::default::Default));
+    }
+    generics.make_where_clause().predicates.extend(predicates);
+    generics.params.insert(0, parse_quote!('__de));
+
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let ctx = Ctx {
+        vis: &input.vis,
+        generics,
+        ty: quote!(#name #ty_generics),
+    };
+    let (impl_generics, state_ty_generics, where_clause) = ctx.generics.split_for_impl();
+    let ty = &ctx.ty;
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let state_name = format_ident!("__{}DeserializerState", name);
+            let fields = parse_fields(&data.fields, container.rename_all)?;
+            let builder = builder(
+                &ctx,
+                &state_name,
+                quote!(#name),
+                &data.fields,
+                &fields,
+                &container,
+            )?;
+
+            quote! {
+                #builder
+
+                impl #impl_generics ::sud_core::Deserializer<'__de> for #ty #where_clause {
+                    type State = #state_name #state_ty_generics;
+
+                    fn get_state() -> Self::State {
+                        #state_name::new()
+                    }
+
+                    #[inline]
+                    fn push_event(
+                        state: &mut Self::State,
+                        event: ::sud_core::Event<'__de>,
+                    ) -> ::core::result::Result<::sud_core::Progress<Self>, ::sud_core::de::Error> {
+                        state.push_event(event)
+                    }
+                }
+            }
+        }
+        Data::Enum(data) => {
+            let content_name = format_ident!("__{}DeserializerContent", name);
+            let mut builders = Vec::new();
+            let mut contents = Vec::new();
+            let mut names = Vec::new();
+            let mut matches = Vec::new();
+            let mut units = Vec::new();
+            let mut inits = Vec::new();
+            let mut pushes = Vec::new();
+
+            for (i, variant) in data.variants.iter().enumerate() {
+                let attrs = attr::Variant::from_ast(variant, container.rename_all)?;
+                let ident = &variant.ident;
+                let state_name = format_ident!("__{}{}DeserializerState", name, ident);
+                let fields = parse_fields(&variant.fields, attrs.rename_all)?;
+                builders.push(builder(
+                    &ctx,
+                    &state_name,
+                    quote!(#name::#ident),
+                    &variant.fields,
+                    &fields,
+                    &Container {
+                        tagging: Tagging::External,
+                        rename_all: attrs.rename_all,
+                        deny_unknown_fields: container.deny_unknown_fields,
+                        default: false,
+                    },
+                )?);
+
+                let content = format_ident!("V{}", i);
+                contents.push(quote!(#content(#state_name #state_ty_generics)));
+                let variant_name = &attrs.name;
+                let aliases = &attrs.aliases;
+                names.push(variant_name.clone());
+                matches.push(
+                    quote!(#variant_name #(| #aliases)* => ::core::option::Option::Some(#i),),
+                );
+                if matches!(variant.fields, Fields::Unit) {
+                    units.push(quote!(#i => ::core::option::Option::Some(#name::#ident),));
+                }
+                inits.push(quote!(#i => #content_name::#content(#state_name::new()),));
+                pushes.push(quote!(#content_name::#content(state) => state.push_event(event),));
+            }
+
+            let (machine, init) = match &container.tagging {
+                Tagging::External => (
+                    quote!(ExternallyTagged),
+                    quote!(::sud_core::__private::ExternallyTagged::new()),
+                ),
+                Tagging::Internal { tag } => (
+                    quote!(InternallyTagged),
+                    quote!(::sud_core::__private::InternallyTagged::new(#tag)),
+                ),
+                Tagging::Adjacent { tag, content } => (
+                    quote!(AdjacentlyTagged),
+                    quote!(::sud_core::__private::AdjacentlyTagged::new(#tag, #content)),
+                ),
+                Tagging::Untagged => (
+                    quote!(Untagged),
+                    quote!(::sud_core::__private::Untagged::new()),
+                ),
+            };
+            let vis = ctx.vis;
+            let phantom = ctx.phantom();
+
+            quote! {
+                #(#builders)*
+
+                #[doc(hidden)]
+                #vis enum #content_name #impl_generics #where_clause {
+                    #(#contents,)*
+                    __Phantom(::core::convert::Infallible, #phantom),
+                }
+
+                impl #impl_generics ::sud_core::__private::Variants<'__de> for #ty #where_clause {
+                    type Content = #content_name #state_ty_generics;
+
+                    const VARIANTS: &'static [&'static str] = &[#(#names),*];
+
+                    fn variant(name: &str) -> ::core::option::Option<usize> {
+                        match name {
+                            #(#matches)*
+                            _ => ::core::option::Option::None,
+                        }
+                    }
+
+                    fn unit(index: usize) -> ::core::option::Option<Self> {
+                        match index {
+                            #(#units)*
+                            _ => ::core::option::Option::None,
+                        }
+                    }
+
+                    fn content(index: usize) -> Self::Content {
+                        match index {
+                            #(#inits)*
+                            _ => unreachable!(),
+                        }
+                    }
+
+                    fn push_content(
+                        content: &mut Self::Content,
+                        event: ::sud_core::Event<'__de>,
+                    ) -> ::core::result::Result<::sud_core::Progress<Self>, ::sud_core::de::Error> {
+                        match content {
+                            #(#pushes)*
+                            #content_name::__Phantom(never, _) => match *never {},
+                        }
+                    }
+                }
+
+                impl #impl_generics ::sud_core::Deserializer<'__de> for #ty #where_clause {
+                    type State = ::sud_core::__private::#machine<'__de, Self>;
+
+                    fn get_state() -> Self::State {
+                        #init
+                    }
+
+                    #[inline]
+                    fn push_event(
+                        state: &mut Self::State,
+                        event: ::sud_core::Event<'__de>,
+                    ) -> ::core::result::Result<::sud_core::Progress<Self>, ::sud_core::de::Error> {
+                        state.push_event(event)
+                    }
+                }
+            }
+        }
+        Data::Union(_) => unreachable!(),
+    };
+
+    Ok(quote! {
+        const _: () = {
+            #body
+        };
+    })
+}
+
+/// Field types that borrow from the input only implement `Deserializer<'__de>`
+/// for some lifetimes, so they need an explicit bound.
+fn bound_fields(generics: &Generics, fields: &Fields, predicates: &mut Vec<WherePredicate>) {
+    if generics.lifetimes().next().is_none() {
+        return;
+    }
+    for field in fields {
+        if mentions_lifetime(&field.ty) {
+            let ty = &field.ty;
+            predicates.push(parse_quote!(#ty: ::sud_core::Deserializer<'__de>));
+        }
+    }
+}
+
+fn mentions_lifetime(ty: &Type) -> bool {
+    struct Visitor(bool);
+    impl<'ast> syn::visit::Visit<'ast> for Visitor {
+        fn visit_lifetime(&mut self, lifetime: &'ast Lifetime) {
+            if lifetime.ident != "static" {
+                self.0 = true;
+            }
+        }
+    }
+    let mut visitor = Visitor(false);
+    syn::visit::Visit::visit_type(&mut visitor, ty);
+    visitor.0
+}
+
+/// Generates the state machine that builds one struct or variant, as a struct
+/// named `state_name` with inherent `new` and `push_event` methods.
+///
+/// Named fields are read from a map, tuple fields from a sequence. A newtype is
+/// read as the value it wraps, and a unit from `null` or an empty map.
+fn builder(
+    ctx: &Ctx,
+    state_name: &syn::Ident,
+    path: TokenStream,
+    ast: &Fields,
+    fields: &[FieldInfo],
+    container: &Container,
+) -> syn::Result<TokenStream> {
+    let (impl_generics, ty_generics, where_clause) = ctx.generics.split_for_impl();
+    let vis = ctx.vis;
+    let ty = &ctx.ty;
+    let phantom = ctx.phantom();
+
+    let (state, init, push) = match ast {
+        Fields::Named(_) => map_builder(ctx, &path, fields, container)?,
+        Fields::Unnamed(_) if newtype(fields).is_some() => {
+            let field = newtype(fields).unwrap();
+            let field_ty = field.ty;
+            let slots = fields
+                .iter()
+                .map(|f| {
+                    if f.member == field.member {
+                        Slot::Value(quote!(value))
+                    } else {
+                        slot(f, container, None)
+                    }
+                })
+                .collect();
+            let value = construct(ctx, &path, fields, slots);
+            (
+                quote!(__state: <#field_ty as ::sud_core::Deserializer<'__de>>::State,),
+                quote!(__state: <#field_ty as ::sud_core::Deserializer<'__de>>::get_state(),),
+                quote! {
+                    match <#field_ty as ::sud_core::Deserializer<'__de>>::push_event(&mut self.__state, event)? {
+                        ::sud_core::Progress::NeedMore => ::core::result::Result::Ok(::sud_core::Progress::NeedMore),
+                        ::sud_core::Progress::Done(value) => {
+                            ::core::result::Result::Ok(::sud_core::Progress::Done(#value))
+                        }
+                    }
+                },
+            )
+        }
+        Fields::Unnamed(_) => seq_builder(ctx, &path, fields, container),
+        Fields::Unit => (
+            quote!(__started: bool,),
+            quote!(__started: false,),
+            quote! {
+                match (self.__started, event) {
+                    (false, ::sud_core::Event::Atom(::sud_core::Atom::Null)) | (true, ::sud_core::Event::MapEnd) => {
+                        ::core::result::Result::Ok(::sud_core::Progress::Done(#path {}))
+                    }
+                    (false, ::sud_core::Event::MapStart(_)) => {
+                        self.__started = true;
+                        ::core::result::Result::Ok(::sud_core::Progress::NeedMore)
+                    }
+                    (false, event) => ::core::result::Result::Err(::sud_core::de::Error::unexpected("null", &event)),
+                    (true, event) => ::core::result::Result::Err(::sud_core::de::Error::unexpected("end of map", &event)),
+                }
+            },
+        ),
+    };
+
+    Ok(quote! {
+        #[doc(hidden)]
+        #vis struct #state_name #impl_generics #where_clause {
+            #state
+            __phantom: #phantom,
+        }
+
+        impl #impl_generics #state_name #ty_generics #where_clause {
+            fn new() -> Self {
+                #state_name {
+                    #init
+                    __phantom: ::core::marker::PhantomData,
+                }
+            }
+
+            fn push_event(
+                &mut self,
+                event: ::sud_core::Event<'__de>,
+            ) -> ::core::result::Result<::sud_core::Progress<#ty>, ::sud_core::de::Error> {
+                #push
+            }
+        }
+    })
+}
+
+/// How a field of the finished value is filled in.
+enum Slot {
+    /// An expression for the field's value.
+    Value(TokenStream),
+    /// An `Option` of the field's value, falling back to the container's
+    /// `Default` value when `None`.
+    Defaulted(TokenStream),
+}
+
+/// The slot of a field whose value, if it was read, is the `Option` `value`.
+fn slot(field: &FieldInfo, container: &Container, value: Option<TokenStream>) -> Slot {
+    let none = quote!(::core::option::Option::None);
+    if field.attrs.default.is_none() && container.default {
+        return Slot::Defaulted(value.unwrap_or(none));
+    }
+    let missing = missing(field);
+    Slot::Value(match value {
+        Some(value) => quote! {
+            match #value {
+                ::core::option::Option::Some(value) => value,
+                #none => #missing,
+            }
+        },
+        None => missing,
+    })
+}
+
+/// The value of a field that was skipped or is missing from the input.
+fn missing(field: &FieldInfo) -> TokenStream {
+    match &field.attrs.default {
+        Some(attr::Default::Trait) => quote!(::core::default::Default::default()),
+        Some(attr::Default::Path(path)) => quote!(#path()),
+        None if field.attrs.skip => quote!(::core::default::Default::default()),
+        None => {
+            let name = &field.attrs.name;
+            quote!(return ::core::result::Result::Err(::sud_core::de::Error::MissingField(#name)))
+        }
+    }
+}
+
+/// The expression that builds the finished value.
+///
+/// The container's `Default` value is only created when a field falls back
+/// on it. The fields that were read are then assigned into it, rather than
+/// the defaulted fields being moved out, so that containers implementing
+/// `Drop` are supported.
+fn construct(ctx: &Ctx, path: &TokenStream, fields: &[FieldInfo], slots: Vec<Slot>) -> TokenStream {
+    let ty = &ctx.ty;
+    let members: Vec<_> = fields.iter().map(|field| &field.member).collect();
+    let mut values = Vec::new();
+    let mut options = Vec::new();
+    let mut bindings = Vec::new();
+    let mut assign = Vec::new();
+    for (member, slot) in members.iter().zip(slots) {
+        match slot {
+            Slot::Value(value) => {
+                assign.push(quote!(__default.#member = #value;));
+                values.push(value);
+            }
+            Slot::Defaulted(option) => {
+                let binding = format_ident!("__o{}", options.len());
+                assign.push(quote! {
+                    if let ::core::option::Option::Some(value) = #binding {
+                        __default.#member = value;
+                    }
+                });
+                values.push(quote!(#binding));
+                options.push(option);
+                bindings.push(binding);
+            }
+        }
+    }
+
+    if options.is_empty() {
+        return quote!(#path { #(#members: #values,)* });
+    }
+    quote! {
+        match (#(#options,)*) {
+            (#(::core::option::Option::Some(#bindings),)*) => #path { #(#members: #values,)* },
+            (#(#bindings,)*) => {
+                let mut __default: #ty = ::core::default::Default::default();
+                #(#assign)*
+                __default
+            }
+        }
+    }
+}
+
+fn map_builder(
+    ctx: &Ctx,
+    path: &TokenStream,
+    fields: &[FieldInfo],
+    container: &Container,
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
+    let mut flatten = None;
+    for field in fields.iter().filter(|field| field.attrs.flatten) {
+        if flatten.is_some() {
+            return Err(syn::Error::new_spanned(
+                &field.member,
+                "`Deserializer` supports at most one `flatten` field",
+            ));
+        }
+        flatten = Some(field);
+    }
+
+    let read: Vec<_> = fields
+        .iter()
+        .filter(|field| !field.attrs.skip && !field.attrs.flatten)
+        .collect();
+    let ignore = read.len();
+    let flat = read.len() + 1;
+
+    let mut state = quote! {
+        __started: bool,
+        __current: ::core::option::Option<usize>,
+        __ignore: usize,
+    };
+    let mut init = quote! {
+        __started: false,
+        __current: ::core::option::Option::None,
+        __ignore: 0,
+    };
+    let mut keys = Vec::new();
+    let mut values = Vec::new();
+    for (i, field) in read.iter().enumerate() {
+        let field_ty = field.ty;
+        let value = format_ident!("__v{}", i);
+        let child = format_ident!("__s{}", i);
+        state.extend(quote! {
+            #value: ::core::option::Option<#field_ty>,
+            #child: ::core::option::Option<<#field_ty as ::sud_core::Deserializer<'__de>>::State>,
+        });
+        init.extend(quote! {
+            #value: ::core::option::Option::None,
+            #child: ::core::option::Option::None,
+        });
+
+        let name = &field.attrs.name;
+        let aliases = &field.attrs.aliases;
+        keys.push(quote! {
+            #name #(| #aliases)* => {
+                if self.#value.is_some() {
+                    return ::core::result::Result::Err(::sud_core::de::Error::DuplicateField(#name));
+                }
+                #i
+            }
+        });
+        values.push(quote! {
+            ::core::option::Option::Some(#i) => {
+                if let ::core::option::Option::Some(value) =
+                    ::sud_core::__private::push_child::<#field_ty>(&mut self.#child, event)?
+                {
+                    self.#value = ::core::option::Option::Some(value);
+                    self.__current = ::core::option::Option::None;
+                }
+            }
+        });
+    }
+
+    let mut start = TokenStream::new();
+    let mut finish = TokenStream::new();
+    let unknown = if let Some(field) = flatten {
+        let field_ty = field.ty;
+        state.extend(quote! {
+            __flatten: <#field_ty as ::sud_core::Deserializer<'__de>>::State,
+            __flatten_depth: usize,
+        });
+        init.extend(quote! {
+            __flatten: <#field_ty as ::sud_core::Deserializer<'__de>>::get_state(),
+            __flatten_depth: 0,
+        });
+        // the flattened value sees a map of all the entries we don't recognise
+        start = quote! {
+            <#field_ty as ::sud_core::Deserializer<'__de>>::push_event(
+                &mut self.__flatten,
+                ::sud_core::Event::MapStart(::core::option::Option::None),
+            )?;
+        };
+        finish = quote! {
+            let __flatten = match <#field_ty as ::sud_core::Deserializer<'__de>>::push_event(
+                &mut self.__flatten,
+                ::sud_core::Event::MapEnd,
+            )? {
+                ::sud_core::Progress::Done(value) => value,
+                ::sud_core::Progress::NeedMore => {
+                    return ::core::result::Result::Err(::sud_core::de::Error::UnexpectedEof)
+                }
+            };
+        };
+        values.push(quote! {
+            ::core::option::Option::Some(#flat) => {
+                let done = ::sud_core::__private::track_depth(&mut self.__flatten_depth, &event)?;
+                <#field_ty as ::sud_core::Deserializer<'__de>>::push_event(&mut self.__flatten, event)?;
+                if done {
+                    self.__current = ::core::option::Option::None;
+                }
+            }
+        });
+        quote! {{
+            <#field_ty as ::sud_core::Deserializer<'__de>>::push_event(
+                &mut self.__flatten,
+                ::sud_core::Event::Atom(::sud_core::Atom::Str(key)),
+            )?;
+            #flat
+        }}
+    } else if container.deny_unknown_fields {
+        quote!(
+            return ::core::result::Result::Err(::sud_core::de::Error::UnknownField(
+                key.into_owned()
+            ))
+        )
+    } else {
+        quote!(#ignore)
+    };
+
+    let mut read_index = 0usize;
+    let slots = fields
+        .iter()
+        .map(|field| {
+            if field.attrs.flatten {
+                Slot::Value(quote!(__flatten))
+            } else if field.attrs.skip {
+                slot(field, container, None)
+            } else {
+                let value = format_ident!("__v{}", read_index);
+                read_index += 1;
+                slot(field, container, Some(quote!(self.#value.take())))
+            }
+        })
+        .collect();
+    let value = construct(ctx, path, fields, slots);
+
+    let push = quote! {
+        if !self.__started {
+            return match event {
+                ::sud_core::Event::MapStart(_) => {
+                    self.__started = true;
+                    #start
+                    ::core::result::Result::Ok(::sud_core::Progress::NeedMore)
+                }
+                event => ::core::result::Result::Err(::sud_core::de::Error::unexpected("map", &event)),
+            };
+        }
+
+        match self.__current {
+            ::core::option::Option::None => match event {
+                ::sud_core::Event::Atom(::sud_core::Atom::Str(key)) => {
+                    self.__current = ::core::option::Option::Some(match &*key {
+                        #(#keys)*
+                        _ => #unknown,
+                    });
+                }
+                ::sud_core::Event::MapEnd => {
+                    #finish
+                    return ::core::result::Result::Ok(::sud_core::Progress::Done(#value));
+                }
+                event => {
+                    return ::core::result::Result::Err(::sud_core::de::Error::unexpected("field name", &event))
+                }
+            },
+            #(#values)*
+            ::core::option::Option::Some(_) => {
+                if ::sud_core::__private::track_depth(&mut self.__ignore, &event)? {
+                    self.__current = ::core::option::Option::None;
+                }
+            }
+        }
+        ::core::result::Result::Ok(::sud_core::Progress::NeedMore)
+    };
+
+    Ok((state, init, push))
+}
+
+fn seq_builder(
+    ctx: &Ctx,
+    path: &TokenStream,
+    fields: &[FieldInfo],
+    container: &Container,
+) -> (TokenStream, TokenStream, TokenStream) {
+    let read: Vec<_> = fields.iter().filter(|field| !field.attrs.skip).collect();
+    let len = read.len();
+
+    let mut state = quote! {
+        __started: bool,
+        __index: usize,
+    };
+    let mut init = quote! {
+        __started: false,
+        __index: 0,
+    };
+    let mut idle = Vec::new();
+    let mut values = Vec::new();
+    for (i, field) in read.iter().enumerate() {
+        let field_ty = field.ty;
+        let value = format_ident!("__v{}", i);
+        let child = format_ident!("__s{}", i);
+        state.extend(quote! {
+            #value: ::core::option::Option<#field_ty>,
+            #child: ::core::option::Option<<#field_ty as ::sud_core::Deserializer<'__de>>::State>,
+        });
+        init.extend(quote! {
+            #value: ::core::option::Option::None,
+            #child: ::core::option::Option::None,
+        });
+        idle.push(quote!(#i => self.#child.is_none(),));
+        values.push(quote! {
+            #i => {
+                if let ::core::option::Option::Some(value) =
+                    ::sud_core::__private::push_child::<#field_ty>(&mut self.#child, event)?
+                {
+                    self.#value = ::core::option::Option::Some(value);
+                    self.__index += 1;
+                }
+            }
+        });
+    }
+
+    let mut read_index = 0usize;
+    let slots = fields
+        .iter()
+        .map(|field| {
+            if field.attrs.skip {
+                slot(field, container, None)
+            } else {
+                let value = format_ident!("__v{}", read_index);
+                read_index += 1;
+                Slot::Value(quote!(self.#value.take().unwrap()))
+            }
+        })
+        .collect();
+    let value = construct(ctx, path, fields, slots);
+
+    let push = quote! {
+        if !self.__started {
+            return match event {
+                ::sud_core::Event::SeqStart(_) => {
+                    self.__started = true;
+                    ::core::result::Result::Ok(::sud_core::Progress::NeedMore)
+                }
+                event => ::core::result::Result::Err(::sud_core::de::Error::unexpected("sequence", &event)),
+            };
+        }
+
+        let idle = match self.__index {
+            #(#idle)*
+            _ => true,
+        };
+        if idle && event == ::sud_core::Event::SeqEnd {
+            if self.__index < #len {
+                return ::core::result::Result::Err(::sud_core::de::Error::InvalidLength {
+                    expected: #len,
+                    found: self.__index,
+                });
+            }
+            return ::core::result::Result::Ok(::sud_core::Progress::Done(#value));
+        }
+
+        match self.__index {
+            #(#values)*
+            _ => {
+                return ::core::result::Result::Err(::sud_core::de::Error::InvalidLength {
+                    expected: #len,
+                    found: #len + 1,
+                })
+            }
+        }
+        ::core::result::Result::Ok(::sud_core::Progress::NeedMore)
+    };
+
+    (state, init, push)
+}
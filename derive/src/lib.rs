@@ -0,0 +1,38 @@
+//! Derive macros for `sud-core`.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod attr;
+mod case;
+mod de;
+mod ser;
+
+/// Derives `sud_core::Serializer`.
+///
+/// Fields marked `#[sud(flatten)]` and the newtype variants of internally
+/// tagged enums must serialize as maps, so their types must implement
+/// `sud_core::MapSerializer`:
+///
+/// ```compile_fail
+/// #[derive(sud_derive::Serializer)]
+/// #[sud(tag = "type")]
+/// enum E {
+///     A(u8),
+/// }
+/// ```
+#[proc_macro_derive(Serializer, attributes(sud))]
+pub fn derive_serializer(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    ser::expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(Deserializer, attributes(sud))]
+pub fn derive_deserializer(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    de::expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,445 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::spanned::Spanned;
+use syn::{parse_quote, Data, DeriveInput, Fields, Member, Type};
+
+use crate::attr::{self, parse_fields, Container, FieldInfo, Tagging};
+
+/// A single piece of the event stream of a type.
+enum Step {
+    /// A constant event, eg a `MapStart` or a field name.
+    Event(TokenStream),
+    /// A nested value that emits its own events.
+    Value {
+        ty: TokenStream,
+        access: TokenStream,
+    },
+    /// A nested map whose entries are written directly into the enclosing map.
+    Flatten {
+        ty: TokenStream,
+        access: TokenStream,
+    },
+    /// A nested map that gets an extra `tag: variant` entry spliced in.
+    Tagged {
+        ty: TokenStream,
+        access: TokenStream,
+        tag: String,
+        variant: String,
+    },
+}
+
+/// The steps for one shape of the type, and the pattern that selects it.
+struct Path {
+    pat: TokenStream,
+    steps: Vec<Step>,
+}
+
+pub fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let container = Container::from_ast(input)?;
+    let is_enum = matches!(input.data, Data::Enum(_));
+    let paths = match &input.data {
+        Data::Struct(data) => vec![Path {
+            pat: TokenStream::new(),
+            steps: struct_steps(
+                &data.fields,
+                &parse_fields(&data.fields, container.rename_all)?,
+                |member| quote!(&self.#member),
+            ),
+        }],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let ident = &variant.ident;
+                let access = |member: &Member| {
+                    quote! {
+                        match self {
+                            Self::#ident { #member: value, .. } => value,
+                            #[allow(unreachable_patterns)]
+                            _ => unreachable!(),
+                        }
+                    }
+                };
+                let attrs = attr::Variant::from_ast(variant, container.rename_all)?;
+                Ok(Path {
+                    pat: quote!(Self::#ident { .. }),
+                    steps: variant_steps(&container.tagging, variant, &attrs, access)?,
+                })
+            })
+            .collect::<syn::Result<_>>()?,
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`Serializer` cannot be derived for unions",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let state_name = format_ident!("__{}SerializerState", name);
+
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::sud_core::Serializer));
+    }
+    // a type parameter that gets spliced into a map has to be a map itself.
+    // Concrete types are checked where their state is created.
+    let params: Vec<_> = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    for step in paths.iter().flat_map(|path| &path.steps) {
+        if let Step::Flatten { ty, .. } | Step::Tagged { ty, .. } = step {
+            let ty: Type = syn::parse2(ty.clone())?;
+            if params.iter().any(
+                |param| matches!(&ty, Type::Path(p) if p.qself.is_none() && p.path.is_ident(param)),
+            ) {
+                generics
+                    .make_where_clause()
+                    .predicates
+                    .push(parse_quote!(#ty: ::sud_core::MapSerializer));
+            }
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // the state borrows from the value, so everything in it must outlive `'__a`
+    let mut state_generics = generics.clone();
+    for param in state_generics.lifetimes_mut() {
+        param.bounds.push(parse_quote!('__a));
+    }
+    for param in state_generics.type_params_mut() {
+        param.bounds.push(parse_quote!('__a));
+    }
+    state_generics.params.insert(0, parse_quote!('__a));
+    let (state_impl_generics, state_ty_generics, state_where_clause) =
+        state_generics.split_for_impl();
+
+    // every step of every path gets its own state, numbered in order
+    let mut variants = Vec::new();
+    let mut arms = Vec::new();
+    let mut inits = Vec::new();
+    let mut sizes = Vec::new();
+    let mut offset = 0;
+    for Path { pat, steps } in &paths {
+        let init = init_state(&state_name, steps, offset, 0);
+        // a flattened value may have no entries, so it can't be counted
+        let size = steps
+            .iter()
+            .filter(|step| !matches!(step, Step::Flatten { .. }))
+            .count();
+        if is_enum {
+            inits.push(quote!(#pat => #init,));
+            sizes.push(quote!(#pat => #size,));
+        } else {
+            inits.push(init);
+            sizes.push(quote!(#size));
+        }
+
+        for (i, step) in steps.iter().enumerate() {
+            let ident = format_ident!("S{}", offset + i);
+            variants.push(match step {
+                Step::Event(_) => quote!(#ident),
+                Step::Value { ty, .. } => quote! {
+                    #ident(&'__a #ty, <#ty as ::sud_core::Serializer>::State<'__a>)
+                },
+                Step::Flatten { ty, .. } => quote! {
+                    #ident(&'__a #ty, ::sud_core::__private::Flatten<'__a, #ty>)
+                },
+                Step::Tagged { ty, .. } => quote! {
+                    #ident(&'__a #ty, ::sud_core::__private::Tagged<'__a, #ty>)
+                },
+            });
+
+            let last = i + 1 == steps.len();
+            let next = init_state(&state_name, steps, offset, i + 1);
+            let (pat, fold) = match step {
+                Step::Event(event) => (quote!(#state_name::#ident), quote!(f(init, #event))),
+                Step::Value { ty, .. } => (
+                    quote!(#state_name::#ident(value, s)),
+                    quote! {
+                        <#ty as ::sud_core::Serializer>::try_fold_events(*value, s, init, &mut f)
+                    },
+                ),
+                Step::Flatten { .. } | Step::Tagged { .. } => (
+                    quote!(#state_name::#ident(value, s)),
+                    quote!(s.try_fold_events(*value, init, &mut f)),
+                ),
+            };
+            arms.push(if last {
+                quote!(#pat => break #fold,)
+            } else {
+                quote! {
+                    #pat => {
+                        init = ::sud_core::tri!(#fold);
+                        *state = #next;
+                    }
+                }
+            });
+        }
+        offset += steps.len();
+    }
+
+    let (init, size) = if is_enum {
+        (
+            quote!(match *self { #(#inits)* }),
+            quote!(match *self { #(#sizes)* }),
+        )
+    } else {
+        (quote!(#(#inits)*), quote!(#(#sizes)*))
+    };
+
+    // the shapes that always serialize as a map, or as `null`
+    let is_map = match &input.data {
+        Data::Struct(data) => !matches!(data.fields, Fields::Unnamed(_)),
+        _ => matches!(
+            container.tagging,
+            Tagging::Internal { .. } | Tagging::Adjacent { .. }
+        ),
+    };
+    let map_impl = is_map.then(|| {
+        quote! {
+            impl #impl_generics ::sud_core::MapSerializer for #name #ty_generics #where_clause {}
+        }
+    });
+
+    Ok(quote! {
+        const _: () = {
+            #[doc(hidden)]
+            #vis enum #state_name #state_impl_generics #state_where_clause {
+                #(#variants,)*
+                __Phantom(
+                    ::core::convert::Infallible,
+                    ::core::marker::PhantomData<&'__a #name #ty_generics>,
+                ),
+            }
+
+            #map_impl
+
+            impl #impl_generics ::sud_core::Serializer for #name #ty_generics #where_clause {
+                type State<'__a> = #state_name #state_ty_generics where Self: '__a;
+
+                #[inline]
+                fn estimate_size(&self) -> usize {
+                    #size
+                }
+
+                fn get_state(&self) -> Self::State<'_> {
+                    #init
+                }
+
+                fn try_fold_events<'__a, __B, __R, __F>(
+                    &'__a self,
+                    state: &mut Self::State<'__a>,
+                    mut init: __B,
+                    mut f: __F,
+                ) -> __R
+                where
+                    __R: ::sud_core::try_polyfill::Try<Continue = __B>,
+                    __F: FnMut(__B, ::sud_core::Event<'__a>) -> __R,
+                {
+                    loop {
+                        match state {
+                            #(#arms)*
+                            #state_name::__Phantom(never, _) => match *never {},
+                        }
+                    }
+                }
+            }
+        };
+    })
+}
+
+/// The expression that moves the state machine onto step `i` of a path.
+fn init_state(state_name: &syn::Ident, steps: &[Step], offset: usize, i: usize) -> TokenStream {
+    let ident = format_ident!("S{}", offset + i);
+    match steps.get(i) {
+        None => TokenStream::new(),
+        Some(Step::Event(_)) => quote!(#state_name::#ident),
+        Some(Step::Value { ty, access }) => quote! {{
+            let value = #access;
+            #state_name::#ident(value, <#ty as ::sud_core::Serializer>::get_state(value))
+        }},
+        Some(Step::Flatten { ty, access }) => quote_spanned! {ty.span()=> {
+            let value = #access;
+            #state_name::#ident(value, ::sud_core::__private::Flatten::new(value))
+        }},
+        Some(Step::Tagged {
+            ty,
+            access,
+            tag,
+            variant,
+        }) => quote_spanned! {ty.span()=> {
+            let value = #access;
+            #state_name::#ident(value, ::sud_core::__private::Tagged::new(value, #tag, #variant))
+        }},
+    }
+}
+
+fn variant_steps(
+    tagging: &Tagging,
+    variant: &syn::Variant,
+    attrs: &attr::Variant,
+    access: impl Fn(&Member) -> TokenStream,
+) -> syn::Result<Vec<Step>> {
+    let name = &attrs.name;
+    let fields = parse_fields(&variant.fields, attrs.rename_all)?;
+    let unit = matches!(variant.fields, Fields::Unit);
+
+    let steps = match tagging {
+        Tagging::External if unit => vec![Step::Event(str_event(name))],
+        Tagging::External => {
+            let mut steps = vec![map_start(Some(1)), Step::Event(str_event(name))];
+            steps.extend(struct_steps(&variant.fields, &fields, access));
+            steps.push(map_end());
+            steps
+        }
+        Tagging::Internal { tag } => match &variant.fields {
+            Fields::Named(_) => {
+                let mut steps = vec![
+                    map_start(map_len(&fields).map(|len| len + 1)),
+                    Step::Event(str_event(tag)),
+                    Step::Event(str_event(name)),
+                ];
+                steps.extend(field_steps(&fields, access));
+                steps.push(map_end());
+                steps
+            }
+            Fields::Unnamed(_) => match newtype(&fields) {
+                Some(field) => vec![Step::Tagged {
+                    ty: field.ty.to_token_stream(),
+                    access: access(&field.member),
+                    tag: tag.clone(),
+                    variant: name.clone(),
+                }],
+                None => {
+                    return Err(syn::Error::new_spanned(
+                        &variant.ident,
+                        "internally tagged enums cannot contain tuple variants",
+                    ))
+                }
+            },
+            Fields::Unit => vec![
+                map_start(Some(1)),
+                Step::Event(str_event(tag)),
+                Step::Event(str_event(name)),
+                map_end(),
+            ],
+        },
+        Tagging::Adjacent { tag, content } => {
+            let mut steps = vec![
+                map_start(Some(if unit { 1 } else { 2 })),
+                Step::Event(str_event(tag)),
+                Step::Event(str_event(name)),
+            ];
+            if !unit {
+                steps.push(Step::Event(str_event(content)));
+                steps.extend(struct_steps(&variant.fields, &fields, access));
+            }
+            steps.push(map_end());
+            steps
+        }
+        Tagging::Untagged => struct_steps(&variant.fields, &fields, access),
+    };
+    Ok(steps)
+}
+
+/// Named fields are written as a map, tuple fields as a sequence.
+///
+/// A newtype is written as the value it wraps, and a unit as `null`.
+fn struct_steps(
+    ast: &Fields,
+    fields: &[FieldInfo],
+    access: impl Fn(&Member) -> TokenStream,
+) -> Vec<Step> {
+    match ast {
+        Fields::Named(_) => {
+            let mut steps = vec![map_start(map_len(fields))];
+            steps.extend(field_steps(fields, access));
+            steps.push(map_end());
+            steps
+        }
+        Fields::Unnamed(_) => {
+            if let Some(field) = newtype(fields) {
+                return vec![value_step(field.ty, access(&field.member))];
+            }
+            let fields: Vec<_> = fields.iter().filter(|field| !field.attrs.skip).collect();
+            let len = fields.len();
+            let mut steps = vec![Step::Event(quote! {
+                ::sud_core::Event::SeqStart(::core::option::Option::Some(#len))
+            })];
+            for field in fields {
+                steps.push(value_step(field.ty, access(&field.member)));
+            }
+            steps.push(Step::Event(quote!(::sud_core::Event::SeqEnd)));
+            steps
+        }
+        Fields::Unit => vec![Step::Event(quote!(::sud_core::Event::Atom(
+            ::sud_core::Atom::Null
+        )))],
+    }
+}
+
+/// The key and value steps of each named field, without the surrounding map.
+fn field_steps(fields: &[FieldInfo], access: impl Fn(&Member) -> TokenStream) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for field in fields.iter().filter(|field| !field.attrs.skip) {
+        if field.attrs.flatten {
+            steps.push(Step::Flatten {
+                ty: field.ty.to_token_stream(),
+                access: access(&field.member),
+            });
+        } else {
+            steps.push(Step::Event(str_event(&field.attrs.name)));
+            steps.push(value_step(field.ty, access(&field.member)));
+        }
+    }
+    steps
+}
+
+/// The number of entries in the map of named fields, if known up front.
+fn map_len(fields: &[FieldInfo]) -> Option<usize> {
+    let mut len = 0;
+    for field in fields.iter().filter(|field| !field.attrs.skip) {
+        if field.attrs.flatten {
+            return None;
+        }
+        len += 1;
+    }
+    Some(len)
+}
+
+/// The only field of a tuple struct that isn't skipped, if there is exactly one.
+pub fn newtype<'a, 'b>(fields: &'b [FieldInfo<'a>]) -> Option<&'b FieldInfo<'a>> {
+    let mut fields = fields.iter().filter(|field| !field.attrs.skip);
+    match (fields.next(), fields.next()) {
+        (Some(field), None) => Some(field),
+        _ => None,
+    }
+}
+
+fn value_step(ty: &Type, access: TokenStream) -> Step {
+    Step::Value {
+        ty: quote!(#ty),
+        access,
+    }
+}
+
+fn map_start(len: Option<usize>) -> Step {
+    let len = match len {
+        Some(len) => quote!(::core::option::Option::Some(#len)),
+        None => quote!(::core::option::Option::None),
+    };
+    Step::Event(quote!(::sud_core::Event::MapStart(#len)))
+}
+
+fn map_end() -> Step {
+    Step::Event(quote!(::sud_core::Event::MapEnd))
+}
+
+fn str_event(s: &str) -> TokenStream {
+    quote! {
+        ::sud_core::Event::Atom(::sud_core::Atom::Str(::std::borrow::Cow::Borrowed(#s)))
+    }
+}
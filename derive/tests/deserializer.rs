@@ -0,0 +1,527 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use sud_core::{
+    de::{from_events, Error},
+    Atom, Deserializer, Event, Progress, Serializer,
+};
+use sud_derive::{Deserializer, Serializer};
+
+fn events<T: Serializer>(value: &T) -> Vec<Event<'_>> {
+    let mut v = Vec::new();
+    value.for_each_event(&mut value.get_state(), |event| v.push(event));
+    v
+}
+
+fn str(s: &str) -> Event<'_> {
+    Event::Atom(Atom::Str(Cow::Borrowed(s)))
+}
+
+fn u64(n: u64) -> Event<'static> {
+    Event::Atom(Atom::U64(n))
+}
+
+/// Serializes the value and builds it back up from the events.
+fn round_trip<'a, T>(value: &'a T) -> T
+where
+    T: Serializer + Deserializer<'a>,
+{
+    from_events(events(value)).unwrap()
+}
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+struct Named {
+    id: u32,
+    r#type: String,
+    tags: Vec<String>,
+}
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+struct Tuple(u8, i32);
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+struct Newtype(u64);
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+struct Unit;
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+pub struct Borrowed<'a, T> {
+    name: &'a str,
+    inner: T,
+    map: BTreeMap<String, T>,
+}
+
+#[test]
+fn structs() {
+    let named = Named {
+        id: 1,
+        r#type: "foo".to_owned(),
+        tags: vec!["a".to_owned()],
+    };
+    assert_eq!(round_trip(&named), named);
+    assert_eq!(round_trip(&Tuple(1, -1)), Tuple(1, -1));
+    assert_eq!(round_trip(&Newtype(1)), Newtype(1));
+    assert_eq!(round_trip(&Unit), Unit);
+
+    let borrowed = Borrowed {
+        name: "x",
+        inner: Tuple(2, 3),
+        map: BTreeMap::from([("k".to_owned(), Tuple(4, 5))]),
+    };
+    assert_eq!(round_trip(&borrowed), borrowed);
+}
+
+#[test]
+fn field_order() {
+    let value: Named = from_events([
+        Event::MapStart(None),
+        str("tags"),
+        Event::SeqStart(None),
+        Event::SeqEnd,
+        str("type"),
+        str("foo"),
+        str("id"),
+        u64(1),
+        Event::MapEnd,
+    ])
+    .unwrap();
+
+    assert_eq!(
+        value,
+        Named {
+            id: 1,
+            r#type: "foo".to_owned(),
+            tags: vec![],
+        }
+    );
+}
+
+#[test]
+fn errors() {
+    let missing =
+        from_events::<Named, _>([Event::MapStart(None), str("id"), u64(1), Event::MapEnd]);
+    assert_eq!(missing, Err(Error::MissingField("type")));
+
+    let duplicate = from_events::<Named, _>([Event::MapStart(None), str("id"), u64(1), str("id")]);
+    assert_eq!(duplicate, Err(Error::DuplicateField("id")));
+
+    let short = from_events::<Tuple, _>([Event::SeqStart(None), u64(1), Event::SeqEnd]);
+    assert_eq!(
+        short,
+        Err(Error::InvalidLength {
+            expected: 2,
+            found: 1
+        })
+    );
+
+    let long = from_events::<Tuple, _>([Event::SeqStart(None), u64(1), u64(2), u64(3)]);
+    assert_eq!(
+        long,
+        Err(Error::InvalidLength {
+            expected: 2,
+            found: 3
+        })
+    );
+}
+
+#[test]
+fn unknown_fields() {
+    let value: Named = from_events([
+        Event::MapStart(None),
+        str("id"),
+        u64(1),
+        str("extra"),
+        Event::MapStart(None),
+        str("nested"),
+        Event::SeqStart(None),
+        u64(1),
+        Event::SeqEnd,
+        Event::MapEnd,
+        str("type"),
+        str("foo"),
+        str("tags"),
+        Event::SeqStart(None),
+        Event::SeqEnd,
+        Event::MapEnd,
+    ])
+    .unwrap();
+
+    assert_eq!(
+        value,
+        Named {
+            id: 1,
+            r#type: "foo".to_owned(),
+            tags: vec![],
+        }
+    );
+}
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+#[sud(rename_all = "camelCase")]
+struct Attrs {
+    #[sud(default)]
+    id: u64,
+    #[sud(rename = "kind", alias = "type")]
+    first_kind: String,
+    #[sud(skip)]
+    cache: Vec<u8>,
+    #[sud(default = "default_level")]
+    log_level: u8,
+}
+
+fn default_level() -> u8 {
+    3
+}
+
+#[test]
+fn field_attributes() {
+    let value = Attrs {
+        id: 1,
+        first_kind: "a".to_owned(),
+        cache: vec![1, 2, 3],
+        log_level: 4,
+    };
+    assert_eq!(
+        events(&value),
+        [
+            Event::MapStart(Some(3)),
+            str("id"),
+            u64(1),
+            str("kind"),
+            str("a"),
+            str("logLevel"),
+            u64(4),
+            Event::MapEnd,
+        ]
+    );
+
+    let value: Attrs = from_events([
+        Event::MapStart(None),
+        str("type"),
+        str("b"),
+        str("cache"),
+        Event::SeqStart(None),
+        u64(1),
+        Event::SeqEnd,
+        Event::MapEnd,
+    ])
+    .unwrap();
+    assert_eq!(
+        value,
+        Attrs {
+            id: 0,
+            first_kind: "b".to_owned(),
+            cache: vec![],
+            log_level: 3,
+        }
+    );
+}
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+#[sud(deny_unknown_fields, default)]
+struct Strict {
+    a: u8,
+    b: u8,
+}
+
+impl Default for Strict {
+    fn default() -> Self {
+        Strict { a: 1, b: 2 }
+    }
+}
+
+#[test]
+fn container_attributes() {
+    let value: Strict =
+        from_events([Event::MapStart(None), str("b"), u64(5), Event::MapEnd]).unwrap();
+    assert_eq!(value, Strict { a: 1, b: 5 });
+
+    let unknown = from_events::<Strict, _>([Event::MapStart(None), str("c"), u64(5)]);
+    assert_eq!(unknown, Err(Error::UnknownField("c".to_owned())));
+}
+
+thread_local! {
+    static DEFAULTS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Implements `Drop`, so defaulted fields can't be moved out of its
+/// `Default` value.
+#[derive(Deserializer, Debug, PartialEq)]
+#[sud(default)]
+struct Guarded {
+    name: String,
+    #[sud(skip)]
+    cache: Vec<u8>,
+}
+
+impl Default for Guarded {
+    fn default() -> Self {
+        DEFAULTS.with(|n| n.set(n.get() + 1));
+        Guarded {
+            name: "default".to_owned(),
+            cache: vec![1],
+        }
+    }
+}
+
+impl Drop for Guarded {
+    fn drop(&mut self) {}
+}
+
+#[derive(Deserializer, Debug, PartialEq)]
+#[sud(default)]
+struct Lazy {
+    a: u8,
+    b: Guarded,
+}
+
+impl Default for Lazy {
+    fn default() -> Self {
+        DEFAULTS.with(|n| n.set(n.get() + 1));
+        Lazy {
+            a: 1,
+            b: Guarded::default(),
+        }
+    }
+}
+
+#[test]
+fn container_default_drop() {
+    let value: Guarded =
+        from_events([Event::MapStart(None), str("name"), str("a"), Event::MapEnd]).unwrap();
+    assert_eq!(
+        value,
+        Guarded {
+            name: "a".to_owned(),
+            cache: vec![1],
+        }
+    );
+    let value: Guarded = from_events([Event::MapStart(None), Event::MapEnd]).unwrap();
+    assert_eq!(value.name, "default");
+
+    // the default is only built when a field is missing
+    DEFAULTS.with(|n| n.set(0));
+    let value: Lazy = from_events([
+        Event::MapStart(None),
+        str("b"),
+        Event::MapStart(None),
+        str("name"),
+        str("b"),
+        Event::MapEnd,
+        str("a"),
+        u64(2),
+        Event::MapEnd,
+    ])
+    .unwrap();
+    assert_eq!(value.a, 2);
+    assert_eq!(DEFAULTS.with(|n| n.get()), 1);
+}
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+struct Flattened {
+    id: u8,
+    #[sud(flatten)]
+    rest: BTreeMap<String, u8>,
+}
+
+#[test]
+fn flatten() {
+    let value = Flattened {
+        id: 1,
+        rest: BTreeMap::from([("a".to_owned(), 2), ("b".to_owned(), 3)]),
+    };
+    assert_eq!(
+        events(&value),
+        [
+            Event::MapStart(None),
+            str("id"),
+            u64(1),
+            str("a"),
+            u64(2),
+            str("b"),
+            u64(3),
+            Event::MapEnd,
+        ]
+    );
+    assert_eq!(round_trip(&value), value);
+}
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+#[sud(rename_all = "snake_case")]
+enum External {
+    Unit,
+    Newtype(u8),
+    Tuple(u8, u8),
+    #[sud(rename_all = "UPPERCASE", alias = "Alias")]
+    StructVariant {
+        a: u8,
+    },
+}
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+#[sud(tag = "type")]
+enum Internal {
+    Unit,
+    Newtype(Named),
+    NewtypeUnit(Unit),
+    Struct { a: u8 },
+}
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+#[sud(tag = "t", content = "c")]
+enum Adjacent {
+    Unit,
+    Newtype(u8),
+    Tuple(u8, u8),
+    Struct { a: u8 },
+}
+
+#[derive(Serializer, Deserializer, Debug, PartialEq)]
+#[sud(untagged)]
+enum Untagged {
+    Unit,
+    Newtype(u8),
+    Tuple(u8, u8),
+    Struct { a: u8 },
+}
+
+#[test]
+fn externally_tagged() {
+    for value in [
+        External::Unit,
+        External::Newtype(1),
+        External::Tuple(1, 2),
+        External::StructVariant { a: 1 },
+    ] {
+        assert_eq!(round_trip(&value), value);
+    }
+
+    assert_eq!(
+        events(&External::StructVariant { a: 1 }),
+        [
+            Event::MapStart(Some(1)),
+            str("struct_variant"),
+            Event::MapStart(Some(1)),
+            str("A"),
+            u64(1),
+            Event::MapEnd,
+            Event::MapEnd,
+        ]
+    );
+
+    let alias: External = from_events([
+        Event::MapStart(None),
+        str("Alias"),
+        Event::MapStart(None),
+        str("A"),
+        u64(2),
+        Event::MapEnd,
+        Event::MapEnd,
+    ])
+    .unwrap();
+    assert_eq!(alias, External::StructVariant { a: 2 });
+
+    let unknown = from_events::<External, _>([str("nope")]);
+    assert_eq!(unknown, Err(Error::UnknownVariant("nope".to_owned())));
+}
+
+#[test]
+fn internally_tagged() {
+    for value in [
+        Internal::Unit,
+        Internal::Newtype(Named {
+            id: 1,
+            r#type: "foo".to_owned(),
+            tags: vec![],
+        }),
+        Internal::NewtypeUnit(Unit),
+        Internal::Struct { a: 1 },
+    ] {
+        assert_eq!(round_trip(&value), value);
+    }
+
+    // the tag does not have to come first
+    let value: Internal = from_events([
+        Event::MapStart(None),
+        str("a"),
+        u64(1),
+        str("type"),
+        str("Struct"),
+        Event::MapEnd,
+    ])
+    .unwrap();
+    assert_eq!(value, Internal::Struct { a: 1 });
+
+    let missing = from_events::<Internal, _>([Event::MapStart(None), Event::MapEnd]);
+    assert_eq!(missing, Err(Error::MissingField("type")));
+}
+
+#[test]
+fn adjacently_tagged() {
+    for value in [
+        Adjacent::Unit,
+        Adjacent::Newtype(1),
+        Adjacent::Tuple(1, 2),
+        Adjacent::Struct { a: 1 },
+    ] {
+        assert_eq!(round_trip(&value), value);
+    }
+
+    // the content does not have to come after the tag
+    let value: Adjacent = from_events([
+        Event::MapStart(None),
+        str("c"),
+        Event::SeqStart(None),
+        u64(1),
+        u64(2),
+        Event::SeqEnd,
+        str("t"),
+        str("Tuple"),
+        Event::MapEnd,
+    ])
+    .unwrap();
+    assert_eq!(value, Adjacent::Tuple(1, 2));
+}
+
+#[test]
+fn untagged() {
+    for value in [
+        Untagged::Unit,
+        Untagged::Newtype(1),
+        Untagged::Tuple(1, 2),
+        Untagged::Struct { a: 1 },
+    ] {
+        assert_eq!(round_trip(&value), value);
+    }
+
+    let none = from_events::<Untagged, _>([str("x")]);
+    assert!(none.is_err());
+}
+
+#[test]
+fn resume() {
+    // the builder only ever sees one event at a time, so it can be fed from anywhere
+    let value = Internal::Struct { a: 7 };
+    let mut state = <Internal as Deserializer>::get_state();
+    let mut result = None;
+    for event in events(&value) {
+        assert!(result.is_none());
+        if let Progress::Done(value) =
+            <Internal as Deserializer>::push_event(&mut state, event).unwrap()
+        {
+            result = Some(value);
+        }
+    }
+    assert_eq!(result, Some(value));
+}
+
+#[derive(Deserializer)]
+enum Empty {}
+
+#[test]
+fn empty() {
+    assert_eq!(
+        from_events::<Empty, _>([str("x")]).err(),
+        Some(Error::UnknownVariant("x".to_owned()))
+    );
+}
@@ -0,0 +1,380 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use sud_cbor::CborSerializer;
+use sud_core::{try_polyfill::Try, Atom, Event, MapSerializer, Serializer};
+use sud_derive::Serializer;
+use sud_json::{JsonEncoder, JsonSerializer};
+
+fn events<T: Serializer>(value: &T) -> Vec<Event<'_>> {
+    let mut v = Vec::new();
+    value.for_each_event(&mut value.get_state(), |event| v.push(event));
+    v
+}
+
+fn str(s: &str) -> Event<'_> {
+    Event::Atom(Atom::Str(Cow::Borrowed(s)))
+}
+
+#[derive(Serializer)]
+struct Named {
+    id: u32,
+    r#type: String,
+    tags: Vec<&'static str>,
+}
+
+#[derive(Serializer)]
+struct Tuple(u8, i32);
+
+#[derive(Serializer)]
+struct Newtype(u64);
+
+#[derive(Serializer)]
+struct Unit;
+
+#[derive(Serializer)]
+pub struct Generic<'a, T> {
+    inner: T,
+    name: &'a str,
+    map: BTreeMap<&'a str, T>,
+}
+
+#[test]
+fn named() {
+    let value = Named {
+        id: 1,
+        r#type: "foo".to_owned(),
+        tags: vec!["a"],
+    };
+
+    assert_eq!(
+        events(&value),
+        [
+            Event::MapStart(Some(3)),
+            str("id"),
+            Event::Atom(Atom::U64(1)),
+            str("type"),
+            str("foo"),
+            str("tags"),
+            Event::SeqStart(Some(1)),
+            str("a"),
+            Event::SeqEnd,
+            Event::MapEnd,
+        ]
+    );
+}
+
+#[test]
+fn tuple() {
+    assert_eq!(
+        events(&Tuple(1, -1)),
+        [
+            Event::SeqStart(Some(2)),
+            Event::Atom(Atom::U64(1)),
+            Event::Atom(Atom::I64(-1)),
+            Event::SeqEnd,
+        ]
+    );
+    assert_eq!(events(&Newtype(1)), [Event::Atom(Atom::U64(1))]);
+    assert_eq!(events(&Unit), [Event::Atom(Atom::Null)]);
+}
+
+#[test]
+fn generic() {
+    let value = Generic {
+        inner: Tuple(2, 3),
+        name: "x",
+        map: BTreeMap::new(),
+    };
+
+    assert_eq!(
+        events(&value),
+        [
+            Event::MapStart(Some(3)),
+            str("inner"),
+            Event::SeqStart(Some(2)),
+            Event::Atom(Atom::U64(2)),
+            Event::Atom(Atom::I64(3)),
+            Event::SeqEnd,
+            str("name"),
+            str("x"),
+            str("map"),
+            Event::MapStart(Some(0)),
+            Event::MapEnd,
+            Event::MapEnd,
+        ]
+    );
+}
+
+#[test]
+fn resume() {
+    let value = Named {
+        id: 1,
+        r#type: "foo".to_owned(),
+        tags: vec!["a", "b"],
+    };
+    let expected = events(&value);
+
+    // only accept two events per call, pausing mid-struct and mid-list
+    let mut state = value.get_state();
+    let mut v = Vec::new();
+    loop {
+        let mut n = 0;
+        let res = value.try_for_each_event(&mut state, |event| {
+            if n < 2 {
+                n += 1;
+                v.push(event);
+                Ok(())
+            } else {
+                Err(())
+            }
+        });
+        if res.is_ok() {
+            break;
+        }
+    }
+
+    assert_eq!(v, expected);
+}
+
+#[derive(Serializer)]
+enum External {
+    Unit,
+    Newtype(u8),
+    Tuple(u8, u8),
+    Struct { a: u8 },
+}
+
+#[derive(Serializer)]
+#[sud(tag = "type")]
+enum Internal {
+    Unit,
+    Newtype(Named),
+    NewtypeUnit(Unit),
+    Struct { a: u8 },
+}
+
+#[derive(Serializer)]
+#[sud(tag = "t", content = "c")]
+enum Adjacent {
+    Unit,
+    Newtype(u8),
+    Tuple(u8, u8),
+    Struct { a: u8 },
+}
+
+#[derive(Serializer)]
+#[sud(untagged)]
+enum Untagged {
+    Unit,
+    Newtype(u8),
+    Tuple(u8, u8),
+    Struct { a: u8 },
+}
+
+#[derive(Serializer)]
+enum Single {
+    Only(u8),
+}
+
+#[derive(Serializer)]
+enum Empty {}
+
+fn json<T: Serializer>(value: &T) -> String {
+    let mut out = Vec::new();
+    let mut serializer = JsonSerializer::new(&mut out);
+    value
+        .try_for_each_event(&mut value.get_state(), |event| serializer.write(event))
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn cbor<T: Serializer>(value: &T) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut serializer = CborSerializer::new(&mut out);
+    value
+        .try_for_each_event(&mut value.get_state(), |event| serializer.write(event))
+        .unwrap();
+    out
+}
+
+#[test]
+fn externally_tagged() {
+    assert_eq!(json(&External::Unit), r#""Unit""#);
+    assert_eq!(json(&External::Newtype(1)), r#"{"Newtype":1}"#);
+    assert_eq!(json(&External::Tuple(1, 2)), r#"{"Tuple":[1,2]}"#);
+    assert_eq!(json(&External::Struct { a: 1 }), r#"{"Struct":{"a":1}}"#);
+    assert_eq!(json(&Single::Only(1)), r#"{"Only":1}"#);
+
+    // {"Tuple": [1, 2]}
+    assert_eq!(cbor(&External::Tuple(1, 2)), b"\xa1\x65Tuple\x82\x01\x02");
+}
+
+#[test]
+fn internally_tagged() {
+    let named = Named {
+        id: 1,
+        r#type: "foo".to_owned(),
+        tags: vec![],
+    };
+
+    assert_eq!(json(&Internal::Unit), r#"{"type":"Unit"}"#);
+    assert_eq!(
+        json(&Internal::Newtype(named)),
+        r#"{"type":"Newtype","id":1,"type":"foo","tags":[]}"#
+    );
+    assert_eq!(
+        json(&Internal::NewtypeUnit(Unit)),
+        r#"{"type":"NewtypeUnit"}"#
+    );
+    assert_eq!(
+        json(&Internal::Struct { a: 1 }),
+        r#"{"type":"Struct","a":1}"#
+    );
+
+    // {"type": "Struct", "a": 1}
+    assert_eq!(
+        cbor(&Internal::Struct { a: 1 }),
+        b"\xa2\x64type\x66Struct\x61a\x01"
+    );
+}
+
+#[test]
+fn internally_tagged_resume() {
+    let value = Internal::Newtype(Named {
+        id: 1,
+        r#type: "foo".to_owned(),
+        tags: vec![],
+    });
+    let expected = events(&value);
+    assert_eq!(expected[0], Event::MapStart(Some(4)));
+
+    // only accept one event per call, so the spliced in tag gets interrupted
+    let mut state = value.get_state();
+    let mut v = Vec::new();
+    loop {
+        let mut n = 0;
+        let res = value.try_for_each_event(&mut state, |event| {
+            if n < 1 {
+                n += 1;
+                v.push(event);
+                Ok(())
+            } else {
+                Err(())
+            }
+        });
+        if res.is_ok() {
+            break;
+        }
+    }
+
+    assert_eq!(v, expected);
+}
+
+#[test]
+fn adjacently_tagged() {
+    assert_eq!(json(&Adjacent::Unit), r#"{"t":"Unit"}"#);
+    assert_eq!(json(&Adjacent::Newtype(1)), r#"{"t":"Newtype","c":1}"#);
+    assert_eq!(json(&Adjacent::Tuple(1, 2)), r#"{"t":"Tuple","c":[1,2]}"#);
+    assert_eq!(
+        json(&Adjacent::Struct { a: 1 }),
+        r#"{"t":"Struct","c":{"a":1}}"#
+    );
+}
+
+#[test]
+fn untagged() {
+    assert_eq!(json(&Untagged::Unit), "null");
+    assert_eq!(json(&Untagged::Newtype(1)), "1");
+    assert_eq!(json(&Untagged::Tuple(1, 2)), "[1,2]");
+    assert_eq!(json(&Untagged::Struct { a: 1 }), r#"{"a":1}"#);
+}
+
+#[test]
+fn empty() {
+    fn assert_serializer<T: Serializer>() {}
+    assert_serializer::<Empty>();
+}
+
+#[derive(Serializer)]
+struct Spliced<T> {
+    id: u8,
+    #[sud(flatten)]
+    inner: T,
+    #[sud(flatten)]
+    unit: Unit,
+}
+
+#[derive(Serializer)]
+#[sud(tag = "kind")]
+enum Outer<T> {
+    Inner(T),
+    Adjacent(Adjacent),
+}
+
+/// Claims to serialize as a map, but does not.
+struct Faulty;
+
+impl Serializer for Faulty {
+    type State<'a> = bool;
+
+    fn try_fold_events<'a, B, R, F>(&'a self, done: &mut bool, init: B, mut f: F) -> R
+    where
+        R: Try<Continue = B>,
+        F: FnMut(B, Event<'a>) -> R,
+    {
+        if std::mem::replace(done, true) {
+            return R::from_continue(init);
+        }
+        f(init, Event::Atom(Atom::U64(1)))
+    }
+
+    fn get_state(&self) -> bool {
+        false
+    }
+}
+
+impl MapSerializer for Faulty {}
+
+#[test]
+fn spliced_maps() {
+    let spliced = Spliced {
+        id: 1,
+        inner: Internal::Struct { a: 2 },
+        unit: Unit,
+    };
+    assert_eq!(json(&spliced), r#"{"id":1,"type":"Struct","a":2}"#);
+    assert_eq!(
+        json(&Outer::Inner(spliced)),
+        r#"{"kind":"Inner","id":1,"type":"Struct","a":2}"#
+    );
+    assert_eq!(
+        json(&Outer::<Unit>::Adjacent(Adjacent::Newtype(3))),
+        r#"{"kind":"Adjacent","t":"Newtype","c":3}"#
+    );
+
+    // flattened values may be empty, so aren't counted in the size estimate
+    let empty = Spliced {
+        id: 1,
+        inner: BTreeMap::<String, u8>::new(),
+        unit: Unit,
+    };
+    assert_eq!(json(&empty), r#"{"id":1}"#);
+    assert!(empty.estimate_size() <= events(&empty).len());
+
+    // a value that is not a map is passed on, for the encoder to reject
+    let faulty = Outer::Inner(Faulty);
+    assert_eq!(
+        events(&faulty),
+        [
+            Event::MapStart(None),
+            str("kind"),
+            str("Inner"),
+            Event::Atom(Atom::U64(1)),
+            Event::MapEnd,
+        ]
+    );
+    let encoder = JsonEncoder::builder().strict(true).build();
+    let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+    let res = faulty.try_for_each_event(&mut faulty.get_state(), |event| serializer.write(event));
+    assert!(res.is_err());
+}
@@ -0,0 +1,186 @@
+use std::io;
+
+use sud_core::ByteEncoding;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// Writes `bytes` as a complete JSON value.
+pub(crate) fn write<W: io::Write>(
+    encoding: ByteEncoding,
+    mut dst: W,
+    bytes: &[u8],
+) -> io::Result<()> {
+    // encoded in chunks, so that long byte strings do not need an allocation
+    let mut buf = [0; 1024];
+    match encoding {
+        ByteEncoding::Array => {
+            let mut num = itoa::Buffer::new();
+            dst.write_all(b"[")?;
+            for (i, b) in bytes.iter().enumerate() {
+                if i > 0 {
+                    dst.write_all(b",")?;
+                }
+                dst.write_all(num.format(*b).as_bytes())?;
+            }
+            dst.write_all(b"]")
+        }
+        ByteEncoding::Base64 | ByteEncoding::Base64UrlNoPad => {
+            let (alphabet, pad) = match encoding {
+                ByteEncoding::Base64 => (BASE64, true),
+                _ => (BASE64_URL, false),
+            };
+            dst.write_all(b"\"")?;
+            for chunk in bytes.chunks(buf.len() / 4 * 3) {
+                let mut len = 0;
+                for group in chunk.chunks(3) {
+                    let n = group
+                        .iter()
+                        .enumerate()
+                        .fold(0, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+                    let digits = group.len() + 1;
+                    for i in 0..4 {
+                        buf[len + i] = if i < digits {
+                            alphabet[(n >> (18 - 6 * i)) as usize & 0x3f]
+                        } else {
+                            b'='
+                        };
+                    }
+                    len += if pad { 4 } else { digits };
+                }
+                dst.write_all(&buf[..len])?;
+            }
+            dst.write_all(b"\"")
+        }
+        ByteEncoding::Hex => {
+            dst.write_all(b"\"")?;
+            for chunk in bytes.chunks(buf.len() / 2) {
+                for (i, b) in chunk.iter().enumerate() {
+                    buf[2 * i] = HEX[(b >> 4) as usize];
+                    buf[2 * i + 1] = HEX[(b & 0xf) as usize];
+                }
+                dst.write_all(&buf[..chunk.len() * 2])?;
+            }
+            dst.write_all(b"\"")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use sud_core::{de::from_events, Event, Serializer};
+
+    use super::ByteEncoding;
+    use crate::{DecodeOptions, JsonEncoder, JsonParser, JsonSerializer};
+
+    fn encode(encoding: ByteEncoding, bytes: &[u8]) -> String {
+        let encoder = JsonEncoder::builder().bytes(encoding).build();
+        let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+        serializer.write(Event::from(bytes)).unwrap();
+        String::from_utf8(serializer.out).unwrap()
+    }
+
+    #[test]
+    fn encodings() {
+        let cases = [
+            (ByteEncoding::Array, "[102,111,111,98]"),
+            (ByteEncoding::Base64, r#""Zm9vYg==""#),
+            (ByteEncoding::Base64UrlNoPad, r#""Zm9vYg""#),
+            (ByteEncoding::Hex, r#""666f6f62""#),
+        ];
+        for (encoding, expected) in cases {
+            assert_eq!(encode(encoding, b"foob"), expected);
+            assert_eq!(encode(encoding, b"")[1..], expected[expected.len() - 1..]);
+        }
+
+        assert_eq!(encode(ByteEncoding::Base64, &[0xfb, 0xff]), r#""+/8=""#);
+        assert_eq!(
+            encode(ByteEncoding::Base64UrlNoPad, &[0xfb, 0xff]),
+            r#""-_8""#
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes: Vec<u8> = (0..=255).cycle().take(3000).collect();
+        let encodings = [
+            ByteEncoding::Array,
+            ByteEncoding::Base64,
+            ByteEncoding::Base64UrlNoPad,
+            ByteEncoding::Hex,
+        ];
+        for encoding in encodings {
+            for len in [0, 1, 2, 3, 4, 767, 768, 769, 3000] {
+                let value = bytes[..len].to_vec();
+                let encoder = JsonEncoder::builder().bytes(encoding).build();
+                let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+                value
+                    .try_for_each_event(&mut value.get_state(), |event| serializer.write(event))
+                    .unwrap();
+
+                let options = DecodeOptions::new().bytes(encoding);
+                let events = JsonParser::with_options(&serializer.out, options).map(Result::unwrap);
+                let decoded: Vec<u8> = from_events(events).unwrap();
+                assert_eq!(decoded, value, "{encoding:?} {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn only_the_configured_encoding() {
+        let decode = |json: &str, encoding| {
+            let options = DecodeOptions::new().bytes(encoding);
+            let events = JsonParser::with_options(json, options).map(Result::unwrap);
+            from_events::<Vec<u8>, _>(events)
+        };
+        assert_eq!(
+            decode(r#""deadbeef""#, ByteEncoding::Hex).unwrap(),
+            [0xde, 0xad, 0xbe, 0xef]
+        );
+        for (json, encoding) in [
+            (r#""deadbeef""#, ByteEncoding::Array),
+            (r#""6869""#, ByteEncoding::Array),
+            (r#""aGk""#, ByteEncoding::Base64),
+            (r#""aGk=""#, ByteEncoding::Base64UrlNoPad),
+            (r#""-_8""#, ByteEncoding::Base64),
+            (r#""aGk=""#, ByteEncoding::Hex),
+            (r#""DEADBEEF""#, ByteEncoding::Hex),
+        ] {
+            assert!(decode(json, encoding).is_err(), "{json} {encoding:?}");
+        }
+        // without the option, no string is bytes
+        let events = JsonParser::new(r#""aGk=""#).map(Result::unwrap);
+        assert!(from_events::<Vec<u8>, _>(events).is_err());
+    }
+
+    #[test]
+    fn strings_are_left_alone() {
+        // strings that happen to be valid base64 or hex are still strings
+        let options = DecodeOptions::new().bytes(ByteEncoding::Hex);
+        let json = r#"{"6869": "6869", "a": "test"}"#;
+        let value: BTreeMap<String, String> =
+            from_events(JsonParser::with_options(json, options).map(Result::unwrap)).unwrap();
+        assert_eq!(value["6869"], "6869");
+        let events: Vec<_> = JsonParser::new(r#"{"6869": "6869", "a": "test", "b": "cafe"}"#)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            events,
+            [
+                Event::MapStart(None),
+                Event::from("6869"),
+                Event::from("6869"),
+                Event::from("a"),
+                Event::from("test"),
+                Event::from("b"),
+                Event::from("cafe"),
+                Event::MapEnd,
+            ]
+        );
+        let value: BTreeMap<String, String> = from_events(events).unwrap();
+        assert_eq!(value["a"], "test");
+    }
+}
@@ -0,0 +1,367 @@
+use std::io::{self, Write};
+
+use sud_core::{Atom, Event};
+
+use crate::{
+    format_escaped_str, invalid_data, number::Decimal, parse::is_number, EncodeError, JsonEncoder,
+    JsonParser, MapKeys, Object, Position, State, ESCAPE,
+};
+
+/// The entries of a map that is still being written in canonical mode.
+///
+/// Each value is written out as soon as its events arrive, but the entries can
+/// only be ordered once the map ends.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CanonicalMap {
+    /// Each key with its encoded value, which starts with the `:`.
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl JsonEncoder {
+    /// Writes an event for RFC 8785 (JCS) output.
+    ///
+    /// The events have already been validated, so maps are balanced and every
+    /// key has a value.
+    pub(crate) fn write_canonical<W: Write>(&mut self, event: Event<'_>, dst: W) -> io::Result<()> {
+        if self.at_key() {
+            let key = match event {
+                Event::Atom(atom) => self.canonical_key(atom).map_err(invalid_data)?,
+                Event::MapEnd => return self.end_canonical_map(dst),
+                _ => return Err(invalid_data(EncodeError::NonStringKey)),
+            };
+            // keys are only written once the map is sorted
+            self.write_prefix(io::sink())?;
+            self.end_value();
+            self.maps
+                .last_mut()
+                .expect("every map has an entry list")
+                .entries
+                .push((key, Vec::new()));
+            return Ok(());
+        }
+
+        match event {
+            Event::Atom(Atom::RawJson(raw)) => {
+                // re-encoded, since its maps have to be sorted too. That is done
+                // on its own first, so that an invalid value changes nothing here.
+                let mut value = JsonEncoder {
+                    options: self.options.clone(),
+                    ..JsonEncoder::default()
+                };
+                value.options.json_lines = false;
+                let mut buf = Vec::new();
+                for event in JsonParser::new(raw.as_bytes()) {
+                    let event =
+                        event.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                    value.write(event, &mut buf)?;
+                }
+                let raw = String::from_utf8(buf).expect("the encoder writes utf-8");
+                let event = Event::Atom(Atom::RawJson(raw.into()));
+                self.in_target(dst, |enc, dst| enc.write_event(event, dst))
+            }
+            Event::MapStart(_) => {
+                self.in_target(dst, |enc, dst| enc.write_prefix(dst))?;
+                self.stack.push(State {
+                    pos: Position::First,
+                    object: Object::MapKey,
+                });
+                self.maps.push(CanonicalMap::default());
+                Ok(())
+            }
+            event => self.in_target(dst, |enc, dst| enc.write_event(event, dst)),
+        }
+    }
+
+    fn end_canonical_map<W: Write>(&mut self, dst: W) -> io::Result<()> {
+        let entries = &mut self
+            .maps
+            .last_mut()
+            .expect("every map has an entry list")
+            .entries;
+        entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+        if entries.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(invalid_data(EncodeError::DuplicateKey));
+        }
+        let map = self.maps.pop().expect("every map has an entry list");
+        self.stack.pop();
+
+        self.in_target(dst, |_, dst| {
+            dst.write_all(b"{")?;
+            for (i, (key, value)) in map.entries.iter().enumerate() {
+                if i > 0 {
+                    dst.write_all(b",")?;
+                }
+                format_escaped_str(dst, key, &ESCAPE)?;
+                dst.write_all(value)?;
+            }
+            dst.write_all(b"}")
+        })?;
+        self.end_value();
+        Ok(())
+    }
+
+    /// Runs `f` against the value of the innermost open map, or `dst` outside of any map.
+    fn in_target<W: Write>(
+        &mut self,
+        mut dst: W,
+        f: impl FnOnce(&mut Self, &mut dyn Write) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let Some((_, value)) = self.maps.last_mut().and_then(|map| map.entries.last_mut()) else {
+            return f(self, &mut dst);
+        };
+        let mut buf = std::mem::take(value);
+        let res = f(self, &mut buf);
+        if let Some((_, value)) = self.maps.last_mut().and_then(|map| map.entries.last_mut()) {
+            *value = buf;
+        }
+        res
+    }
+
+    fn canonical_key(&self, atom: Atom<'_>) -> Result<String, EncodeError> {
+        match atom {
+            Atom::Str(s) | Atom::Hinted(s, _) => Ok(s.into_owned()),
+            Atom::Char(c) => Ok(c.to_string()),
+            _ if self.options.map_keys == MapKeys::Verbatim => Err(EncodeError::NonStringKey),
+            Atom::Bool(b) => Ok(b.to_string()),
+            Atom::U64(n) => Ok(n.to_string()),
+            Atom::I64(n) => Ok(n.to_string()),
+            Atom::Number(text) if finite(&text) => number_key(text.parse().unwrap()),
+            Atom::F64(f) if f.is_finite() => number_key(f),
+            _ => Err(EncodeError::NonStringKey),
+        }
+    }
+}
+
+fn number_key(f: f64) -> Result<String, EncodeError> {
+    let mut key = Vec::new();
+    write_number(&mut key, f).expect("writing to a vec cannot fail");
+    Ok(String::from_utf8(key).expect("numbers are ascii"))
+}
+
+/// Whether the text of an `Atom::Number` is a number that is a finite double,
+/// rounded.
+pub(crate) fn finite(text: &str) -> bool {
+    is_number(text) && text.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+/// Writes a finite float the way ECMAScript's `Number.prototype.toString` does.
+pub(crate) fn write_number<W: Write>(mut dst: W, f: f64) -> io::Result<()> {
+    if f == 0.0 {
+        return dst.write_all(b"0");
+    }
+
+    // ryu finds the shortest digits that round trip, which is what ECMAScript
+    // asks for too. It only lays them out differently.
+    let mut buf = ryu::Buffer::new();
+    let Decimal {
+        negative,
+        digits,
+        point: n,
+    } = Decimal::parse(buf.format_finite(f)).expect("ryu writes small exponents");
+    let k = digits.len() as i64;
+
+    if negative {
+        dst.write_all(b"-")?;
+    }
+    if k <= n && n <= 21 {
+        dst.write_all(&digits)?;
+        for _ in k..n {
+            dst.write_all(b"0")?;
+        }
+    } else if 0 < n && n <= 21 {
+        let (int, frac) = digits.split_at(n as usize);
+        dst.write_all(int)?;
+        dst.write_all(b".")?;
+        dst.write_all(frac)?;
+    } else if -6 < n && n <= 0 {
+        dst.write_all(b"0.")?;
+        for _ in n..0 {
+            dst.write_all(b"0")?;
+        }
+        dst.write_all(&digits)?;
+    } else {
+        dst.write_all(&digits[..1])?;
+        if k > 1 {
+            dst.write_all(b".")?;
+            dst.write_all(&digits[1..])?;
+        }
+        let e = n - 1;
+        write!(
+            dst,
+            "e{}{}",
+            if e < 0 { '-' } else { '+' },
+            e.unsigned_abs()
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use sud_core::{Atom, Event, Serializer, Value};
+
+    use super::write_number;
+    use crate::{
+        EncodeError, IntegerStrings, JsonEncoder, JsonParser, JsonSerializer, PrettyConfig,
+    };
+
+    fn number(f: f64) -> String {
+        let mut out = Vec::new();
+        write_number(&mut out, f).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn numbers() {
+        // from RFC 8785 appendix B, and ECMAScript's own examples
+        let cases = [
+            (0.0, "0"),
+            (-0.0, "0"),
+            (5e-324, "5e-324"),
+            (-5e-324, "-5e-324"),
+            (1.7976931348623157e308, "1.7976931348623157e+308"),
+            (-1.7976931348623157e308, "-1.7976931348623157e+308"),
+            (9007199254740992.0, "9007199254740992"),
+            (-9007199254740992.0, "-9007199254740992"),
+            (295147905179352830000.0, "295147905179352830000"),
+            (1e21, "1e+21"),
+            (9.999999999999997e22, "9.999999999999997e+22"),
+            (1e23, "1e+23"),
+            (0.000001, "0.000001"),
+            (1e-7, "1e-7"),
+            (333333333.3333333, "333333333.3333333"),
+            (4.5, "4.5"),
+            (0.002, "0.002"),
+            (1e-27, "1e-27"),
+            (100.0, "100"),
+            (123.456, "123.456"),
+        ];
+        for (f, expected) in cases {
+            assert_eq!(number(f), expected, "{f:e}");
+        }
+    }
+
+    fn canonical(value: &Value) -> io::Result<String> {
+        let encoder = JsonEncoder::builder().canonical(true).build();
+        let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+        value.try_for_each_event(&mut value.get_state(), |event| serializer.write(event))?;
+        assert!(serializer.enc.maps.is_empty());
+        Ok(String::from_utf8(serializer.out).unwrap())
+    }
+
+    fn parse(json: &str) -> Value {
+        sud_core::de::from_events(JsonParser::new(json).map(Result::unwrap)).unwrap()
+    }
+
+    #[test]
+    fn sorted() {
+        // RFC 8785 section 3.2.3
+        let value = parse(
+            r#"{
+                "€": "Euro Sign",
+                "\r": "Carriage Return",
+                "דּ": "Hebrew Letter Dalet With Dagesh",
+                "1": "One",
+                "😀": "Emoji: Grinning Face",
+                "\u0080": "Control",
+                "ö": "Latin Small Letter O With Diaeresis"
+            }"#,
+        );
+        let output = canonical(&value).unwrap();
+        let keys: Vec<_> = JsonParser::new(&output)
+            .map(Result::unwrap)
+            .skip(1)
+            .step_by(2)
+            .filter_map(|event| match event {
+                Event::Atom(Atom::Str(s)) => Some(s.into_owned()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "\r",
+                "1",
+                "\u{80}",
+                "\u{f6}",
+                "\u{20ac}",
+                "\u{1f600}",
+                "\u{fb33}"
+            ]
+        );
+    }
+
+    #[test]
+    fn document() {
+        // RFC 8785 section 3.2.2, with the nesting and escaping
+        let value = parse(
+            r#"{
+                "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+                "string": "€$\u000F\u000aA'B\u0022\u005c\\\"\/",
+                "literals": [null, true, false],
+                "nested": {"b": [], "a": {"d": 1, "c": 2}}
+            }"#,
+        );
+        assert_eq!(
+            canonical(&value).unwrap(),
+            concat!(
+                r#"{"literals":[null,true,false],"nested":{"a":{"c":2,"d":1},"b":[]},"#,
+                r#""numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"#,
+                r#""string":"€$\u000f\nA'B\"\\\\\"/"}"#
+            )
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let encoder = JsonEncoder::builder()
+            .pretty(PrettyConfig::new())
+            .integer_strings(IntegerStrings::Always)
+            .canonical(true)
+            .build();
+        let value = parse(r#"{"b": [1, 18446744073709551615], "a": 1.0}"#);
+        let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+        value
+            .try_for_each_event(&mut value.get_state(), |event| serializer.write(event))
+            .unwrap();
+        assert_eq!(serializer.out, br#"{"a":1,"b":[1,18446744073709552000]}"#);
+
+        let err = canonical(&Value::Seq(vec![Value::F64(f64::NAN)])).unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(&EncodeError::NonFiniteFloat)
+        );
+        let err = canonical(&Value::Map(vec![(Value::Null, Value::Null)])).unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(&EncodeError::NonStringKey)
+        );
+    }
+
+    #[test]
+    fn rejected() {
+        let key = || Value::Str("a".to_owned());
+        let dup = Value::Map(vec![(key(), Value::U64(1)), (key(), Value::U64(2))]);
+        let err = canonical(&dup).unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(&EncodeError::DuplicateKey)
+        );
+
+        // an invalid raw value leaves the encoder as it was
+        let encoder = JsonEncoder::builder().canonical(true).build();
+        let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+        serializer.write(Event::MapStart(None)).unwrap();
+        serializer.write(Event::from("x")).unwrap();
+        for raw in [r#"{"b": 1, "a": [1,"#, r#"{"a": 1, "a": 2}"#, "", "1 2"] {
+            let raw = Event::Atom(Atom::RawJson(raw.into()));
+            assert!(serializer.write(raw).is_err());
+        }
+        let raw = Event::Atom(Atom::RawJson(r#"{"b": 1, "a": [1]}"#.into()));
+        serializer.write(raw).unwrap();
+        serializer.write(Event::MapEnd).unwrap();
+        assert_eq!(serializer.out, br#"{"x":{"a":[1],"b":1}}"#);
+    }
+}
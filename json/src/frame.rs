@@ -1,8 +1,10 @@
-use bytes::{BufMut, BytesMut};
+use std::io;
+
+use bytes::{Buf, BufMut, BytesMut};
 use sud_core::Event;
-use tokio_util::codec::Encoder;
+use tokio_util::codec::{Decoder, Encoder};
 
-use crate::JsonEncoder;
+use crate::{parse::Tokenizer, DecodeOptions, JsonEncoder};
 
 impl<'a> Encoder<Event<'a>> for JsonEncoder {
     type Error = std::io::Error;
@@ -11,3 +13,181 @@ impl<'a> Encoder<Event<'a>> for JsonEncoder {
         self.write(item, dst.writer())
     }
 }
+
+/// Decodes a stream of JSON values into events.
+///
+/// Each event is returned as soon as it is complete. The nesting, and how much
+/// of a partially received string has been scanned, carry over between calls.
+/// Any number of top level values can follow each other in the stream.
+///
+/// Syntax errors are returned as [`io::ErrorKind::InvalidData`] wrapping a
+/// [`ParseError`](crate::ParseError).
+#[derive(Default)]
+pub struct JsonDecoder {
+    tokenizer: Tokenizer,
+}
+
+impl JsonDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: DecodeOptions) -> Self {
+        JsonDecoder {
+            tokenizer: Tokenizer::new(options),
+        }
+    }
+
+    fn decode_event(
+        &mut self,
+        src: &mut BytesMut,
+        eof: bool,
+    ) -> io::Result<Option<Event<'static>>> {
+        self.tokenizer.reset();
+
+        let mut pos = 0;
+        self.tokenizer.skip_whitespace(src, &mut pos);
+        let res = if eof && pos == src.len() && self.tokenizer.at_boundary() {
+            Ok(None)
+        } else {
+            self.tokenizer
+                .next(src, &mut pos, eof)
+                .map(|event| event.map(Event::into_static))
+        };
+
+        src.advance(pos);
+        self.tokenizer.advance(pos);
+        res.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+impl Decoder for JsonDecoder {
+    type Item = Event<'static>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_event(src, false)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_event(src, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use futures_util::StreamExt;
+    use sud_core::{Atom, Event};
+    use tokio_util::codec::{Decoder, FramedRead};
+
+    use super::JsonDecoder;
+    use crate::{ErrorKind, JsonParser, ParseError};
+
+    const DOC: &str =
+        r#"{"key": ["a\"b", 12345, -1.5e3, true, null, {}], "long": "aaaaaaaaaaaaaaaaaaaaé"}"#;
+
+    /// Feeds the input in chunks of `size` bytes, collecting every event.
+    fn decode_chunked(input: &str, size: usize) -> Result<Vec<Event<'static>>, std::io::Error> {
+        let mut decoder = JsonDecoder::new();
+        let mut buf = BytesMut::new();
+        let mut events = Vec::new();
+        for chunk in input.as_bytes().chunks(size) {
+            buf.extend_from_slice(chunk);
+            while let Some(event) = decoder.decode(&mut buf)? {
+                events.push(event);
+            }
+        }
+        while let Some(event) = decoder.decode_eof(&mut buf)? {
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    #[test]
+    fn chunked() {
+        let expected: Vec<_> = JsonParser::new(DOC)
+            .map(|event| event.unwrap().into_static())
+            .collect();
+
+        for size in 1..=DOC.len() {
+            assert_eq!(
+                decode_chunked(DOC, size).unwrap(),
+                expected,
+                "chunk size {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn eager() {
+        let mut decoder = JsonDecoder::new();
+        let mut buf = BytesMut::from(r#"[1, "ab"#);
+
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap(),
+            Some(Event::SeqStart(None))
+        );
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap(),
+            Some(Event::Atom(Atom::U64(1)))
+        );
+        assert_eq!(decoder.decode(&mut buf).unwrap(), None);
+        // the partial string is kept in the buffer until it is complete
+        assert_eq!(&buf[..], br#""ab"#);
+
+        buf.extend_from_slice(br#"c"]"#);
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(Event::from("abc")));
+        assert_eq!(decoder.decode(&mut buf).unwrap(), Some(Event::SeqEnd));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn numbers_wait_for_a_delimiter() {
+        let mut decoder = JsonDecoder::new();
+        let mut buf = BytesMut::from("12");
+        assert_eq!(decoder.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"3");
+        assert_eq!(decoder.decode(&mut buf).unwrap(), None);
+        assert_eq!(
+            decoder.decode_eof(&mut buf).unwrap(),
+            Some(Event::Atom(Atom::U64(123)))
+        );
+        assert_eq!(decoder.decode_eof(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn errors_have_stream_positions() {
+        let err = decode_chunked("[1,\n 2,\n ]", 2).unwrap_err();
+        let err = err.get_ref().unwrap().downcast_ref::<ParseError>().unwrap();
+        assert_eq!(*err.kind(), ErrorKind::ExpectedValue);
+        assert_eq!((err.offset(), err.line(), err.column()), (9, 3, 2));
+
+        let err = decode_chunked("[1", 1).unwrap_err();
+        let err = err.get_ref().unwrap().downcast_ref::<ParseError>().unwrap();
+        assert_eq!(*err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn framed_read() {
+        let input: &[u8] = b"{\"a\": 1}\n[true]\n\"x\" ";
+        let events: Vec<_> = FramedRead::new(input, JsonDecoder::new())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        assert_eq!(
+            events,
+            [
+                Event::MapStart(None),
+                Event::from("a"),
+                Event::Atom(Atom::U64(1)),
+                Event::MapEnd,
+                Event::SeqStart(None),
+                Event::Atom(Atom::Bool(true)),
+                Event::SeqEnd,
+                Event::from("x"),
+            ]
+        );
+    }
+}
@@ -2,16 +2,101 @@ use std::io::{self, Write};
 
 use sud_core::{Atom, Event};
 
+use crate::{canonical::CanonicalMap, scan::Scan};
+
+pub use frame::JsonDecoder;
+pub use lines::JsonLinesDecoder;
+pub use parse::{DecodeOptions, ErrorKind, JsonParser, ParseError};
+pub use pretty::{Newline, PrettyConfig};
+pub use strict::EncodeError;
+pub use sud_core::ByteEncoding;
+
+mod bytes;
+mod canonical;
 mod frame;
+mod lines;
+mod number;
+mod parse;
+mod pretty;
+mod scan;
+mod strict;
 
 pub struct JsonSerializer<W> {
     enc: JsonEncoder,
     out: W,
 }
 
-#[derive(Default)]
-struct JsonEncoder {
+/// Writes events as JSON text, to any [`Write`] or through a
+/// [`FramedWrite`](tokio_util::codec::FramedWrite).
+///
+/// The default encoder writes compact JSON. Use [`JsonEncoder::builder`] to
+/// configure the output.
+#[derive(Clone, Debug, Default)]
+pub struct JsonEncoder {
     stack: Vec<State>,
+    options: Options,
+    /// The top level value is complete.
+    finished: bool,
+    /// The open maps, only in canonical mode.
+    maps: Vec<CanonicalMap>,
+}
+
+/// Configures a [`JsonEncoder`].
+#[derive(Clone, Debug, Default)]
+pub struct JsonEncoderBuilder {
+    options: Options,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Options {
+    pretty: Option<PrettyConfig>,
+    strict: bool,
+    map_keys: MapKeys,
+    bytes: ByteEncoding,
+    non_finite: NonFinite,
+    integer_strings: IntegerStrings,
+    canonical: bool,
+    escape_html: bool,
+    escape_non_ascii: bool,
+    json_lines: bool,
+}
+
+/// How map keys that are not strings are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MapKeys {
+    /// Integer, float and bool keys are quoted, like `{"1":true}`. Any other
+    /// key is an [`EncodeError::NonStringKey`].
+    #[default]
+    Coerce,
+    /// Keys are written like any other value, which may not be valid JSON.
+    Verbatim,
+}
+
+/// Which integers are written as strings, for readers that parse every number
+/// as a double, like JavaScript.
+///
+/// The decoder reads these as strings, and integer types accept them wherever
+/// an integer is expected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntegerStrings {
+    #[default]
+    Never,
+    /// Integers that a double cannot hold exactly, outside of
+    /// `-(2^53 - 1)..=2^53 - 1`, like `"9007199254740993"`.
+    OutsideSafeRange,
+    Always,
+}
+
+/// How NaN and the infinities are written, since JSON has no numbers for them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonFinite {
+    /// Written as `null`, like `JSON.stringify` does.
+    #[default]
+    Null,
+    /// Written as the strings `"NaN"`, `"Infinity"` and `"-Infinity"`.
+    Str,
+    /// An [`EncodeError::NonFiniteFloat`].
+    Error,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -52,8 +137,18 @@ impl Object {
 
 impl<W> JsonSerializer<W> {
     pub fn new(output: W) -> Self {
+        Self::with_encoder(output, JsonEncoder::new())
+    }
+
+    /// Writes indented JSON, laid out as described by `config`.
+    pub fn pretty(output: W, config: PrettyConfig) -> Self {
+        Self::with_encoder(output, JsonEncoder::builder().pretty(config).build())
+    }
+
+    /// Writes to `output` using a configured encoder.
+    pub fn with_encoder(output: W, encoder: JsonEncoder) -> Self {
         Self {
-            enc: JsonEncoder::default(),
+            enc: encoder,
             out: output,
         }
     }
@@ -65,36 +160,175 @@ impl<W: Write> JsonSerializer<W> {
     }
 }
 
+impl JsonEncoderBuilder {
+    /// Writes indented JSON, laid out as described by `config`.
+    pub fn pretty(mut self, config: PrettyConfig) -> Self {
+        self.options.pretty = Some(config);
+        self
+    }
+
+    /// Returns an [`EncodeError`] for events that would not make valid JSON,
+    /// instead of writing them anyway. An `Atom::RawJson` is parsed to check
+    /// that it is a single JSON value.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    pub fn map_keys(mut self, map_keys: MapKeys) -> Self {
+        self.options.map_keys = map_keys;
+        self
+    }
+
+    pub fn bytes(mut self, encoding: ByteEncoding) -> Self {
+        self.options.bytes = encoding;
+        self
+    }
+
+    pub fn non_finite(mut self, policy: NonFinite) -> Self {
+        self.options.non_finite = policy;
+        self
+    }
+
+    pub fn integer_strings(mut self, policy: IntegerStrings) -> Self {
+        self.options.integer_strings = policy;
+        self
+    }
+
+    /// Writes the canonical form of RFC 8785 (JCS), for signing or hashing.
+    ///
+    /// Map entries are sorted by their keys, so each open map is buffered until
+    /// it ends. Floats are written as ECMAScript does, and integers outside of
+    /// the safe range are written as the nearest float. There is no whitespace,
+    /// so [`pretty`](Self::pretty) is ignored, and strings are only escaped where
+    /// JSON requires it. Integers are never quoted, so
+    /// [`integer_strings`](Self::integer_strings) is ignored too. Events are
+    /// always checked as if in [`strict`](Self::strict) mode, and non-finite
+    /// floats are an error.
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.options.canonical = canonical;
+        self
+    }
+
+    /// Escapes `<`, `>`, `&`, U+2028 and U+2029, so that the output can be
+    /// embedded in an HTML `<script>` tag or read as JavaScript source.
+    pub fn escape_html(mut self, escape: bool) -> Self {
+        self.options.escape_html = escape;
+        self
+    }
+
+    /// Escapes every character outside of ASCII as `\uXXXX`, using a surrogate
+    /// pair for characters outside of the basic multilingual plane.
+    pub fn escape_non_ascii(mut self, escape: bool) -> Self {
+        self.options.escape_non_ascii = escape;
+        self
+    }
+
+    /// Writes JSON Lines, ending every top level value with `\n`.
+    ///
+    /// Any number of values can be written, one per line, so
+    /// [`pretty`](Self::pretty) is ignored. An `Atom::RawJson` with a line
+    /// break of its own is an [`EncodeError::RawNewline`].
+    pub fn json_lines(mut self, json_lines: bool) -> Self {
+        self.options.json_lines = json_lines;
+        self
+    }
+
+    pub fn build(mut self) -> JsonEncoder {
+        if self.options.json_lines {
+            self.options.pretty = None;
+        }
+        if self.options.canonical {
+            self.options.pretty = None;
+            self.options.escape_html = false;
+            self.options.escape_non_ascii = false;
+            self.options.integer_strings = IntegerStrings::Never;
+        }
+        JsonEncoder {
+            options: self.options,
+            ..JsonEncoder::default()
+        }
+    }
+}
+
 impl JsonEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn builder() -> JsonEncoderBuilder {
+        JsonEncoderBuilder::default()
+    }
+
     pub fn write<W: Write>(&mut self, event: Event<'_>, mut dst: W) -> io::Result<()> {
+        if self.options.strict || self.options.canonical {
+            self.validate(&event).map_err(invalid_data)?;
+        }
+
+        if self.options.canonical {
+            self.write_canonical(event, &mut dst)?;
+        } else {
+            self.write_event(event, &mut dst)?;
+        }
+
+        if self.options.json_lines && self.finished {
+            // ready for the next line
+            self.finished = false;
+            dst.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn write_event<W: Write>(&mut self, event: Event<'_>, mut dst: W) -> io::Result<()> {
         match event {
             Event::Atom(atom) => {
-                if let Some(State { pos, object }) = self.stack.last_mut() {
-                    if *pos == Position::NotFirst {
-                        dst.write_all(object.prefix())?;
-                    } else {
-                        *pos = Position::NotFirst;
-                    }
+                let quote = self.quote_key(&atom).map_err(invalid_data)? || self.quote_int(&atom);
+                if matches!(atom, Atom::F64(f) if !f.is_finite())
+                    && (self.options.non_finite == NonFinite::Error || self.options.canonical)
+                {
+                    return Err(invalid_data(EncodeError::NonFiniteFloat));
+                }
+                if matches!(&atom, Atom::Number(text) if !parse::is_number(text)) {
+                    return Err(invalid_data(EncodeError::InvalidNumber));
+                }
+                if matches!(&atom, Atom::Number(text) if !canonical::finite(text))
+                    && self.options.canonical
+                {
+                    return Err(invalid_data(EncodeError::NonFiniteFloat));
+                }
+                if matches!(&atom, Atom::RawJson(raw) if raw.contains('\n'))
+                    && self.options.json_lines
+                {
+                    return Err(invalid_data(EncodeError::RawNewline));
+                }
+                self.write_prefix(&mut dst)?;
+                if quote {
+                    dst.write_all(b"\"")?;
                 }
 
                 match atom {
                     Atom::Null => dst.write_all(b"null")?,
                     Atom::Bool(true) => dst.write_all(b"true")?,
                     Atom::Bool(false) => dst.write_all(b"false")?,
-                    Atom::Char(c) => format_escaped_str(&mut dst, c.encode_utf8(&mut [0; 4]))?,
-                    Atom::Str(s) => format_escaped_str(&mut dst, &s)?,
-                    Atom::Bytes(b) => {
-                        let mut buf = itoa::Buffer::new();
-                        let mut not_first = false;
-                        dst.write_all(b"[")?;
-                        for b in &*b {
-                            if not_first {
-                                dst.write_all(b",")?;
-                                not_first = true;
-                            }
-                            dst.write_all(buf.format(*b).as_bytes())?;
-                        }
-                        dst.write_all(b"]")?;
+                    Atom::Char(c) => {
+                        format_escaped_str(&mut dst, c.encode_utf8(&mut [0; 4]), self.escapes())?
+                    }
+                    Atom::Str(s) | Atom::Hinted(s, _) => {
+                        format_escaped_str(&mut dst, &s, self.escapes())?
+                    }
+                    Atom::Bytes(b) => bytes::write(self.options.bytes, &mut dst, &b)?,
+                    Atom::Number(text) if self.options.canonical => {
+                        canonical::write_number(&mut dst, text.parse().unwrap())?
+                    }
+                    Atom::Number(text) => dst.write_all(text.as_bytes())?,
+                    Atom::RawJson(raw) => dst.write_all(raw.as_bytes())?,
+                    Atom::U64(i) if self.options.canonical && i > MAX_SAFE_INTEGER => {
+                        canonical::write_number(&mut dst, i as f64)?
+                    }
+                    Atom::I64(i)
+                        if self.options.canonical && i.unsigned_abs() > MAX_SAFE_INTEGER =>
+                    {
+                        canonical::write_number(&mut dst, i as f64)?
                     }
                     Atom::U64(i) => {
                         let mut buf = itoa::Buffer::new();
@@ -104,25 +338,31 @@ impl JsonEncoder {
                         let mut buf = itoa::Buffer::new();
                         dst.write_all(buf.format(i).as_bytes())?;
                     }
+                    Atom::F64(f) if !f.is_finite() => match self.options.non_finite {
+                        NonFinite::Str if f.is_nan() => dst.write_all(b"\"NaN\"")?,
+                        NonFinite::Str if f > 0.0 => dst.write_all(b"\"Infinity\"")?,
+                        NonFinite::Str => dst.write_all(b"\"-Infinity\"")?,
+                        _ => dst.write_all(b"null")?,
+                    },
+                    Atom::F64(f) if self.options.canonical => canonical::write_number(&mut dst, f)?,
                     Atom::F64(i) => {
                         let mut buf = ryu::Buffer::new();
                         dst.write_all(buf.format(i).as_bytes())?;
                     }
-                    _ => {
-                        return Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            "unsupported atom in JSON",
-                        ))
-                    }
+                    _ => return Err(io::Error::other("unsupported atom in JSON")),
                 }
-
-                if let Some(State { object, .. }) = self.stack.last_mut() {
-                    *object = object.next();
+                if quote {
+                    dst.write_all(b"\"")?;
                 }
 
+                self.end_value();
                 Ok(())
             }
             Event::MapStart(_) => {
+                if self.options.map_keys == MapKeys::Coerce && self.at_key() {
+                    return Err(invalid_data(EncodeError::NonStringKey));
+                }
+                self.write_prefix(&mut dst)?;
                 dst.write_all(b"{")?;
                 self.stack.push(State {
                     pos: Position::First,
@@ -130,12 +370,12 @@ impl JsonEncoder {
                 });
                 Ok(())
             }
-            Event::MapEnd => {
-                dst.write_all(b"}")?;
-                self.stack.pop();
-                Ok(())
-            }
+            Event::MapEnd => self.write_end(&mut dst, b"}"),
             Event::SeqStart(_) => {
+                if self.options.map_keys == MapKeys::Coerce && self.at_key() {
+                    return Err(invalid_data(EncodeError::NonStringKey));
+                }
+                self.write_prefix(&mut dst)?;
                 dst.write_all(b"[")?;
                 self.stack.push(State {
                     pos: Position::First,
@@ -143,21 +383,120 @@ impl JsonEncoder {
                 });
                 Ok(())
             }
-            Event::SeqEnd => {
-                dst.write_all(b"]")?;
-                self.stack.pop();
-                Ok(())
+            Event::SeqEnd => self.write_end(&mut dst, b"]"),
+        }
+    }
+
+    fn at_key(&self) -> bool {
+        matches!(
+            self.stack.last(),
+            Some(State {
+                object: Object::MapKey,
+                ..
+            })
+        )
+    }
+
+    /// Whether an atom has to be quoted to be written as a map key.
+    fn quote_key(&self, atom: &Atom<'_>) -> Result<bool, EncodeError> {
+        if self.options.map_keys != MapKeys::Coerce || !self.at_key() {
+            return Ok(false);
+        }
+        match atom {
+            Atom::Str(_) | Atom::Hinted(..) | Atom::Char(_) => Ok(false),
+            Atom::U64(_) | Atom::I64(_) | Atom::Number(_) | Atom::Bool(_) => Ok(true),
+            Atom::F64(f) if f.is_finite() => Ok(true),
+            _ => Err(EncodeError::NonStringKey),
+        }
+    }
+
+    /// Whether an integer has to be written as a string.
+    fn quote_int(&self, atom: &Atom<'_>) -> bool {
+        let magnitude = match *atom {
+            Atom::U64(n) => n,
+            Atom::I64(n) => n.unsigned_abs(),
+            // only integers, which are all too large for 64 bits when read
+            Atom::Number(ref text) if !text.contains(['.', 'e', 'E']) => {
+                text.trim_start_matches('-').parse().unwrap_or(u64::MAX)
+            }
+            _ => return false,
+        };
+        match self.options.integer_strings {
+            IntegerStrings::Never => false,
+            IntegerStrings::OutsideSafeRange => magnitude > MAX_SAFE_INTEGER,
+            IntegerStrings::Always => true,
+        }
+    }
+
+    /// Writes the separator that comes before a new value or key, if any.
+    fn write_prefix<W: Write>(&mut self, mut dst: W) -> io::Result<()> {
+        let depth = self.stack.len();
+        let Some(State { pos, object }) = self.stack.last_mut() else {
+            return Ok(());
+        };
+        let first = *pos == Position::First;
+        *pos = Position::NotFirst;
+
+        match (&self.options.pretty, *object) {
+            (None, object) if !first => dst.write_all(object.prefix()),
+            (None, _) => Ok(()),
+            (Some(pretty), Object::MapValue) => pretty.write_colon(dst),
+            (Some(pretty), _) => {
+                if !first {
+                    dst.write_all(b",")?;
+                }
+                pretty.write_line(dst, depth)
             }
         }
     }
+
+    /// Closes the innermost container.
+    fn write_end<W: Write>(&mut self, mut dst: W, close: &[u8]) -> io::Result<()> {
+        let state = self.stack.pop();
+        if let (Some(pretty), Some(State { pos, .. })) = (&self.options.pretty, state) {
+            if pretty.break_before_end(pos == Position::First) {
+                pretty.write_line(&mut dst, self.stack.len())?;
+            }
+        }
+        dst.write_all(close)?;
+        self.end_value();
+        Ok(())
+    }
+
+    /// Moves the parent on once a complete value or key has been written.
+    fn end_value(&mut self) {
+        match self.stack.last_mut() {
+            Some(State { object, .. }) => *object = object.next(),
+            None => self.finished = true,
+        }
+    }
+}
+
+impl JsonEncoder {
+    /// The escape table for strings, from the [`ESCAPE`] family.
+    fn escapes(&self) -> &'static [u8; 256] {
+        match (self.options.escape_html, self.options.escape_non_ascii) {
+            (false, false) => &ESCAPE,
+            (true, false) => &HTML_ESCAPE,
+            (false, true) => &ASCII_ESCAPE,
+            (true, true) => &HTML_ASCII_ESCAPE,
+        }
+    }
 }
 
-fn format_escaped_str<W>(writer: &mut W, value: &str) -> io::Result<()>
+/// The largest integer that a double holds exactly, along with all smaller ones.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+fn invalid_data(err: EncodeError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn format_escaped_str<W>(writer: &mut W, value: &str, table: &[u8; 256]) -> io::Result<()>
 where
     W: ?Sized + io::Write,
 {
     writer.write_all(b"\"")?;
-    format_escaped_str_contents(writer, value)?;
+    format_escaped_str_contents(writer, value, table)?;
     writer.write_all(b"\"")
 }
 
@@ -182,6 +521,9 @@ enum CharEscape {
     /// An escaped ASCII plane control character (usually escaped as
     /// `\u00XX` where `XX` are two hex characters)
     AsciiControl(u8),
+    /// A character outside of ASCII, escaped as `\uXXXX` for each of its UTF-16
+    /// code units
+    NonAscii(char),
 }
 
 impl CharEscape {
@@ -229,33 +571,71 @@ where
             ];
             return writer.write_all(bytes);
         }
+        NonAscii(c) => {
+            static HEX_DIGITS: [u8; 16] = *b"0123456789abcdef";
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                let bytes = &[
+                    b'\\',
+                    b'u',
+                    HEX_DIGITS[(*unit >> 12) as usize],
+                    HEX_DIGITS[(*unit >> 8 & 0xF) as usize],
+                    HEX_DIGITS[(*unit >> 4 & 0xF) as usize],
+                    HEX_DIGITS[(*unit & 0xF) as usize],
+                ];
+                writer.write_all(bytes)?;
+            }
+            return Ok(());
+        }
     };
 
     writer.write_all(s)
 }
 
-fn format_escaped_str_contents<W>(writer: &mut W, value: &str) -> io::Result<()>
+fn format_escaped_str_contents<W>(writer: &mut W, value: &str, table: &[u8; 256]) -> io::Result<()>
 where
     W: ?Sized + io::Write,
 {
     let bytes = value.as_bytes();
 
+    let scan = Scan::new(table);
     let mut start = 0;
+    let mut i = 0;
 
-    for (i, &byte) in bytes.iter().enumerate() {
-        let escape = ESCAPE[byte as usize];
+    loop {
+        i += scan.skip(&bytes[i..]);
+        if i == bytes.len() {
+            break;
+        }
+
+        let byte = bytes[i];
+        let escape = table[byte as usize];
         if escape == 0 {
+            i += 1;
             continue;
         }
 
+        // only the first byte of each character is looked up, so a multibyte
+        // character is escaped as a whole or skipped as a whole
+        let (char_escape, len) = match escape {
+            NA | LS => {
+                let c = value[i..].chars().next().unwrap();
+                if escape == LS && !matches!(c, '\u{2028}' | '\u{2029}') {
+                    i += c.len_utf8();
+                    continue;
+                }
+                (CharEscape::NonAscii(c), c.len_utf8())
+            }
+            _ => (CharEscape::from_escape_table(escape, byte), 1),
+        };
+
         if start < i {
             writer.write_all(&bytes[start..i])?;
         }
 
-        let char_escape = CharEscape::from_escape_table(escape, byte);
         write_char_escape(writer, char_escape)?;
 
-        start = i + 1;
+        i += len;
+        start = i;
     }
 
     if start == bytes.len() {
@@ -273,6 +653,8 @@ const RR: u8 = b'r'; // \x0D
 const QU: u8 = b'"'; // \x22
 const BS: u8 = b'\\'; // \x5C
 const UU: u8 = b'u'; // \x00...\x1F except the ones above
+const NA: u8 = 0x80; // the first byte of any character outside of ASCII
+const LS: u8 = 0xE2; // the first byte of U+2028 and U+2029, among others
 const __: u8 = 0;
 
 // Lookup table of escape sequences. A value of b'x' at index i means that byte
@@ -297,15 +679,51 @@ static ESCAPE: [u8; 256] = [
     __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, // F
 ];
 
+/// [`ESCAPE`] with `<`, `>`, `&`, U+2028 and U+2029 escaped as well.
+static HTML_ESCAPE: [u8; 256] = escape_table(true, false);
+/// [`ESCAPE`] with every character outside of ASCII escaped as well.
+static ASCII_ESCAPE: [u8; 256] = escape_table(false, true);
+static HTML_ASCII_ESCAPE: [u8; 256] = escape_table(true, true);
+
+const fn escape_table(html: bool, non_ascii: bool) -> [u8; 256] {
+    let mut table = ESCAPE;
+    if html {
+        table[b'<' as usize] = UU;
+        table[b'>' as usize] = UU;
+        table[b'&' as usize] = UU;
+        table[0xE2] = LS;
+    }
+    if non_ascii {
+        let mut byte = 0x80;
+        while byte < table.len() {
+            table[byte] = NA;
+            byte += 1;
+        }
+    }
+    table
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, future::poll_fn, pin::pin, task::Poll};
+    use std::{
+        collections::{BTreeMap, HashMap},
+        future::poll_fn,
+        io,
+        pin::pin,
+        task::Poll,
+    };
 
     use futures_util::{sink::Sink, SinkExt};
-    use sud_core::{tri, Serializer};
+    use sud_core::{
+        de::{self, from_events},
+        tri, Atom, Event, Serializer, Value,
+    };
     use tokio_util::codec::FramedWrite;
 
-    use crate::{JsonEncoder, JsonSerializer};
+    use crate::{
+        DecodeOptions, EncodeError, IntegerStrings, JsonEncoder, JsonParser, JsonSerializer,
+        NonFinite, PrettyConfig,
+    };
 
     #[test]
     fn int_slice() {
@@ -322,6 +740,21 @@ mod tests {
         assert_eq!(serializer.enc.stack, &[]);
     }
 
+    #[test]
+    fn nested() {
+        let data = [vec![1, 2], vec![], vec![3]];
+
+        let mut serializer = JsonSerializer::new(Vec::new());
+
+        data.try_for_each_event(&mut data.get_state(), |event| serializer.write(event))
+            .unwrap();
+
+        let output = String::from_utf8(serializer.out).unwrap();
+        assert_eq!(output, "[[1,2],[],[3]]");
+
+        assert_eq!(serializer.enc.stack, &[]);
+    }
+
     #[test]
     fn str_map() {
         let data = HashMap::from([("abc", 1), ("def", 2)]);
@@ -346,7 +779,7 @@ mod tests {
     async fn async_str_map() {
         let data = HashMap::from([("abc", 1), ("def", 2)]);
 
-        let mut serializer = pin!(FramedWrite::new(Vec::new(), JsonEncoder::default()));
+        let mut serializer = pin!(FramedWrite::new(Vec::new(), JsonEncoder::new()));
 
         let mut state = data.get_state();
         poll_fn(|cx| {
@@ -370,4 +803,237 @@ mod tests {
 
         assert_eq!(serializer.encoder().stack, &[]);
     }
+
+    #[test]
+    fn map_keys() {
+        let data = BTreeMap::from([(-1, "a"), (2, "b")]);
+
+        let mut serializer = JsonSerializer::new(Vec::new());
+        data.try_for_each_event(&mut data.get_state(), |event| serializer.write(event))
+            .unwrap();
+        assert_eq!(serializer.out, br#"{"-1":"a","2":"b"}"#);
+
+        let events = JsonParser::new(&serializer.out).map(Result::unwrap);
+        let decoded: BTreeMap<i32, &str> = from_events(events).unwrap();
+        assert_eq!(decoded, data);
+
+        let value = Value::Map(vec![
+            (Value::Bool(true), Value::Null),
+            (Value::F64(1.5), Value::Null),
+            (Value::Char('c'), Value::Null),
+        ]);
+        let mut serializer = JsonSerializer::new(Vec::new());
+        value
+            .try_for_each_event(&mut value.get_state(), |event| serializer.write(event))
+            .unwrap();
+        assert_eq!(serializer.out, br#"{"true":null,"1.5":null,"c":null}"#);
+    }
+
+    #[test]
+    fn invalid_map_keys() {
+        for key in [
+            Event::Atom(Atom::Null),
+            Event::Atom(Atom::F64(f64::NAN)),
+            Event::SeqStart(None),
+            Event::MapStart(None),
+        ] {
+            let mut serializer = JsonSerializer::new(Vec::new());
+            serializer.write(Event::MapStart(None)).unwrap();
+            let err = serializer.write(key).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(
+                err.get_ref().unwrap().downcast_ref(),
+                Some(&EncodeError::NonStringKey)
+            );
+            assert_eq!(serializer.out, b"{");
+        }
+    }
+
+    #[test]
+    fn non_finite() {
+        let floats = Value::Seq(
+            [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1.5]
+                .map(Value::F64)
+                .to_vec(),
+        );
+        let write = |policy| {
+            let encoder = JsonEncoder::builder().non_finite(policy).build();
+            let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+            floats
+                .try_for_each_event(&mut floats.get_state(), |event| serializer.write(event))
+                .map(|()| String::from_utf8(serializer.out).unwrap())
+        };
+
+        assert_eq!(write(NonFinite::Null).unwrap(), "[null,null,null,1.5]");
+        let output = write(NonFinite::Str).unwrap();
+        assert_eq!(output, r#"["NaN","Infinity","-Infinity",1.5]"#);
+        let err = write(NonFinite::Error).unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(&EncodeError::NonFiniteFloat)
+        );
+
+        let options = DecodeOptions::new().non_finite_strings(true);
+        let events = JsonParser::with_options(&output, options.clone()).map(Result::unwrap);
+        let decoded: Vec<f64> = from_events(events).unwrap();
+        assert!(decoded[0].is_nan());
+        assert_eq!(decoded[1..], [f64::INFINITY, f64::NEG_INFINITY, 1.5]);
+
+        // the strings are still strings where a float isn't expected
+        let events = JsonParser::with_options(&output, options.clone()).map(Result::unwrap);
+        let decoded: Vec<Value> = from_events(events).unwrap();
+        assert_eq!(decoded[0], Value::Str("NaN".to_owned()));
+        let events = JsonParser::with_options(r#""nan""#, options).map(Result::unwrap);
+        assert!(from_events::<f64, _>(events).is_err());
+
+        // only when asked to
+        let events = JsonParser::new(&output).map(Result::unwrap);
+        assert!(from_events::<Vec<f64>, _>(events).is_err());
+    }
+
+    #[test]
+    fn integer_strings() {
+        let max_safe = (1 << 53) - 1;
+        let data = BTreeMap::from([
+            (1, vec![max_safe, -max_safe]),
+            (2, vec![max_safe + 1, -max_safe - 1, i64::MIN]),
+        ]);
+        let write = |policy| {
+            let encoder = JsonEncoder::builder().integer_strings(policy).build();
+            let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+            data.try_for_each_event(&mut data.get_state(), |event| serializer.write(event))
+                .unwrap();
+            String::from_utf8(serializer.out).unwrap()
+        };
+
+        assert_eq!(
+            write(IntegerStrings::Never),
+            r#"{"1":[9007199254740991,-9007199254740991],"2":[9007199254740992,-9007199254740992,-9223372036854775808]}"#
+        );
+        let output = write(IntegerStrings::OutsideSafeRange);
+        assert_eq!(
+            output,
+            r#"{"1":[9007199254740991,-9007199254740991],"2":["9007199254740992","-9007199254740992","-9223372036854775808"]}"#
+        );
+        assert_eq!(
+            write(IntegerStrings::Always),
+            r#"{"1":["9007199254740991","-9007199254740991"],"2":["9007199254740992","-9007199254740992","-9223372036854775808"]}"#
+        );
+
+        fn parse(input: &str) -> impl Iterator<Item = Event<'_>> {
+            let options = DecodeOptions::new().integer_strings(true);
+            JsonParser::with_options(input, options).map(Result::unwrap)
+        }
+        let decoded: BTreeMap<u8, Vec<i64>> = from_events(parse(output.as_str())).unwrap();
+        assert_eq!(decoded, data);
+
+        // only when asked to
+        let events = JsonParser::new(&output).map(Result::unwrap);
+        assert!(from_events::<BTreeMap<u8, Vec<i64>>, _>(events).is_err());
+
+        // strings are left alone, and only the form the encoder writes is an integer
+        let input = r#"["18446744073709551615", "-0", "01", "1.0", "x"]"#;
+        let decoded: Vec<String> = from_events(parse(input)).unwrap();
+        assert_eq!(decoded[1], "-0");
+        for n in [r#""-0""#, r#""01""#, r#""+1""#, r#""1.0""#, r#""x""#] {
+            assert!(matches!(
+                from_events::<u64, _>(parse(n)),
+                Err(de::Error::Unexpected { .. })
+            ));
+        }
+        let events = parse(r#""18446744073709551615""#);
+        assert_eq!(from_events::<u64, _>(events), Ok(u64::MAX));
+        assert_eq!(from_events::<u8, _>(parse(r#""256""#)), Err(de::Error::OutOfRange));
+    }
+
+    #[test]
+    fn escapes() {
+        let data = BTreeMap::from([(
+            "</script>".to_owned(),
+            "a & b\u{2028}\u{2029}\u{e2}\u{20ac}\n\u{7f}\u{80}\u{e9}\u{1f600}".to_owned(),
+        )]);
+        let write = |html, non_ascii| {
+            let encoder = JsonEncoder::builder()
+                .escape_html(html)
+                .escape_non_ascii(non_ascii)
+                .build();
+            let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+            data.try_for_each_event(&mut data.get_state(), |event| serializer.write(event))
+                .unwrap();
+            String::from_utf8(serializer.out).unwrap()
+        };
+
+        let cases = [
+            (
+                false,
+                false,
+                "{\"</script>\":\"a & b\u{2028}\u{2029}\u{e2}\u{20ac}\\n\u{7f}\u{80}\u{e9}\u{1f600}\"}",
+            ),
+            (
+                true,
+                false,
+                "{\"\\u003c/script\\u003e\":\"a \\u0026 b\\u2028\\u2029\u{e2}\u{20ac}\\n\u{7f}\u{80}\u{e9}\u{1f600}\"}",
+            ),
+            (
+                false,
+                true,
+                "{\"</script>\":\"a & b\\u2028\\u2029\\u00e2\\u20ac\\n\u{7f}\\u0080\\u00e9\\ud83d\\ude00\"}",
+            ),
+            (
+                true,
+                true,
+                "{\"\\u003c/script\\u003e\":\"a \\u0026 b\\u2028\\u2029\\u00e2\\u20ac\\n\u{7f}\\u0080\\u00e9\\ud83d\\ude00\"}",
+            ),
+        ];
+        for (html, non_ascii, expected) in cases {
+            let output = write(html, non_ascii);
+            assert_eq!(output, expected);
+
+            let events = JsonParser::new(&output).map(Result::unwrap);
+            let decoded: BTreeMap<String, String> = from_events(events).unwrap();
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn raw_json() {
+        let input = r#"{"a": {"z": 1,  "y": [ 2 ]}, "b": 1.50}"#;
+        let options = DecodeOptions::new().raw_depth(1);
+        let events: Vec<_> = JsonParser::with_options(input, options)
+            .map(Result::unwrap)
+            .collect();
+        let write = |encoder: JsonEncoder| {
+            let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+            for event in events.iter().cloned() {
+                serializer.write(event)?;
+            }
+            Ok::<_, io::Error>(String::from_utf8(serializer.out).unwrap())
+        };
+
+        assert_eq!(
+            write(JsonEncoder::new()).unwrap(),
+            r#"{"a":{"z": 1,  "y": [ 2 ]},"b":1.50}"#
+        );
+        let pretty = JsonEncoder::builder().pretty(PrettyConfig::new()).build();
+        assert_eq!(
+            write(pretty).unwrap(),
+            "{\n  \"a\": {\"z\": 1,  \"y\": [ 2 ]},\n  \"b\": 1.50\n}"
+        );
+        // canonical output has to be re-encoded
+        let canonical = JsonEncoder::builder().canonical(true).build();
+        assert_eq!(
+            write(canonical).unwrap(),
+            r#"{"a":{"y":[2],"z":1},"b":1.5}"#
+        );
+
+        let lines = JsonEncoder::builder().json_lines(true).build();
+        let mut serializer = JsonSerializer::with_encoder(Vec::new(), lines);
+        let err = serializer
+            .write(Event::Atom(Atom::RawJson("[\n1]".into())))
+            .unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(&EncodeError::RawNewline)
+        );
+    }
 }
@@ -0,0 +1,206 @@
+use std::io;
+
+use bytes::{Buf, BytesMut};
+use sud_core::Event;
+use tokio_util::codec::Decoder;
+
+use crate::{parse::Tokenizer, DecodeOptions, ErrorKind, ParseError};
+
+/// Decodes JSON Lines, one top level value per line, into the events of each
+/// value.
+///
+/// A line is only parsed once all of it has arrived. A malformed line is
+/// returned as an `Err` item and skipped, so that the following lines can
+/// still be read; the codec's own error is only used for I/O errors. Blank
+/// lines are skipped, and lines may end with `\r\n`.
+#[derive(Default)]
+pub struct JsonLinesDecoder {
+    tokenizer: Tokenizer,
+    /// How much of the buffer is known not to contain a `\n`.
+    scanned: usize,
+}
+
+impl JsonLinesDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: DecodeOptions) -> Self {
+        JsonLinesDecoder {
+            tokenizer: Tokenizer::new(options),
+            scanned: 0,
+        }
+    }
+
+    /// Reads the value on a line, without its line break.
+    fn parse_line(&mut self, line: &[u8]) -> Result<Option<Vec<Event<'static>>>, ParseError> {
+        let mut pos = 0;
+        self.tokenizer.skip_whitespace(line, &mut pos);
+        if pos == line.len() {
+            return Ok(None);
+        }
+
+        let mut events = Vec::new();
+        while let Some(event) = self.tokenizer.next(line, &mut pos, true)? {
+            events.push(event.into_static());
+        }
+        self.tokenizer.skip_whitespace(line, &mut pos);
+        if pos < line.len() {
+            return Err(self.tokenizer.error(ErrorKind::TrailingCharacters, pos));
+        }
+        Ok(Some(events))
+    }
+
+    /// Parses the first `len` bytes of `src`, which hold one line.
+    fn decode_line(
+        &mut self,
+        src: &mut BytesMut,
+        len: usize,
+    ) -> Option<Result<Vec<Event<'static>>, ParseError>> {
+        let line = &src[..len];
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let res = self.parse_line(line).transpose();
+        src.advance(len);
+        self.tokenizer.next_line(len);
+        self.scanned = 0;
+        res
+    }
+}
+
+impl Decoder for JsonLinesDecoder {
+    type Item = Result<Vec<Event<'static>>, ParseError>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let Some(n) = src[self.scanned..].iter().position(|&b| b == b'\n') else {
+                self.scanned = src.len();
+                return Ok(None);
+            };
+            let len = self.scanned + n + 1;
+            if let Some(item) = self.decode_line(src, len) {
+                return Ok(Some(item));
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if let Some(item) = self.decode(src)? {
+            return Ok(Some(item));
+        }
+        // the last line does not need a line break
+        let len = src.len();
+        Ok(self.decode_line(src, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use futures_util::StreamExt;
+    use sud_core::{de::from_events, Atom, Event, Serializer, Value};
+    use tokio_util::codec::{Decoder, FramedRead};
+
+    use super::JsonLinesDecoder;
+    use crate::{EncodeError, ErrorKind, JsonEncoder, JsonSerializer, PrettyConfig};
+
+    fn records() -> Vec<Value> {
+        vec![
+            Value::Map(vec![(
+                Value::Str("a".to_owned()),
+                Value::Str("line\nbreak".to_owned()),
+            )]),
+            Value::Seq(vec![Value::U64(1), Value::Seq(vec![])]),
+            Value::Null,
+            Value::F64(1.5),
+        ]
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let encoder = JsonEncoder::builder().json_lines(true).strict(true).build();
+        let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+        for record in records() {
+            record
+                .try_for_each_event(&mut record.get_state(), |event| serializer.write(event))
+                .unwrap();
+        }
+        let output = serializer.out;
+        assert_eq!(output, b"{\"a\":\"line\\nbreak\"}\n[1,[]]\nnull\n1.5\n");
+
+        let decoded: Vec<Value> = FramedRead::new(&output[..], JsonLinesDecoder::new())
+            .map(|line| from_events(line.unwrap().unwrap()).unwrap())
+            .collect()
+            .await;
+        assert_eq!(decoded, records());
+    }
+
+    #[test]
+    fn raw_newlines() {
+        let encoder = JsonEncoder::builder()
+            .json_lines(true)
+            .pretty(PrettyConfig::new())
+            .build();
+        let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+        serializer.write(Event::Atom(Atom::U64(1))).unwrap();
+        serializer.write(Event::SeqStart(None)).unwrap();
+        serializer.write(Event::SeqEnd).unwrap();
+        // pretty printing is ignored, so that every value fits on its line
+        serializer.write(Event::SeqStart(None)).unwrap();
+        serializer.write(Event::Atom(Atom::U64(1))).unwrap();
+        assert_eq!(serializer.out, b"1\n[]\n[1");
+
+        let err = serializer
+            .write(Event::Atom(Atom::RawJson("[\n2]".into())))
+            .unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(&EncodeError::RawNewline)
+        );
+        serializer.write(Event::SeqEnd).unwrap();
+        assert_eq!(serializer.out, b"1\n[]\n[1]\n");
+    }
+
+    #[test]
+    fn resync() {
+        let input = "{\"a\": 1}\r\n[1, 2\n\n  \"x\"  \n{\"b\": tru}\n3 4\n[\"\u{e9}\"]";
+        let mut decoder = JsonLinesDecoder::new();
+        let mut buf = BytesMut::new();
+        let mut lines = Vec::new();
+        // one byte at a time, to check that lines are only read once complete
+        for &byte in input.as_bytes() {
+            buf.extend_from_slice(&[byte]);
+            while let Some(line) = decoder.decode(&mut buf).unwrap() {
+                lines.push(line);
+            }
+        }
+        while let Some(line) = decoder.decode_eof(&mut buf).unwrap() {
+            lines.push(line);
+        }
+
+        assert_eq!(lines.len(), 6);
+        assert_eq!(
+            lines[0].as_deref().unwrap(),
+            [
+                Event::MapStart(None),
+                Event::from("a"),
+                Event::Atom(Atom::U64(1)),
+                Event::MapEnd,
+            ]
+        );
+        let err = lines[1].as_ref().unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!((err.line(), err.column()), (2, 6));
+        assert_eq!(lines[2].as_deref().unwrap(), [Event::from("x")]);
+        let err = lines[3].as_ref().unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::ExpectedValue);
+        assert_eq!((err.offset(), err.line(), err.column()), (31, 5, 7));
+        let err = lines[4].as_ref().unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::TrailingCharacters);
+        assert_eq!((err.line(), err.column()), (6, 3));
+        assert_eq!(
+            lines[5].as_deref().unwrap(),
+            [Event::SeqStart(None), Event::from("\u{e9}"), Event::SeqEnd]
+        );
+    }
+}
@@ -0,0 +1,156 @@
+/// A decimal number split into its significant digits and the position of its
+/// decimal point, so that numbers written in different ways can be compared.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Decimal {
+    pub(crate) negative: bool,
+    /// The digits without any leading or trailing zeros, so zero has none.
+    pub(crate) digits: Vec<u8>,
+    /// The value is `0.digits * 10^point`.
+    pub(crate) point: i64,
+}
+
+impl Decimal {
+    /// Reads a number in JSON's syntax, which is also what ryu writes.
+    ///
+    /// Returns `None` if the exponent is too large to work with.
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text),
+        };
+        let (mantissa, exp) = match text.split_once(['e', 'E']) {
+            Some((m, e)) => (m, e.strip_prefix('+').unwrap_or(e).parse::<i64>().ok()?),
+            None => (text, 0),
+        };
+        let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+        let mut digits: Vec<u8> = int.bytes().chain(frac.bytes()).collect();
+        let leading = digits.iter().take_while(|&&d| d == b'0').count();
+        digits.drain(..leading);
+        while digits.last() == Some(&b'0') {
+            digits.pop();
+        }
+        let point = if digits.is_empty() {
+            0
+        } else {
+            exp.checked_add(int.len() as i64 - leading as i64)?
+        };
+        Some(Decimal {
+            negative,
+            digits,
+            point,
+        })
+    }
+}
+
+/// Whether `f` is exactly the number written as `text`, in its shortest form.
+pub(crate) fn is_exact(text: &str, f: f64) -> bool {
+    if !f.is_finite() {
+        return false;
+    }
+    let mut buf = ryu::Buffer::new();
+    let shortest = Decimal::parse(buf.format_finite(f));
+    shortest.is_some() && shortest == Decimal::parse(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, io};
+
+    use sud_core::{Atom, Event};
+
+    use crate::{DecodeOptions, EncodeError, JsonEncoder, JsonParser, JsonSerializer};
+
+    const DOC: &str = "[12345678901234567890123, 0.1000000000000000055511151231257827, \
+        1.5, 1E30, 0.1, -5, -0.0, 1e400]";
+
+    fn atoms(options: DecodeOptions) -> Vec<Atom<'static>> {
+        JsonParser::with_options(DOC, options)
+            .filter_map(|event| match event.unwrap() {
+                Event::Atom(atom) => Some(atom.to_static()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn encode(encoder: JsonEncoder, atoms: &[Atom<'_>]) -> io::Result<String> {
+        let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+        serializer.write(Event::SeqStart(None))?;
+        for atom in atoms {
+            serializer.write(Event::Atom(atom.clone()))?;
+        }
+        serializer.write(Event::SeqEnd)?;
+        Ok(String::from_utf8(serializer.out).unwrap())
+    }
+
+    #[test]
+    fn exact_numbers() {
+        let number = |s| Atom::Number(Cow::Borrowed(s));
+        let atoms = atoms(DecodeOptions::new().exact_numbers(true));
+        assert_eq!(
+            atoms,
+            [
+                number("12345678901234567890123"),
+                number("0.1000000000000000055511151231257827"),
+                Atom::F64(1.5),
+                Atom::F64(1e30),
+                Atom::F64(0.1),
+                Atom::I64(-5),
+                Atom::F64(-0.0),
+                number("1e400"),
+            ]
+        );
+
+        // written back out as they were read
+        let numbers: Vec<_> = atoms
+            .into_iter()
+            .filter(|atom| matches!(atom, Atom::Number(_)))
+            .collect();
+        assert_eq!(
+            encode(JsonEncoder::new(), &numbers).unwrap(),
+            "[12345678901234567890123,0.1000000000000000055511151231257827,1e400]"
+        );
+
+        // canonical output has to round them to a float
+        let canonical = || JsonEncoder::builder().canonical(true).build();
+        assert_eq!(
+            encode(canonical(), &numbers[..2]).unwrap(),
+            "[1.2345678901234568e+22,0.1]"
+        );
+        let err = encode(canonical(), &numbers[2..]).unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(&EncodeError::NonFiniteFloat)
+        );
+    }
+
+    #[test]
+    fn rounded_numbers() {
+        let atoms = atoms(DecodeOptions::new());
+        assert_eq!(atoms[0], Atom::F64(1.2345678901234568e22));
+        assert_eq!(atoms[1], Atom::F64(0.1));
+        assert_eq!(atoms[7], Atom::F64(f64::INFINITY));
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        for text in [
+            "", "-", "01", "1.", ".5", "+1", "1e", "0x10", "inf", "NaN", "1 2", "1,2",
+        ] {
+            let number = Atom::Number(Cow::Borrowed(text));
+            for encoder in [
+                JsonEncoder::new(),
+                JsonEncoder::builder().canonical(true).build(),
+            ] {
+                let err = encode(encoder, std::slice::from_ref(&number)).unwrap_err();
+                assert_eq!(
+                    err.get_ref().unwrap().downcast_ref(),
+                    Some(&EncodeError::InvalidNumber),
+                    "{text:?}"
+                );
+            }
+        }
+        let number = Atom::Number(Cow::Borrowed("-0.5E+3"));
+        assert_eq!(encode(JsonEncoder::new(), &[number]).unwrap(), "[-0.5E+3]");
+    }
+}
@@ -0,0 +1,1041 @@
+use std::{borrow::Cow, fmt};
+
+use sud_core::{Atom, ByteEncoding, Event, Hints};
+
+use crate::number;
+
+/// A pull based JSON parser over an in-memory document.
+///
+/// Strings without escapes are borrowed straight from the input.
+pub struct JsonParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    tokenizer: Tokenizer,
+    failed: bool,
+}
+
+impl<'a> JsonParser<'a> {
+    pub fn new(input: &'a (impl AsRef<[u8]> + ?Sized)) -> Self {
+        Self::with_options(input, DecodeOptions::default())
+    }
+
+    pub fn with_options(input: &'a (impl AsRef<[u8]> + ?Sized), options: DecodeOptions) -> Self {
+        JsonParser {
+            input: input.as_ref(),
+            pos: 0,
+            tokenizer: Tokenizer::new(options),
+            failed: false,
+        }
+    }
+}
+
+/// Configures how a [`JsonParser`] or [`JsonDecoder`](crate::JsonDecoder)
+/// turns JSON values into events.
+#[derive(Clone, Debug, Default)]
+pub struct DecodeOptions {
+    raw_depth: Option<usize>,
+    exact_numbers: bool,
+    hints: Hints,
+}
+
+impl DecodeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets the string values `"NaN"`, `"Infinity"` and `"-Infinity"` be read
+    /// as floats where a float is expected, the way
+    /// [`NonFinite::Str`](crate::NonFinite::Str) writes them. Strings are
+    /// still strings everywhere else.
+    pub fn non_finite_strings(mut self, accept: bool) -> Self {
+        self.hints.non_finite = accept;
+        self
+    }
+
+    /// Lets string values that hold an integer, like `"9007199254740993"`, be
+    /// read as integers where an integer is expected, the way
+    /// [`IntegerStrings`](crate::IntegerStrings) writes them. Strings are still
+    /// strings everywhere else.
+    ///
+    /// Only the form the encoder writes is an integer, so `"01"`, `"-0"` and
+    /// `"+1"` are not.
+    pub fn integer_strings(mut self, accept: bool) -> Self {
+        self.hints.integers = accept;
+        self
+    }
+
+    /// Reads numbers that would lose precision as a float, like
+    /// `12345678901234567890123` or `0.1000000000000000055511151231257827`,
+    /// as `Atom::Number` holding their text.
+    ///
+    /// Integers that fit in 64 bits are always read as integers, and any other
+    /// number is only read as a float if that float's shortest form is the
+    /// same number.
+    pub fn exact_numbers(mut self, exact: bool) -> Self {
+        self.exact_numbers = exact;
+        self
+    }
+
+    /// Lets string values be read as bytes in this encoding where bytes are
+    /// expected, the way [`JsonEncoderBuilder::bytes`](crate::JsonEncoderBuilder::bytes)
+    /// writes them. Strings are still strings everywhere else.
+    ///
+    /// Only the exact form of the encoding is accepted, so with
+    /// [`ByteEncoding::Base64`] a string of hex or of URL-safe base64 is not
+    /// bytes. [`ByteEncoding::Array`], the default, reads no strings as bytes.
+    pub fn bytes(mut self, encoding: ByteEncoding) -> Self {
+        self.hints.bytes = (encoding != ByteEncoding::Array).then_some(encoding);
+        self
+    }
+
+    /// Reads every value nested `depth` containers deep as one `Atom::RawJson`
+    /// holding its source text, rather than as events. A depth of 0 reads
+    /// each top level value whole.
+    ///
+    /// The value is skipped over by following its strings and brackets, so
+    /// what is inside of a raw map, sequence or string is not checked.
+    pub fn raw_depth(mut self, depth: usize) -> Self {
+        self.raw_depth = Some(depth);
+        self
+    }
+}
+
+impl<'a> Iterator for JsonParser<'a> {
+    type Item = Result<Event<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let res = match self.tokenizer.next(self.input, &mut self.pos, true) {
+            Ok(Some(event)) => return Some(Ok(event)),
+            Ok(None) => {
+                // the document is complete, there should be nothing left
+                self.tokenizer.skip_whitespace(self.input, &mut self.pos);
+                if self.pos == self.input.len() {
+                    return None;
+                }
+                Err(self
+                    .tokenizer
+                    .error(ErrorKind::TrailingCharacters, self.pos))
+            }
+            Err(err) => Err(err),
+        };
+        self.failed = true;
+        Some(res)
+    }
+}
+
+/// An error in the syntax of a JSON document.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    kind: ErrorKind,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl ParseError {
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The byte offset into the input where the error was found.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The 1-based line of the error.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The 1-based column of the error, in bytes.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {} column {}",
+            self.kind, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    UnexpectedEof,
+    ExpectedValue,
+    ExpectedKey,
+    ExpectedColon,
+    /// Expected a `,` or the end of the current map or sequence.
+    ExpectedCommaOrEnd,
+    InvalidNumber,
+    InvalidEscape,
+    /// A `\u` escape that is not a valid unicode scalar value, eg a lone surrogate.
+    InvalidUnicode,
+    ControlCharacter,
+    InvalidUtf8,
+    TrailingCharacters,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::UnexpectedEof => "unexpected end of input",
+            ErrorKind::ExpectedValue => "expected value",
+            ErrorKind::ExpectedKey => "expected string key",
+            ErrorKind::ExpectedColon => "expected `:`",
+            ErrorKind::ExpectedCommaOrEnd => "expected `,` or end of container",
+            ErrorKind::InvalidNumber => "invalid number",
+            ErrorKind::InvalidEscape => "invalid escape",
+            ErrorKind::InvalidUnicode => "invalid unicode code point",
+            ErrorKind::ControlCharacter => "control character in string",
+            ErrorKind::InvalidUtf8 => "invalid utf-8",
+            ErrorKind::TrailingCharacters => "trailing characters",
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Nest {
+    Map,
+    Seq,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Expect {
+    Value,
+    /// Just after a `[`.
+    FirstValue,
+    /// Just after a `{`.
+    FirstKey,
+    Key,
+    Colon,
+    CommaOrEnd,
+    /// The top level value is complete.
+    Done,
+}
+
+/// The nesting and position of a JSON tokenizer, kept separate from the input
+/// so that it can be resumed when more input arrives.
+#[derive(Debug)]
+pub(crate) struct Tokenizer {
+    stack: Vec<Nest>,
+    expect: Expect,
+    /// The absolute offset of `input[0]`.
+    base: usize,
+    line: usize,
+    /// The absolute offset of the start of the current line.
+    line_start: usize,
+    /// How much of an incomplete string token has already been scanned.
+    scanned: usize,
+    /// How much of an incomplete raw value has already been scanned.
+    raw: RawScan,
+    options: DecodeOptions,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Tokenizer::new(DecodeOptions::default())
+    }
+}
+
+/// Whether a token ran off the end of the input, or is really invalid.
+enum Incomplete {
+    NeedMore,
+    Error(ErrorKind, usize),
+}
+
+impl Tokenizer {
+    pub(crate) fn new(options: DecodeOptions) -> Self {
+        Tokenizer {
+            stack: Vec::new(),
+            expect: Expect::Value,
+            base: 0,
+            line: 1,
+            line_start: 0,
+            scanned: 0,
+            raw: RawScan::default(),
+            options,
+        }
+    }
+
+    /// Reads the next event from `input[*pos..]`.
+    ///
+    /// Returns `None` once the top level value is complete. If `eof` is false,
+    /// `None` is also returned when the input ends mid-token, in which case
+    /// `pos` is left at the start of that token.
+    pub(crate) fn next<'a>(
+        &mut self,
+        input: &'a [u8],
+        pos: &mut usize,
+        eof: bool,
+    ) -> Result<Option<Event<'a>>, ParseError> {
+        loop {
+            self.skip_whitespace(input, pos);
+            if self.expect == Expect::Done {
+                return Ok(None);
+            }
+            let Some(&byte) = input.get(*pos) else {
+                return if eof {
+                    Err(self.error(ErrorKind::UnexpectedEof, *pos))
+                } else {
+                    Ok(None)
+                };
+            };
+
+            match (self.expect, byte) {
+                (Expect::Colon, b':') => {
+                    *pos += 1;
+                    self.expect = Expect::Value;
+                }
+                (Expect::Colon, _) => return Err(self.error(ErrorKind::ExpectedColon, *pos)),
+                (Expect::CommaOrEnd, b',') => {
+                    *pos += 1;
+                    self.expect = match self.stack.last() {
+                        Some(Nest::Map) => Expect::Key,
+                        _ => Expect::Value,
+                    };
+                }
+                (Expect::CommaOrEnd | Expect::FirstKey, b'}')
+                    if self.stack.last() == Some(&Nest::Map) =>
+                {
+                    *pos += 1;
+                    self.stack.pop();
+                    self.end_value();
+                    return Ok(Some(Event::MapEnd));
+                }
+                (Expect::CommaOrEnd | Expect::FirstValue, b']')
+                    if self.stack.last() == Some(&Nest::Seq) =>
+                {
+                    *pos += 1;
+                    self.stack.pop();
+                    self.end_value();
+                    return Ok(Some(Event::SeqEnd));
+                }
+                (Expect::CommaOrEnd, _) => {
+                    return Err(self.error(ErrorKind::ExpectedCommaOrEnd, *pos))
+                }
+                (Expect::FirstKey | Expect::Key, b'"') => {
+                    let Some(key) = self.token(input, pos, eof, |input, pos, _, scanned| {
+                        parse_str(input, pos, scanned)
+                    })?
+                    else {
+                        return Ok(None);
+                    };
+                    self.expect = Expect::Colon;
+                    return Ok(Some(Event::Atom(Atom::Str(key))));
+                }
+                (Expect::FirstKey | Expect::Key, _) => {
+                    return Err(self.error(ErrorKind::ExpectedKey, *pos))
+                }
+                (Expect::Value | Expect::FirstValue, _)
+                    if self.options.raw_depth == Some(self.stack.len()) =>
+                {
+                    let Some(atom) = self.raw(input, pos, eof)? else {
+                        return Ok(None);
+                    };
+                    self.end_value();
+                    return Ok(Some(Event::Atom(atom)));
+                }
+                (Expect::Value | Expect::FirstValue, b'{') => {
+                    *pos += 1;
+                    self.stack.push(Nest::Map);
+                    self.expect = Expect::FirstKey;
+                    return Ok(Some(Event::MapStart(None)));
+                }
+                (Expect::Value | Expect::FirstValue, b'[') => {
+                    *pos += 1;
+                    self.stack.push(Nest::Seq);
+                    self.expect = Expect::FirstValue;
+                    return Ok(Some(Event::SeqStart(None)));
+                }
+                (Expect::Value | Expect::FirstValue, _) => {
+                    let Some(atom) = self.token(input, pos, eof, parse_atom)? else {
+                        return Ok(None);
+                    };
+                    self.end_value();
+                    return Ok(Some(Event::Atom(self.convert(atom))));
+                }
+                (Expect::Done, _) => unreachable!(),
+            }
+        }
+    }
+
+    /// Whether the tokenizer is between top level values.
+    pub(crate) fn at_boundary(&self) -> bool {
+        self.stack.is_empty() && matches!(self.expect, Expect::Value | Expect::Done)
+    }
+
+    /// Starts on the next top level value once the previous one is complete.
+    pub(crate) fn reset(&mut self) {
+        if self.expect == Expect::Done {
+            self.expect = Expect::Value;
+        }
+    }
+
+    /// Moves on to the next line, `len` bytes on from the start of this one,
+    /// dropping whatever is left of the current value.
+    pub(crate) fn next_line(&mut self, len: usize) {
+        self.stack.clear();
+        self.expect = Expect::Value;
+        self.scanned = 0;
+        self.raw = RawScan::default();
+        self.base += len;
+        self.line += 1;
+        self.line_start = self.base;
+    }
+
+    /// Records that `n` bytes were removed from the front of the input.
+    pub(crate) fn advance(&mut self, n: usize) {
+        self.base += n;
+    }
+
+    pub(crate) fn skip_whitespace(&mut self, input: &[u8], pos: &mut usize) {
+        while let Some(&byte) = input.get(*pos) {
+            match byte {
+                b' ' | b'\t' | b'\r' => {}
+                b'\n' => {
+                    self.line += 1;
+                    self.line_start = self.base + *pos + 1;
+                }
+                _ => break,
+            }
+            *pos += 1;
+        }
+    }
+
+    pub(crate) fn error(&self, kind: ErrorKind, pos: usize) -> ParseError {
+        let offset = self.base + pos;
+        ParseError {
+            kind,
+            offset,
+            line: self.line,
+            column: offset - self.line_start + 1,
+        }
+    }
+
+    /// Applies the decode options to a value.
+    fn convert<'a>(&self, atom: Atom<'a>) -> Atom<'a> {
+        if let Atom::Number(text) = atom {
+            // the grammar only accepts numbers that rust can parse too
+            let f = text.parse().expect("valid float syntax");
+            return if self.options.exact_numbers && !number::is_exact(&text, f) {
+                Atom::Number(text)
+            } else {
+                // integers that don't fit in 64 bits lose precision
+                Atom::F64(f)
+            };
+        }
+        let Atom::Str(s) = atom else {
+            return atom;
+        };
+        if self.options.hints != Hints::default() {
+            return Atom::Hinted(s, self.options.hints);
+        }
+        Atom::Str(s)
+    }
+
+    /// Reads a whole value as `Atom::RawJson`.
+    fn raw<'a>(
+        &mut self,
+        input: &'a [u8],
+        pos: &mut usize,
+        eof: bool,
+    ) -> Result<Option<Atom<'a>>, ParseError> {
+        let start = *pos;
+        let end = match input[start] {
+            b'{' | b'[' | b'"' => match self.raw.scan(&input[start..]) {
+                Some(len) => start + len,
+                None if eof => return Err(self.error(ErrorKind::UnexpectedEof, input.len())),
+                None => return Ok(None),
+            },
+            // scalars are short, so they are checked like any other
+            _ => match self.token(input, pos, eof, parse_atom)? {
+                Some(_) => *pos,
+                None => return Ok(None),
+            },
+        };
+        self.raw = RawScan::default();
+
+        let raw = std::str::from_utf8(&input[start..end])
+            .map_err(|err| self.error(ErrorKind::InvalidUtf8, start + err.valid_up_to()))?;
+        for (i, _) in raw.match_indices('\n') {
+            self.line += 1;
+            self.line_start = self.base + start + i + 1;
+        }
+        *pos = end;
+        Ok(Some(Atom::RawJson(Cow::Borrowed(raw))))
+    }
+
+    fn end_value(&mut self) {
+        self.expect = if self.stack.is_empty() {
+            Expect::Done
+        } else {
+            Expect::CommaOrEnd
+        };
+    }
+
+    /// Runs a token parser, mapping running out of input to either `None` or an error.
+    fn token<'a, T>(
+        &mut self,
+        input: &'a [u8],
+        pos: &mut usize,
+        eof: bool,
+        parse: fn(&'a [u8], &mut usize, bool, &mut usize) -> Result<T, Incomplete>,
+    ) -> Result<Option<T>, ParseError> {
+        let start = *pos;
+        let res = parse(input, pos, eof, &mut self.scanned);
+        if !matches!(res, Err(Incomplete::NeedMore)) {
+            self.scanned = 0;
+        }
+        match res {
+            Ok(value) => Ok(Some(value)),
+            Err(Incomplete::NeedMore) if !eof => {
+                *pos = start;
+                Ok(None)
+            }
+            Err(Incomplete::NeedMore) => Err(self.error(ErrorKind::UnexpectedEof, input.len())),
+            Err(Incomplete::Error(kind, at)) => Err(self.error(kind, at)),
+        }
+    }
+}
+
+/// The progress through a map, sequence or string that is being read raw.
+#[derive(Debug, Default)]
+struct RawScan {
+    len: usize,
+    depth: usize,
+    in_str: bool,
+    escaped: bool,
+}
+
+impl RawScan {
+    /// Carries on scanning the value at the start of `input`, returning its
+    /// length once it is complete.
+    fn scan(&mut self, input: &[u8]) -> Option<usize> {
+        while let Some(&byte) = input.get(self.len) {
+            self.len += 1;
+            if self.in_str {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_str = false,
+                    _ => {}
+                }
+            } else {
+                match byte {
+                    b'"' => self.in_str = true,
+                    b'{' | b'[' => self.depth += 1,
+                    b'}' | b']' => self.depth -= 1,
+                    _ => {}
+                }
+            }
+            if self.depth == 0 && !self.in_str {
+                return Some(self.len);
+            }
+        }
+        None
+    }
+}
+
+fn parse_atom<'a>(
+    input: &'a [u8],
+    pos: &mut usize,
+    eof: bool,
+    scanned: &mut usize,
+) -> Result<Atom<'a>, Incomplete> {
+    match input[*pos] {
+        b'"' => parse_str(input, pos, scanned).map(Atom::Str),
+        b'n' => parse_ident(input, pos, b"null", Atom::Null),
+        b't' => parse_ident(input, pos, b"true", Atom::Bool(true)),
+        b'f' => parse_ident(input, pos, b"false", Atom::Bool(false)),
+        b'-' | b'0'..=b'9' => parse_number(input, pos, eof),
+        _ => Err(Incomplete::Error(ErrorKind::ExpectedValue, *pos)),
+    }
+}
+
+fn parse_ident<'a>(
+    input: &[u8],
+    pos: &mut usize,
+    ident: &[u8],
+    atom: Atom<'a>,
+) -> Result<Atom<'a>, Incomplete> {
+    let rest = &input[*pos..];
+    if rest.starts_with(ident) {
+        *pos += ident.len();
+        Ok(atom)
+    } else if ident.starts_with(rest) {
+        Err(Incomplete::NeedMore)
+    } else {
+        Err(Incomplete::Error(ErrorKind::ExpectedValue, *pos))
+    }
+}
+
+/// Whether `text` is a whole number in JSON's syntax.
+pub(crate) fn is_number(text: &str) -> bool {
+    let mut pos = 0;
+    !text.is_empty() && parse_number(text.as_bytes(), &mut pos, true).is_ok() && pos == text.len()
+}
+
+fn parse_number<'a>(input: &'a [u8], pos: &mut usize, eof: bool) -> Result<Atom<'a>, Incomplete> {
+    let start = *pos;
+    let mut i = start;
+    let digits = |i: &mut usize| {
+        let from = *i;
+        while input.get(*i).is_some_and(u8::is_ascii_digit) {
+            *i += 1;
+        }
+        *i - from
+    };
+    let invalid = |i: usize| {
+        if i == input.len() && !eof {
+            Incomplete::NeedMore
+        } else {
+            Incomplete::Error(ErrorKind::InvalidNumber, start)
+        }
+    };
+
+    let negative = input[i] == b'-';
+    if negative {
+        i += 1;
+    }
+    match input.get(i) {
+        Some(b'0') => i += 1,
+        Some(b'1'..=b'9') => {
+            digits(&mut i);
+        }
+        _ => return Err(invalid(i)),
+    }
+
+    let mut float = false;
+    if input.get(i) == Some(&b'.') {
+        i += 1;
+        float = true;
+        if digits(&mut i) == 0 {
+            return Err(invalid(i));
+        }
+    }
+    if let Some(b'e' | b'E') = input.get(i) {
+        i += 1;
+        float = true;
+        if let Some(b'+' | b'-') = input.get(i) {
+            i += 1;
+        }
+        if digits(&mut i) == 0 {
+            return Err(invalid(i));
+        }
+    }
+
+    // the number could carry on in the next chunk
+    if i == input.len() && !eof {
+        return Err(Incomplete::NeedMore);
+    }
+    *pos = i;
+
+    // the grammar above only accepts ascii
+    let text = std::str::from_utf8(&input[start..i]).unwrap();
+    if !float {
+        if negative {
+            if let Ok(n) = text.parse() {
+                return Ok(Atom::I64(n));
+            }
+        } else if let Ok(n) = text.parse() {
+            return Ok(Atom::U64(n));
+        }
+    }
+    // turned into a float by `convert`, unless it has to stay exact
+    Ok(Atom::Number(Cow::Borrowed(text)))
+}
+
+/// Parses the string starting at `input[*pos]`.
+///
+/// `scanned` is how far past `*pos` a previous attempt got before the input ran
+/// out, so that a long string arriving in pieces is only scanned once.
+fn parse_str<'a>(
+    input: &'a [u8],
+    pos: &mut usize,
+    scanned: &mut usize,
+) -> Result<Cow<'a, str>, Incomplete> {
+    let start = *pos + 1;
+
+    // find the closing quote
+    let mut end = start.max(*pos + *scanned);
+    loop {
+        let Some(&byte) = input.get(end) else {
+            *scanned = end - *pos;
+            return Err(Incomplete::NeedMore);
+        };
+        match byte {
+            b'"' => break,
+            b'\\' if end + 1 == input.len() => {
+                *scanned = end - *pos;
+                return Err(Incomplete::NeedMore);
+            }
+            b'\\' => end += 2,
+            0..=0x1f => return Err(Incomplete::Error(ErrorKind::ControlCharacter, end)),
+            _ => end += 1,
+        }
+    }
+
+    let s = match input[start..end].iter().position(|&b| b == b'\\') {
+        None => match std::str::from_utf8(&input[start..end]) {
+            Ok(s) => Cow::Borrowed(s),
+            Err(e) => {
+                return Err(Incomplete::Error(
+                    ErrorKind::InvalidUtf8,
+                    start + e.valid_up_to(),
+                ))
+            }
+        },
+        Some(first) => {
+            let mut buf = Vec::with_capacity(end - start);
+            let mut chunk = start;
+            let mut i = start + first;
+            while i < end {
+                if input[i] == b'\\' {
+                    buf.extend_from_slice(&input[chunk..i]);
+                    i = parse_escape(input, i, &mut buf)?;
+                    chunk = i;
+                } else {
+                    i += 1;
+                }
+            }
+            buf.extend_from_slice(&input[chunk..end]);
+            match String::from_utf8(buf) {
+                Ok(s) => Cow::Owned(s),
+                // the offset is not exact once escapes have been expanded
+                Err(_) => return Err(Incomplete::Error(ErrorKind::InvalidUtf8, start)),
+            }
+        }
+    };
+    *pos = end + 1;
+    *scanned = 0;
+    Ok(s)
+}
+
+/// Decodes the escape at `input[i]` into `buf`, returning the index after it.
+fn parse_escape(input: &[u8], i: usize, buf: &mut Vec<u8>) -> Result<usize, Incomplete> {
+    let Some(&byte) = input.get(i + 1) else {
+        return Err(Incomplete::NeedMore);
+    };
+    let unescaped = match byte {
+        b'"' => b'"',
+        b'\\' => b'\\',
+        b'/' => b'/',
+        b'b' => b'\x08',
+        b'f' => b'\x0c',
+        b'n' => b'\n',
+        b'r' => b'\r',
+        b't' => b'\t',
+        b'u' => {
+            let (c, end) = parse_unicode(input, i)?;
+            buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            return Ok(end);
+        }
+        _ => return Err(Incomplete::Error(ErrorKind::InvalidEscape, i)),
+    };
+    buf.push(unescaped);
+    Ok(i + 2)
+}
+
+/// Decodes a `\uXXXX` escape, or a surrogate pair of them, starting at `input[i]`.
+fn parse_unicode(input: &[u8], i: usize) -> Result<(char, usize), Incomplete> {
+    let hex = |at: usize| -> Result<u16, Incomplete> {
+        let Some(digits) = input.get(at..at + 4) else {
+            return Err(Incomplete::NeedMore);
+        };
+        let mut n = 0;
+        for &d in digits {
+            let v = match d {
+                b'0'..=b'9' => d - b'0',
+                b'a'..=b'f' => d - b'a' + 10,
+                b'A'..=b'F' => d - b'A' + 10,
+                _ => return Err(Incomplete::Error(ErrorKind::InvalidEscape, i)),
+            };
+            n = n * 16 + v as u16;
+        }
+        Ok(n)
+    };
+
+    let first = hex(i + 2)?;
+    let mut end = i + 6;
+    let code = match first {
+        0xD800..=0xDBFF => {
+            // a high surrogate must be followed by an escaped low surrogate
+            for (at, expected) in [(end, b'\\'), (end + 1, b'u')] {
+                match input.get(at) {
+                    Some(&byte) if byte == expected => {}
+                    Some(_) => return Err(Incomplete::Error(ErrorKind::InvalidUnicode, i)),
+                    None => return Err(Incomplete::NeedMore),
+                }
+            }
+            let second = hex(end + 2)?;
+            if !(0xDC00..=0xDFFF).contains(&second) {
+                return Err(Incomplete::Error(ErrorKind::InvalidUnicode, i));
+            }
+            end += 6;
+            0x10000 + ((first as u32 - 0xD800) << 10) + (second as u32 - 0xDC00)
+        }
+        0xDC00..=0xDFFF => return Err(Incomplete::Error(ErrorKind::InvalidUnicode, i)),
+        _ => first as u32,
+    };
+    match char::from_u32(code) {
+        Some(c) => Ok((c, end)),
+        None => Err(Incomplete::Error(ErrorKind::InvalidUnicode, i)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use bytes::BytesMut;
+    use sud_core::{
+        de::from_events,
+        path::Path,
+        pointer::{Pointer, Selector},
+        Atom, Event, Value,
+    };
+    use tokio_util::codec::Decoder;
+
+    use super::{ErrorKind, JsonParser, ParseError};
+    use crate::{DecodeOptions, JsonDecoder};
+
+    fn parse(input: &str) -> Result<Vec<Event<'_>>, ParseError> {
+        JsonParser::new(input).collect()
+    }
+
+    fn str(s: &str) -> Event<'_> {
+        Event::Atom(Atom::Str(Cow::Borrowed(s)))
+    }
+
+    #[test]
+    fn document() {
+        let events =
+            parse(r#" {"a": [1, -2, 3.5, true, false, null], "b": {}, "c": []} "#).unwrap();
+        assert_eq!(
+            events,
+            [
+                Event::MapStart(None),
+                str("a"),
+                Event::SeqStart(None),
+                Event::Atom(Atom::U64(1)),
+                Event::Atom(Atom::I64(-2)),
+                Event::Atom(Atom::F64(3.5)),
+                Event::Atom(Atom::Bool(true)),
+                Event::Atom(Atom::Bool(false)),
+                Event::Atom(Atom::Null),
+                Event::SeqEnd,
+                str("b"),
+                Event::MapStart(None),
+                Event::MapEnd,
+                str("c"),
+                Event::SeqStart(None),
+                Event::SeqEnd,
+                Event::MapEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(parse("0").unwrap(), [Event::Atom(Atom::U64(0))]);
+        assert_eq!(parse("-0.0").unwrap(), [Event::Atom(Atom::F64(-0.0))]);
+        assert_eq!(parse("1e3").unwrap(), [Event::Atom(Atom::F64(1000.0))]);
+        assert_eq!(
+            parse("18446744073709551615").unwrap(),
+            [Event::Atom(Atom::U64(u64::MAX))]
+        );
+        assert_eq!(
+            parse("18446744073709551616").unwrap(),
+            [Event::Atom(Atom::F64(18446744073709551616.0))]
+        );
+        assert_eq!(parse(r#""""#).unwrap(), [str("")]);
+    }
+
+    #[test]
+    fn strings() {
+        let events = parse(r#"["plain", "esc\"aped\n", "é😀", "ünï"]"#).unwrap();
+        assert!(matches!(
+            events[1],
+            Event::Atom(Atom::Str(Cow::Borrowed("plain")))
+        ));
+        assert_eq!(
+            events[2],
+            Event::Atom(Atom::Str(Cow::Owned("esc\"aped\n".to_owned())))
+        );
+        assert!(matches!(events[2], Event::Atom(Atom::Str(Cow::Owned(_)))));
+        assert_eq!(events[3], str("é😀"));
+        assert!(matches!(
+            events[4],
+            Event::Atom(Atom::Str(Cow::Borrowed("ünï")))
+        ));
+    }
+
+    #[test]
+    fn into_value() {
+        let value: Value =
+            from_events(JsonParser::new(r#"{"a":[1,"x"]}"#).map(Result::unwrap)).unwrap();
+        assert_eq!(
+            value,
+            Value::Map(vec![(
+                Value::Str("a".to_owned()),
+                Value::Seq(vec![Value::U64(1), Value::Str("x".to_owned())])
+            )])
+        );
+    }
+
+    #[track_caller]
+    fn assert_error(input: &str, kind: ErrorKind, line: usize, column: usize) {
+        let err = parse(input).unwrap_err();
+        assert_eq!(
+            (*err.kind(), err.line(), err.column()),
+            (kind, line, column),
+            "{input:?}"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_error("", ErrorKind::UnexpectedEof, 1, 1);
+        assert_error("[1,]", ErrorKind::ExpectedValue, 1, 4);
+        assert_error("[1 2]", ErrorKind::ExpectedCommaOrEnd, 1, 4);
+        assert_error("{1:2}", ErrorKind::ExpectedKey, 1, 2);
+        assert_error(r#"{"a" 2}"#, ErrorKind::ExpectedColon, 1, 6);
+        assert_error("[1}", ErrorKind::ExpectedCommaOrEnd, 1, 3);
+        assert_error("01", ErrorKind::TrailingCharacters, 1, 2);
+        assert_error("-", ErrorKind::InvalidNumber, 1, 1);
+        assert_error("1.", ErrorKind::InvalidNumber, 1, 1);
+        assert_error("tru", ErrorKind::UnexpectedEof, 1, 4);
+        assert_error("nul!", ErrorKind::ExpectedValue, 1, 1);
+        assert_error(r#""\x""#, ErrorKind::InvalidEscape, 1, 2);
+        assert_error(r#""\ud83d""#, ErrorKind::InvalidUnicode, 1, 2);
+        assert_error("\"a\tb\"", ErrorKind::ControlCharacter, 1, 3);
+        assert_error("[\n  1,\n  {\"a\": }\n]", ErrorKind::ExpectedValue, 3, 9);
+        assert_error("\"abc", ErrorKind::UnexpectedEof, 1, 5);
+
+        let err = parse("[\n  1,\n  {\"a\": }\n]").unwrap_err();
+        assert_eq!(err.offset(), 15);
+        assert_eq!(err.to_string(), "expected value at line 3 column 9");
+
+        let err = JsonParser::new(b"\"\xff\"").collect::<Result<Vec<_>, _>>();
+        assert_eq!(*err.unwrap_err().kind(), ErrorKind::InvalidUtf8);
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let mut parser = JsonParser::new("[1,]");
+        assert!(parser.next().unwrap().is_ok());
+        assert!(parser.next().unwrap().is_ok());
+        assert!(parser.next().unwrap().is_err());
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn raw_values() {
+        const DOC: &str = "{\"id\": 7, \"payload\": {\"b\": [1, \"x]}\\\"\"],\n \"c\": {}}, \"s\": \"q\\\\\"}\n[true]";
+        let raw = |s| Event::Atom(Atom::RawJson(Cow::Borrowed(s)));
+        let expected = [
+            Event::MapStart(None),
+            str("id"),
+            raw("7"),
+            str("payload"),
+            raw("{\"b\": [1, \"x]}\\\"\"],\n \"c\": {}}"),
+            str("s"),
+            raw("\"q\\\\\""),
+            Event::MapEnd,
+        ];
+        let options = DecodeOptions::new().raw_depth(1);
+        let mut parser = JsonParser::with_options(DOC, options.clone());
+        let events: Vec<_> = parser.by_ref().take(8).map(Result::unwrap).collect();
+        assert_eq!(events, expected);
+        // lines are still counted through raw values
+        let err = parser.next().unwrap().unwrap_err();
+        assert_eq!((err.line(), err.column()), (3, 1));
+
+        // resumed across chunks
+        let chunked = |size: usize| {
+            let mut decoder = JsonDecoder::with_options(options.clone());
+            let mut buf = BytesMut::new();
+            let mut events = Vec::new();
+            for chunk in DOC.as_bytes().chunks(size) {
+                buf.extend_from_slice(chunk);
+                while let Some(event) = decoder.decode(&mut buf).unwrap() {
+                    events.push(event);
+                }
+            }
+            events
+        };
+        for size in 1..=DOC.len() {
+            assert_eq!(chunked(size)[..8], expected, "chunk size {size}");
+        }
+
+        let options = DecodeOptions::new().raw_depth(0);
+        let events: Vec<_> = JsonParser::with_options("[1, {\"a\": 2}] ", options.clone())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(events, [raw("[1, {\"a\": 2}]")]);
+        let err = JsonParser::with_options("[1, [2]", options.clone())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::UnexpectedEof);
+        let err = JsonParser::with_options("nul!", options)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::ExpectedValue);
+    }
+
+    #[test]
+    fn pointer() {
+        // the document is only parsed as far as the selected value
+        const DOC: &str = "{\"items\": [{\"name\": \"a\"}, {\"id\": [1], \"name\": \"b\"}], oops";
+        let pointer = Pointer::parse("/items/1/name").unwrap();
+        let events: Result<Vec<_>, _> = pointer.select(JsonParser::new(DOC)).collect();
+        assert_eq!(events.unwrap(), [str("b")]);
+
+        // or over a stream of values, selecting from each one
+        let mut decoder = JsonDecoder::new();
+        let mut selector = Selector::new(Pointer::parse("/0").unwrap());
+        let mut buf = BytesMut::from("[1, 2] {\"0\": [3]} [] [4]");
+        let mut events = Vec::new();
+        while let Some(event) = decoder.decode_eof(&mut buf).unwrap() {
+            events.extend(selector.push(event));
+        }
+        assert_eq!(
+            events,
+            [
+                Event::Atom(Atom::U64(1)),
+                Event::SeqStart(None),
+                Event::Atom(Atom::U64(3)),
+                Event::SeqEnd,
+                Event::Atom(Atom::U64(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn path() {
+        const DOC: &str =
+            r#"{"events": [{"error": "a"}, {"ok": {"error": null}}], "error": ["b"]}"#;
+        let path = Path::parse("$..error").unwrap();
+        let matches: Result<Vec<_>, _> = path.query(JsonParser::new(DOC)).collect();
+        assert_eq!(
+            matches.unwrap(),
+            [
+                vec![str("a")],
+                vec![Event::Atom(Atom::Null)],
+                vec![Event::SeqStart(None), str("b"), Event::SeqEnd],
+            ]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::{borrow::Cow, io};
+
+/// How [`JsonEncoderBuilder::pretty`](crate::JsonEncoderBuilder::pretty) lays out its output.
+///
+/// The layout is decided one event at a time, so pretty output streams just
+/// like compact output does. Empty containers are only known to be empty once
+/// they end, so collapsing them costs nothing either.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrettyConfig {
+    indent: Cow<'static, str>,
+    space_after_colon: bool,
+    newline: Newline,
+    collapse_empty: bool,
+}
+
+/// The line ending written between entries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Newline {
+    Lf,
+    CrLf,
+}
+
+impl Default for PrettyConfig {
+    fn default() -> Self {
+        PrettyConfig {
+            indent: Cow::Borrowed("  "),
+            space_after_colon: true,
+            newline: Newline::Lf,
+            collapse_empty: true,
+        }
+    }
+}
+
+impl PrettyConfig {
+    /// Two space indents, `": "` between keys and values, and `\n` line endings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The string written once per level of nesting at the start of each line.
+    pub fn indent(mut self, indent: impl Into<Cow<'static, str>>) -> Self {
+        self.indent = indent.into();
+        self
+    }
+
+    pub fn space_after_colon(mut self, space: bool) -> Self {
+        self.space_after_colon = space;
+        self
+    }
+
+    pub fn newline(mut self, newline: Newline) -> Self {
+        self.newline = newline;
+        self
+    }
+
+    /// Whether empty containers are written as `[]` and `{}` rather than being
+    /// split over two lines.
+    pub fn collapse_empty(mut self, collapse: bool) -> Self {
+        self.collapse_empty = collapse;
+        self
+    }
+
+    pub(crate) fn write_colon<W: io::Write>(&self, mut dst: W) -> io::Result<()> {
+        dst.write_all(if self.space_after_colon { b": " } else { b":" })
+    }
+
+    /// Starts a new line, indented to `depth`.
+    pub(crate) fn write_line<W: io::Write>(&self, mut dst: W, depth: usize) -> io::Result<()> {
+        dst.write_all(match self.newline {
+            Newline::Lf => b"\n",
+            Newline::CrLf => b"\r\n",
+        })?;
+        for _ in 0..depth {
+            dst.write_all(self.indent.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Whether a container that is ending needs its own line.
+    pub(crate) fn break_before_end(&self, empty: bool) -> bool {
+        !(empty && self.collapse_empty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future::poll_fn, pin::pin, task::Poll};
+
+    use futures_util::{sink::Sink, SinkExt};
+    use sud_core::{tri, Serializer, Value};
+    use tokio_util::codec::FramedWrite;
+
+    use super::{Newline, PrettyConfig};
+    use crate::{JsonEncoder, JsonSerializer};
+
+    fn value() -> Value {
+        Value::Map(vec![
+            (
+                Value::Str("a".to_owned()),
+                Value::Seq(vec![Value::U64(1), Value::Seq(vec![])]),
+            ),
+            (Value::Str("b".to_owned()), Value::Map(vec![])),
+            (
+                Value::Str("c".to_owned()),
+                Value::Map(vec![(Value::Str("d".to_owned()), Value::Null)]),
+            ),
+        ])
+    }
+
+    fn pretty(value: &Value, config: PrettyConfig) -> String {
+        let mut serializer = JsonSerializer::pretty(Vec::new(), config);
+        value
+            .try_for_each_event(&mut value.get_state(), |event| serializer.write(event))
+            .unwrap();
+        assert_eq!(serializer.enc.stack, &[]);
+        String::from_utf8(serializer.out).unwrap()
+    }
+
+    #[test]
+    fn default() {
+        assert_eq!(
+            pretty(&value(), PrettyConfig::new()),
+            "{\n  \"a\": [\n    1,\n    []\n  ],\n  \"b\": {},\n  \"c\": {\n    \"d\": null\n  }\n}"
+        );
+        assert_eq!(pretty(&Value::U64(1), PrettyConfig::new()), "1");
+        assert_eq!(pretty(&Value::Seq(vec![]), PrettyConfig::new()), "[]");
+    }
+
+    #[test]
+    fn configured() {
+        let config = PrettyConfig::new()
+            .indent("\t")
+            .space_after_colon(false)
+            .newline(Newline::CrLf)
+            .collapse_empty(false);
+        assert_eq!(
+            pretty(&value(), config),
+            "{\r\n\t\"a\":[\r\n\t\t1,\r\n\t\t[\r\n\t\t]\r\n\t],\r\n\t\"b\":{\r\n\t},\r\n\t\"c\":{\r\n\t\t\"d\":null\r\n\t}\r\n}"
+        );
+    }
+
+    #[tokio::test]
+    async fn framed_write() {
+        let value = value();
+        let encoder = JsonEncoder::builder().pretty(PrettyConfig::new()).build();
+        let mut serializer = pin!(FramedWrite::new(Vec::new(), encoder));
+
+        let mut state = value.get_state();
+        poll_fn(|cx| {
+            value.try_for_each_event(&mut state, |event| {
+                tri!(serializer.as_mut().poll_ready(cx));
+                Poll::Ready(serializer.as_mut().start_send(event))
+            })
+        })
+        .await
+        .unwrap();
+        serializer.close().await.unwrap();
+
+        let output = String::from_utf8(serializer.get_ref().to_owned()).unwrap();
+        assert_eq!(output, pretty(&value, PrettyConfig::new()));
+    }
+}
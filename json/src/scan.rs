@@ -0,0 +1,243 @@
+/// Finds the next byte of a string that might need escaping, many bytes at a time.
+///
+/// Most of a typical string is written out unchanged, so the escape table is
+/// only consulted at the bytes this stops on. It may stop early, but never
+/// skips over a byte that the table escapes.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Scan {
+    /// Stop on `<`, `>` and `&`.
+    html: bool,
+    /// Stop on every byte outside of ASCII.
+    high: bool,
+}
+
+impl Scan {
+    pub(crate) fn new(table: &[u8; 256]) -> Self {
+        Scan {
+            html: table[b'<' as usize] != 0,
+            high: table[0x80..].iter().any(|&escape| escape != 0),
+        }
+    }
+
+    fn stops_at(self, byte: u8) -> bool {
+        byte < 0x20
+            || byte == b'"'
+            || byte == b'\\'
+            || (self.html && matches!(byte, b'<' | b'>' | b'&'))
+            || (self.high && byte >= 0x80)
+    }
+
+    /// The number of bytes at the start of `bytes` that need no escaping.
+    pub(crate) fn skip(self, bytes: &[u8]) -> usize {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if std::is_x86_feature_detected!("avx2") {
+                // SAFETY: avx2 is available
+                return unsafe { self.skip_avx2(bytes) };
+            }
+            // SAFETY: sse2 is part of x86_64
+            unsafe { self.skip_sse2(bytes) }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            // SAFETY: neon is part of aarch64
+            unsafe { self.skip_neon(bytes) }
+        }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            self.skip_swar(bytes)
+        }
+    }
+
+    fn skip_scalar(self, bytes: &[u8]) -> usize {
+        bytes
+            .iter()
+            .position(|&b| self.stops_at(b))
+            .unwrap_or(bytes.len())
+    }
+
+    /// Scans 8 bytes at a time in a `u64`.
+    #[cfg_attr(any(target_arch = "x86_64", target_arch = "aarch64"), allow(dead_code))]
+    fn skip_swar(self, bytes: &[u8]) -> usize {
+        const ONES: u64 = u64::from_ne_bytes([0x01; 8]);
+        const HIGHS: u64 = u64::from_ne_bytes([0x80; 8]);
+        // the high bit of every byte below `n`. Bytes after the first match can
+        // be flagged wrongly by the borrow, so only the lowest match is exact.
+        let below = |x: u64, n: u8| x.wrapping_sub(ONES * n as u64) & !x & HIGHS;
+        let equal = |x: u64, b: u8| below(x ^ (ONES * b as u64), 1);
+
+        let mut chunks = bytes.chunks_exact(8);
+        for (i, chunk) in chunks.by_ref().enumerate() {
+            let x = u64::from_le_bytes(chunk.try_into().unwrap());
+            let mut found = below(x, 0x20) | equal(x, b'"') | equal(x, b'\\');
+            if self.html {
+                found |= equal(x, b'<') | equal(x, b'>') | equal(x, b'&');
+            }
+            if self.high {
+                found |= x & HIGHS;
+            }
+            if found != 0 {
+                return i * 8 + found.trailing_zeros() as usize / 8;
+            }
+        }
+        let rest = chunks.remainder();
+        bytes.len() - rest.len() + self.skip_scalar(rest)
+    }
+
+    /// Scans 16 bytes at a time.
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "sse2")]
+    unsafe fn skip_sse2(self, bytes: &[u8]) -> usize {
+        use std::arch::x86_64::*;
+
+        let splat = |b: u8| _mm_set1_epi8(b as i8);
+        let mut chunks = bytes.chunks_exact(16);
+        for (i, chunk) in chunks.by_ref().enumerate() {
+            let x = _mm_loadu_si128(chunk.as_ptr().cast());
+            // there is no unsigned compare, but `min(x, 0x1f) == x` is `x < 0x20`
+            let mut found = _mm_cmpeq_epi8(_mm_min_epu8(x, splat(0x1f)), x);
+            found = _mm_or_si128(found, _mm_cmpeq_epi8(x, splat(b'"')));
+            found = _mm_or_si128(found, _mm_cmpeq_epi8(x, splat(b'\\')));
+            if self.html {
+                found = _mm_or_si128(found, _mm_cmpeq_epi8(x, splat(b'<')));
+                found = _mm_or_si128(found, _mm_cmpeq_epi8(x, splat(b'>')));
+                found = _mm_or_si128(found, _mm_cmpeq_epi8(x, splat(b'&')));
+            }
+            if self.high {
+                found = _mm_or_si128(found, x);
+            }
+            let mask = _mm_movemask_epi8(found);
+            if mask != 0 {
+                return i * 16 + mask.trailing_zeros() as usize;
+            }
+        }
+        let rest = chunks.remainder();
+        bytes.len() - rest.len() + self.skip_scalar(rest)
+    }
+
+    /// Scans 32 bytes at a time.
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn skip_avx2(self, bytes: &[u8]) -> usize {
+        use std::arch::x86_64::*;
+
+        let splat = |b: u8| _mm256_set1_epi8(b as i8);
+        let mut chunks = bytes.chunks_exact(32);
+        for (i, chunk) in chunks.by_ref().enumerate() {
+            let x = _mm256_loadu_si256(chunk.as_ptr().cast());
+            let mut found = _mm256_cmpeq_epi8(_mm256_min_epu8(x, splat(0x1f)), x);
+            found = _mm256_or_si256(found, _mm256_cmpeq_epi8(x, splat(b'"')));
+            found = _mm256_or_si256(found, _mm256_cmpeq_epi8(x, splat(b'\\')));
+            if self.html {
+                found = _mm256_or_si256(found, _mm256_cmpeq_epi8(x, splat(b'<')));
+                found = _mm256_or_si256(found, _mm256_cmpeq_epi8(x, splat(b'>')));
+                found = _mm256_or_si256(found, _mm256_cmpeq_epi8(x, splat(b'&')));
+            }
+            if self.high {
+                found = _mm256_or_si256(found, x);
+            }
+            let mask = _mm256_movemask_epi8(found);
+            if mask != 0 {
+                return i * 32 + mask.trailing_zeros() as usize;
+            }
+        }
+        // the tail can still fill a 16 byte chunk
+        let rest = chunks.remainder();
+        bytes.len() - rest.len() + self.skip_sse2(rest)
+    }
+
+    /// Scans 16 bytes at a time.
+    #[cfg(target_arch = "aarch64")]
+    #[target_feature(enable = "neon")]
+    unsafe fn skip_neon(self, bytes: &[u8]) -> usize {
+        use std::arch::aarch64::*;
+
+        let mut chunks = bytes.chunks_exact(16);
+        for (i, chunk) in chunks.by_ref().enumerate() {
+            let x = vld1q_u8(chunk.as_ptr());
+            let mut found = vcltq_u8(x, vdupq_n_u8(0x20));
+            found = vorrq_u8(found, vceqq_u8(x, vdupq_n_u8(b'"')));
+            found = vorrq_u8(found, vceqq_u8(x, vdupq_n_u8(b'\\')));
+            if self.html {
+                found = vorrq_u8(found, vceqq_u8(x, vdupq_n_u8(b'<')));
+                found = vorrq_u8(found, vceqq_u8(x, vdupq_n_u8(b'>')));
+                found = vorrq_u8(found, vceqq_u8(x, vdupq_n_u8(b'&')));
+            }
+            if self.high {
+                found = vorrq_u8(found, vcgeq_u8(x, vdupq_n_u8(0x80)));
+            }
+            // there is no movemask, so the match is found again within the chunk
+            if vmaxvq_u8(found) != 0 {
+                return i * 16 + self.skip_scalar(chunk);
+            }
+        }
+        let rest = chunks.remainder();
+        bytes.len() - rest.len() + self.skip_scalar(rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scan;
+    use crate::{ASCII_ESCAPE, ESCAPE, HTML_ASCII_ESCAPE, HTML_ESCAPE};
+
+    const TABLES: [&[u8; 256]; 4] = [&ESCAPE, &HTML_ESCAPE, &ASCII_ESCAPE, &HTML_ASCII_ESCAPE];
+
+    #[test]
+    fn covers_tables() {
+        // skipping only bytes that the table leaves alone keeps the output the
+        // same as checking every byte against the table
+        for table in TABLES {
+            let scan = Scan::new(table);
+            for byte in 0..=255 {
+                if table[byte as usize] != 0 {
+                    assert!(scan.stops_at(byte), "{byte:#x}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn differential() {
+        // a small LCG, so that the inputs are the same every run
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+        let interesting = [
+            0x00, 0x1f, 0x20, b'"', b'\\', b'<', b'>', b'&', 0x7f, 0x80, 0xe2, 0xff,
+        ];
+
+        for _ in 0..2000 {
+            let len = next() % 100;
+            let mut bytes: Vec<u8> = (0..len).map(|_| b'a' + (next() % 26) as u8).collect();
+            // mostly plain text, with the odd byte to stop on
+            for _ in 0..next() % 3 {
+                if len > 0 {
+                    bytes[next() % len] = interesting[next() % interesting.len()];
+                }
+            }
+
+            for table in TABLES {
+                let scan = Scan::new(table);
+                for start in 0..len.min(33) {
+                    let bytes = &bytes[start..];
+                    let expected = scan.skip_scalar(bytes);
+                    assert_eq!(scan.skip(bytes), expected, "{bytes:x?} {scan:?}");
+                    assert_eq!(scan.skip_swar(bytes), expected, "{bytes:x?} {scan:?}");
+                    #[cfg(target_arch = "x86_64")]
+                    // SAFETY: sse2 is part of x86_64, and avx2 is checked
+                    unsafe {
+                        assert_eq!(scan.skip_sse2(bytes), expected, "{bytes:x?} {scan:?}");
+                        if std::is_x86_feature_detected!("avx2") {
+                            assert_eq!(scan.skip_avx2(bytes), expected, "{bytes:x?} {scan:?}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
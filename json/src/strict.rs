@@ -0,0 +1,227 @@
+use std::fmt;
+
+use sud_core::{Atom, Event};
+
+use crate::{JsonEncoder, JsonParser, MapKeys, Object, State};
+
+/// An event that would make a strict [`JsonEncoder`] write invalid JSON.
+///
+/// It is returned as an [`io::ErrorKind::InvalidData`](std::io::ErrorKind::InvalidData)
+/// error wrapping this value, and nothing is written for the offending event.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum EncodeError {
+    /// A map key that is not a string.
+    NonStringKey,
+    /// A `MapEnd` or `SeqEnd` outside of any container.
+    UnbalancedEnd,
+    /// A `MapEnd` closing a sequence, or a `SeqEnd` closing a map.
+    MismatchedEnd,
+    /// A `MapEnd` straight after a key.
+    MissingMapValue,
+    /// A second value after the top level value is complete.
+    MultipleValues,
+    /// A NaN or infinite float, with [`NonFinite::Error`](crate::NonFinite::Error).
+    NonFiniteFloat,
+    /// A line break inside an `Atom::RawJson`, with
+    /// [`json_lines`](crate::JsonEncoderBuilder::json_lines).
+    RawNewline,
+    /// An `Atom::RawJson` that is not exactly one well-formed JSON value.
+    InvalidRawJson,
+    /// An `Atom::Number` that is not in JSON's number syntax.
+    InvalidNumber,
+    /// A key that is already in the map, with
+    /// [`canonical`](crate::JsonEncoderBuilder::canonical) output.
+    DuplicateKey,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EncodeError::NonStringKey => "map key must be a string",
+            EncodeError::UnbalancedEnd => "end of container without a start",
+            EncodeError::MismatchedEnd => "end of container does not match its start",
+            EncodeError::MissingMapValue => "map key without a value",
+            EncodeError::MultipleValues => "more than one top level value",
+            EncodeError::NonFiniteFloat => "float is not finite",
+            EncodeError::RawNewline => "line break inside a JSON Lines value",
+            EncodeError::InvalidRawJson => "raw JSON is not a single valid value",
+            EncodeError::InvalidNumber => "number is not valid JSON",
+            EncodeError::DuplicateKey => "duplicate map key",
+        })
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+impl JsonEncoder {
+    /// Checks that `event` can follow the events written so far.
+    pub(crate) fn validate(&self, event: &Event<'_>) -> Result<(), EncodeError> {
+        let object = self.stack.last().map(|State { object, .. }| *object);
+        match (event, object) {
+            (Event::MapEnd | Event::SeqEnd, None) => Err(EncodeError::UnbalancedEnd),
+            (Event::MapEnd, Some(Object::Value)) => Err(EncodeError::MismatchedEnd),
+            (Event::SeqEnd, Some(Object::MapKey | Object::MapValue)) => {
+                Err(EncodeError::MismatchedEnd)
+            }
+            (Event::MapEnd, Some(Object::MapValue)) => Err(EncodeError::MissingMapValue),
+            (Event::MapEnd | Event::SeqEnd, _) => Ok(()),
+            (_, None) if self.finished => Err(EncodeError::MultipleValues),
+            (Event::Atom(Atom::RawJson(raw)), _) if !is_json_value(raw) => {
+                Err(EncodeError::InvalidRawJson)
+            }
+            (Event::Atom(Atom::Str(_) | Atom::Hinted(..) | Atom::Char(_)), _) => Ok(()),
+            (_, Some(Object::MapKey)) if self.options.map_keys == MapKeys::Verbatim => {
+                Err(EncodeError::NonStringKey)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether `raw` is exactly one well-formed JSON value.
+fn is_json_value(raw: &str) -> bool {
+    let mut depth = 0usize;
+    let mut values = 0;
+    for event in JsonParser::new(raw) {
+        match event {
+            Err(_) => return false,
+            Ok(Event::MapStart(_) | Event::SeqStart(_)) => {
+                values += usize::from(depth == 0);
+                depth += 1;
+            }
+            Ok(Event::MapEnd | Event::SeqEnd) => depth -= 1,
+            Ok(Event::Atom(_)) => values += usize::from(depth == 0),
+        }
+    }
+    values == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use sud_core::{Atom, Event};
+
+    use super::EncodeError;
+    use crate::{JsonEncoder, JsonSerializer, MapKeys};
+
+    /// Writes every event, returning the first error and the output so far.
+    fn write_strict(
+        events: impl IntoIterator<Item = Event<'static>>,
+    ) -> (String, Option<EncodeError>) {
+        let encoder = JsonEncoder::builder()
+            .strict(true)
+            .map_keys(MapKeys::Verbatim)
+            .build();
+        let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+        let err = events
+            .into_iter()
+            .try_for_each(|event| serializer.write(event))
+            .err()
+            .map(|err| {
+                assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+                *err.get_ref()
+                    .unwrap()
+                    .downcast_ref::<EncodeError>()
+                    .unwrap()
+            });
+        (String::from_utf8(serializer.out).unwrap(), err)
+    }
+
+    #[test]
+    fn valid() {
+        let (output, err) = write_strict([
+            Event::MapStart(None),
+            Event::from("a"),
+            Event::SeqStart(None),
+            Event::Atom(Atom::Char('b')),
+            Event::SeqEnd,
+            Event::Atom(Atom::Char('c')),
+            Event::MapStart(None),
+            Event::MapEnd,
+            Event::from("d"),
+            Event::Atom(Atom::RawJson(r#"[1, {"e": null}]"#.into())),
+            Event::MapEnd,
+        ]);
+        assert_eq!(err, None);
+        assert_eq!(output, r#"{"a":["b"],"c":{},"d":[1, {"e": null}]}"#);
+    }
+
+    #[test]
+    fn errors() {
+        let key = Event::from("a");
+        let cases = [
+            (
+                vec![Event::MapStart(None), Event::Atom(Atom::U64(1))],
+                "{",
+                EncodeError::NonStringKey,
+            ),
+            (
+                vec![Event::MapStart(None), Event::SeqStart(None)],
+                "{",
+                EncodeError::NonStringKey,
+            ),
+            (vec![Event::SeqEnd], "", EncodeError::UnbalancedEnd),
+            (
+                vec![Event::SeqStart(None), Event::SeqEnd, Event::MapEnd],
+                "[]",
+                EncodeError::UnbalancedEnd,
+            ),
+            (
+                vec![Event::SeqStart(None), Event::MapEnd],
+                "[",
+                EncodeError::MismatchedEnd,
+            ),
+            (
+                vec![Event::MapStart(None), Event::SeqEnd],
+                "{",
+                EncodeError::MismatchedEnd,
+            ),
+            (
+                vec![Event::MapStart(None), key.clone(), Event::MapEnd],
+                r#"{"a""#,
+                EncodeError::MissingMapValue,
+            ),
+            (
+                vec![Event::Atom(Atom::U64(1)), Event::Atom(Atom::U64(2))],
+                "1",
+                EncodeError::MultipleValues,
+            ),
+            (
+                vec![Event::Atom(Atom::RawJson("1 2".into()))],
+                "",
+                EncodeError::InvalidRawJson,
+            ),
+            (
+                vec![
+                    Event::SeqStart(None),
+                    Event::Atom(Atom::RawJson(r#"{"a":"#.into())),
+                ],
+                "[",
+                EncodeError::InvalidRawJson,
+            ),
+            (
+                vec![Event::Atom(Atom::RawJson("".into()))],
+                "",
+                EncodeError::InvalidRawJson,
+            ),
+        ];
+
+        for (events, output, err) in cases {
+            assert_eq!(write_strict(events), (output.to_owned(), Some(err)));
+        }
+
+        // without strict mode, the same mistakes are written out as they are
+        let encoder = JsonEncoder::builder().map_keys(MapKeys::Verbatim).build();
+        let mut serializer = JsonSerializer::with_encoder(Vec::new(), encoder);
+        for event in [
+            Event::MapStart(None),
+            Event::Atom(Atom::U64(1)),
+            Event::MapEnd,
+        ] {
+            serializer.write(event).unwrap();
+        }
+        assert_eq!(serializer.out, b"{1}");
+    }
+}